@@ -103,5 +103,10 @@ pub fn arbitrary_part_body(u: &mut Unstructured) -> Result<Vec<u8>> {
     if let Some(b'\r') = body.last() {
         body.push(b'X')
     }
+    // bodies with overlong lines are re-encoded when printed, which changes the
+    // AST; avoid them
+    if crate::print::has_long_line(&body, 0) {
+        return Err(arbitrary::Error::IncorrectFormat);
+    }
     Ok(body)
 }
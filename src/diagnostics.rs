@@ -0,0 +1,564 @@
+use nom::{combinator::consumed, IResult, Parser};
+use std::cell::RefCell;
+use std::ops::Range;
+
+use crate::header::{DuplicateField, FieldRaw};
+use crate::message::Message;
+use crate::mime::mechanism::Mechanism;
+use crate::part::MimeBody;
+use crate::print::long_lines;
+use crate::raw_input::{addresses, range_in};
+
+/// How bad a diagnostic is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The input is not compliant, but we recovered its meaning.
+    Warning,
+    /// The input is not compliant and could not be interpreted: it was
+    /// dropped, or kept as opaque data.
+    Error,
+}
+
+/// What a diagnostic is about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DiagnosticKind {
+    /// Bytes of a header section that are not a header field. They are
+    /// dropped.
+    MalformedHeaderLine,
+    /// A header field that may only appear once is repeated. See
+    /// `header::DuplicatePolicy`.
+    DuplicateField,
+    /// A repeated To, Cc or Bcc field. Its addresses are merged into the first
+    /// occurrence of the field, where they are printed.
+    MergedField,
+    /// A trace field (e.g. Received or ARC-Seal) that comes after other IMF
+    /// fields (RFC 5322, section 3.6.7). The field is kept as a
+    /// `header::Invalid` entry.
+    MisplacedField,
+    /// The body of a header field could not be interpreted. The field is kept
+    /// as a `header::Invalid` entry.
+    InvalidField,
+    /// Data after the end of a multipart body. It is dropped.
+    TrailingData,
+    /// Non-compliant syntax, which we recovered from.
+    Recovered(&'static str),
+    /// Unsupported syntax, which was skipped.
+    Unsupported(&'static str),
+    /// Obsolete syntax, which must be accepted but must not be generated. It is
+    /// named after the corresponding RFC grammar rule, e.g. `obs-zone`.
+    Obsolete(&'static str),
+    /// A mandatory header field is missing (see `validate`).
+    MissingField(&'static str),
+    /// A line is longer than 998 octets (see `validate`).
+    LineTooLong,
+    /// A CR or LF that is not part of a CRLF (see `validate`).
+    BareLineEnding,
+    /// 8-bit data in a header field, or in a part whose transfer encoding only
+    /// allows 7-bit data (see `validate`).
+    EightBitData,
+}
+
+/// A recovery decision made while parsing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub severity: Severity,
+    /// Name of the header field in which the problem was found, if any.
+    pub header: Option<String>,
+    /// Byte range in the input, if known. This is the range of the offending
+    /// bytes when we know them precisely, or else the range of the whole
+    /// header field. It is `None` for data that is not part of the input,
+    /// e.g. a message nested in a base64-encoded part.
+    pub range: Option<Range<usize>>,
+}
+
+struct Collector {
+    input: Range<usize>,
+    field: Option<(String, Option<Range<usize>>)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Collector {
+    fn range_of(&self, s: &[u8]) -> Option<Range<usize>> {
+        range_in(self.input.clone(), s)
+    }
+}
+
+thread_local! {
+    static COLLECTOR: RefCell<Option<Collector>> = const { RefCell::new(None) };
+}
+
+// Runs `f`, collecting the diagnostics reported while parsing `input`.
+// Diagnostics are only recorded while a collector is active, so that
+// `report` is cheap otherwise.
+pub(crate) fn collect<T>(input: &[u8], f: impl FnOnce() -> T) -> (T, Vec<Diagnostic>) {
+    let collector = Collector {
+        input: addresses(input),
+        field: None,
+        diagnostics: vec![],
+    };
+    let prev = COLLECTOR.with(|c| c.replace(Some(collector)));
+    let res = f();
+    let collector = COLLECTOR.with(|c| c.replace(prev));
+    let diagnostics = collector.map(|c| c.diagnostics).unwrap_or_default();
+    (res, diagnostics)
+}
+
+// Records a diagnostic, if a collector is active. `span` is the offending
+// part of the input, when it is known.
+pub(crate) fn report(kind: DiagnosticKind, severity: Severity, span: Option<&[u8]>) {
+    COLLECTOR.with(|c| {
+        if let Some(c) = c.borrow_mut().as_mut() {
+            let (header, field_range) = match &c.field {
+                Some((name, range)) => (Some(name.clone()), range.clone()),
+                None => (None, None),
+            };
+            let range = span.and_then(|s| c.range_of(s)).or(field_range);
+            c.diagnostics.push(Diagnostic {
+                kind,
+                severity,
+                header,
+                range,
+            })
+        }
+    })
+}
+
+// Reports a diagnostic (see `report`), also emitting it as a `tracing` warning
+// when the given feature is enabled. For `Recovered` and `Unsupported`
+// diagnostics, the message is given once and used for both, after the optional
+// fields of the `tracing` event:
+//
+// diagnose!(
+//     "tracing-recover",
+//     (input = %bytes_to_trace_string(i)),
+//     Recovered("mailbox in return-path"),
+//     Severity::Warning,
+//     None,
+// );
+//
+// Other diagnostics take the arguments of the `tracing` event, then the kind:
+//
+// diagnose!(
+//     "tracing-recover",
+//     (field = ?f, "discarding duplicate MIME field"),
+//     DiagnosticKind::DuplicateField,
+//     Severity::Warning,
+//     None,
+// );
+macro_rules! diagnose {
+    ($feature:literal, $kind:ident($msg:literal), $severity:expr, $span:expr $(,)?) => {{
+        #[cfg(feature = $feature)]
+        tracing::warn!($msg);
+        $crate::diagnostics::report(
+            $crate::diagnostics::DiagnosticKind::$kind($msg),
+            $severity,
+            $span,
+        )
+    }};
+    ($feature:literal, ($($field:tt)+), $kind:ident($msg:literal), $severity:expr, $span:expr $(,)?) => {{
+        #[cfg(feature = $feature)]
+        tracing::warn!($($field)+, $msg);
+        $crate::diagnostics::report(
+            $crate::diagnostics::DiagnosticKind::$kind($msg),
+            $severity,
+            $span,
+        )
+    }};
+    ($feature:literal, ($($warn:tt)*), $kind:expr, $severity:expr, $span:expr $(,)?) => {{
+        #[cfg(feature = $feature)]
+        tracing::warn!($($warn)*);
+        $crate::diagnostics::report($kind, $severity, $span)
+    }};
+}
+pub(crate) use diagnose;
+
+// Reports the input recognized by `f` as obsolete syntax.
+pub(crate) fn obsolete<'a, O, F>(
+    rule: &'static str,
+    f: F,
+) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], O>
+where
+    F: Parser<&'a [u8], O, nom::error::Error<&'a [u8]>>,
+{
+    let mut f = consumed(f);
+    move |input| {
+        let (input, (raw, o)) = f(input)?;
+        report(DiagnosticKind::Obsolete(rule), Severity::Warning, Some(raw));
+        Ok((input, o))
+    }
+}
+
+// Runs `f`, dropping the diagnostics it reported if it fails. This is used for
+// alternatives that may fail after a sub-parser reported a diagnostic, so that
+// the input is not reported about twice (or wrongly) when backtracking.
+pub(crate) fn discard_on_failure<'a, O, F>(mut f: F) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], O>
+where
+    F: Parser<&'a [u8], O, nom::error::Error<&'a [u8]>>,
+{
+    move |input| {
+        let len = COLLECTOR.with(|c| c.borrow().as_ref().map(|c| c.diagnostics.len()));
+        let res = f.parse(input);
+        if let (Err(_), Some(len)) = (&res, len) {
+            COLLECTOR.with(|c| {
+                if let Some(c) = c.borrow_mut().as_mut() {
+                    c.diagnostics.truncate(len)
+                }
+            })
+        }
+        res
+    }
+}
+
+// While the returned guard is alive, reported diagnostics are attributed to
+// the header field `f`.
+pub(crate) fn enter_field(f: &FieldRaw<'_>) -> FieldGuard {
+    enter(&f.name.0, Some(f.raw))
+}
+
+// Same as `enter_field`, for a field that was recorded while parsing a header
+// section and is interpreted later on.
+pub(crate) fn enter_recorded_field(f: &DuplicateField<'_>) -> FieldGuard {
+    enter(&f.name.0, f.raw.0)
+}
+
+fn enter(name: &[u8], raw: Option<&[u8]>) -> FieldGuard {
+    let prev = COLLECTOR.with(|c| {
+        c.borrow_mut().as_mut().map(|c| {
+            let field = (
+                String::from_utf8_lossy(name).into_owned(),
+                raw.and_then(|raw| c.range_of(raw)),
+            );
+            c.field.replace(field)
+        })
+    });
+    FieldGuard { prev }
+}
+
+pub(crate) struct FieldGuard {
+    prev: Option<Option<(String, Option<Range<usize>>)>>,
+}
+
+impl Drop for FieldGuard {
+    fn drop(&mut self) {
+        if let Some(prev) = self.prev.take() {
+            COLLECTOR.with(|c| {
+                if let Some(c) = c.borrow_mut().as_mut() {
+                    c.field = prev
+                }
+            })
+        }
+    }
+}
+
+/// Checks the constraints of RFC 5322 and RFC 2045 to 2049 on a parsed message
+/// that are not reported while parsing:
+/// - the `Date` and `From` fields are mandatory, and so is `Sender` if there
+///   are several `From` mailboxes;
+/// - CR and LF only occur together as CRLF;
+/// - lines are at most 998 octets long;
+/// - header fields only contain US-ASCII (raw UTF-8 requires RFC 6532);
+/// - parts whose transfer encoding is not `8bit` or `binary` (possibly
+///   because of an enclosing part) only contain 7-bit data.
+///
+/// The header fields of encapsulated messages are not checked, as RFC 2046
+/// does not require them to conform to RFC 5322. The raw input of the message
+/// is used for the line checks and byte ranges; they are skipped if it is not
+/// available.
+pub fn validate(msg: &Message<'_>) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut missing = |name| {
+        diagnostics.push(Diagnostic {
+            kind: DiagnosticKind::MissingField(name),
+            severity: Severity::Error,
+            header: Some(name.to_string()),
+            range: None,
+        })
+    };
+    if matches!(msg.imf.date, crate::imf::DateTimeOpt::InvalidMissing) {
+        missing("Date")
+    }
+    match msg.imf.from {
+        crate::imf::From::InvalidMissingFrom { .. } => missing("From"),
+        crate::imf::From::InvalidMissingSender { .. } => missing("Sender"),
+        _ => (),
+    }
+
+    let input = msg.raw.0.unwrap_or_default();
+    check_lines(&mut diagnostics, input);
+    if let Some(raw_headers) = msg.raw_headers.0 {
+        check_headers(&mut diagnostics, addresses(input), raw_headers);
+    }
+    check_7bit(&mut diagnostics, addresses(input), &msg.mime_body, true);
+    diagnostics
+}
+
+fn check_lines(diagnostics: &mut Vec<Diagnostic>, input: &[u8]) {
+    let mut push = |kind, range| {
+        diagnostics.push(Diagnostic {
+            kind,
+            severity: Severity::Error,
+            header: None,
+            range: Some(range),
+        })
+    };
+    for (i, c) in input.iter().enumerate() {
+        let bare = match c {
+            b'\r' => input.get(i + 1) != Some(&b'\n'),
+            b'\n' => i == 0 || input[i - 1] != b'\r',
+            _ => false,
+        };
+        if bare {
+            push(DiagnosticKind::BareLineEnding, i..i + 1);
+        }
+    }
+    for range in long_lines(input) {
+        push(DiagnosticKind::LineTooLong, range);
+    }
+}
+
+// RFC 5322 only allows US-ASCII in header fields; 8-bit text requires RFC 6532.
+fn check_headers(diagnostics: &mut Vec<Diagnostic>, input: Range<usize>, raw_headers: &[u8]) {
+    let (_, fields) = crate::header::header_kv(raw_headers);
+    for f in fields.iter().filter(|f| !f.raw.is_ascii()) {
+        diagnostics.push(Diagnostic {
+            kind: DiagnosticKind::EightBitData,
+            severity: Severity::Error,
+            header: Some(String::from_utf8_lossy(&f.name.0).into_owned()),
+            range: range_in(input.clone(), f.raw),
+        })
+    }
+}
+
+fn check_7bit(
+    diagnostics: &mut Vec<Diagnostic>,
+    input: Range<usize>,
+    body: &MimeBody<'_>,
+    allow_8bit: bool,
+) {
+    let allows_8bit =
+        |m: &Mechanism| allow_8bit && matches!(m, Mechanism::_8Bit | Mechanism::Binary);
+    let (raw_body, allow_8bit) = match body {
+        MimeBody::Mult(m) => {
+            let allow_8bit = allows_8bit(&m.mime.fields.transfer_encoding);
+            for child in &m.children {
+                check_7bit(diagnostics, input.clone(), &child.mime_body, allow_8bit)
+            }
+            return;
+        }
+        MimeBody::Msg(m) => {
+            let allow_8bit = allows_8bit(&m.mime.fields.transfer_encoding);
+            check_7bit(diagnostics, input, &m.child.mime_body, allow_8bit);
+            return;
+        }
+        MimeBody::Txt(t) => (&t.raw_body, allows_8bit(&t.mime.fields.transfer_encoding)),
+        MimeBody::Bin(b) => (&b.raw_body, allows_8bit(&b.mime.fields.transfer_encoding)),
+    };
+    match raw_body.0 {
+        Some(raw) if !allow_8bit && raw.iter().any(|&c| c == 0 || !c.is_ascii()) => diagnostics
+            .push(Diagnostic {
+                kind: DiagnosticKind::EightBitData,
+                severity: Severity::Error,
+                header: None,
+                range: range_in(input, raw),
+            }),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::message;
+
+    #[test]
+    fn test_diagnostics() {
+        let input = b"From: alice@example.com\r
+From: mallory@example.com\r
+this is not a header\r
+Date: 2023-11-21 07:07:07\r
+Subject: hello\r
+\r
+Hi!";
+        let (_, diagnostics) = collect(input, || message(input));
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|d| {
+                (
+                    d.kind,
+                    d.severity,
+                    d.header.as_deref(),
+                    String::from_utf8_lossy(&input[d.range.clone().unwrap()]),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    DiagnosticKind::MalformedHeaderLine,
+                    Severity::Error,
+                    None,
+                    "this is not a header".into()
+                ),
+                (
+                    DiagnosticKind::DuplicateField,
+                    Severity::Warning,
+                    Some("From"),
+                    "From: mallory@example.com".into()
+                ),
+                (
+                    DiagnosticKind::Recovered("recovered non-RFC date-time"),
+                    Severity::Warning,
+                    Some("Date"),
+                    " 2023-11-21 07:07:07".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mime_diagnostics() {
+        // the Content-Type and Content-Transfer-Encoding fields are
+        // interpreted after the header section is parsed
+        let input = b"Content-Type: multipart/mixed; boundary=a; boundary=b\r
+Content-Transfer-Encoding: base64\r
+Subject: hello\r
+\r
+--a\r
+\r
+x\r
+--a--\r
+";
+        let (_, diagnostics) = collect(input, || message(input));
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|d| {
+                (
+                    d.kind,
+                    d.header.as_deref(),
+                    d.range
+                        .clone()
+                        .map(|r| String::from_utf8_lossy(&input[r]).into_owned()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    DiagnosticKind::Unsupported("dropping redundant boundary parameter"),
+                    Some("Content-Type"),
+                    Some("Content-Type: multipart/mixed; boundary=a; boundary=b".into())
+                ),
+                (
+                    DiagnosticKind::Recovered("to_multipart_encoding: ignoring invalid mechanism"),
+                    Some("Content-Transfer-Encoding"),
+                    Some("Content-Transfer-Encoding: base64".into())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_obsolete() {
+        let input = b"From: \"john\".doe@example.com,,\r
+To: John Q. Public <@relay.test:jqp@[1.2.3.4\x01]>\r
+Date: 21 Nov 97 09:55:06 GMT\r
+\r
+";
+        let (_, diagnostics) = collect(input, || message(input));
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|d| {
+                (
+                    d.kind,
+                    String::from_utf8_lossy(&input[d.range.clone().unwrap()]),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    DiagnosticKind::Obsolete("obs-local-part"),
+                    " \"john\".doe".into()
+                ),
+                (
+                    DiagnosticKind::Obsolete("obs-list"),
+                    " \"john\".doe@example.com,,".into()
+                ),
+                (DiagnosticKind::Obsolete("obs-phrase"), ". ".into()),
+                (DiagnosticKind::Obsolete("obs-route"), "@relay.test:".into()),
+                (DiagnosticKind::Obsolete("obs-dtext"), "1.2.3.4\x01".into()),
+                (DiagnosticKind::Obsolete("obs-date"), " 21 Nov 97 ".into()),
+                (DiagnosticKind::Obsolete("obs-zone"), " GMT".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let long = "a".repeat(999);
+        let input = format!(
+            "Subject: h\u{e9}llo\r
+MIME-Version: 1.0\r
+Content-Type: multipart/mixed; boundary=xx\r
+Content-Transfer-Encoding: 7bit\r
+\r
+--xx\r
+Content-Transfer-Encoding: 8bit\r
+\r
+h\u{e9}llo\r
+--xx\n\r
+\r
+{long}\r
+--xx--\r
+"
+        );
+        let input = input.as_bytes();
+        let msg = message(input);
+        let found: Vec<_> = validate(&msg)
+            .into_iter()
+            .map(|d| (d.kind, d.header, d.range.map(|r| r.len())))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    DiagnosticKind::MissingField("Date"),
+                    Some("Date".into()),
+                    None
+                ),
+                (
+                    DiagnosticKind::MissingField("From"),
+                    Some("From".into()),
+                    None
+                ),
+                (DiagnosticKind::BareLineEnding, None, Some(1)),
+                (DiagnosticKind::LineTooLong, None, Some(999)),
+                (
+                    DiagnosticKind::EightBitData,
+                    Some("Subject".into()),
+                    Some(15)
+                ),
+                (DiagnosticKind::EightBitData, None, Some(6)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_diagnostics() {
+        let input = b"From: alice@example.com\r
+Date: Tue, 21 Nov 2023 07:07:07 +0100\r
+\r
+Hi!";
+        let (_, diagnostics) = collect(input, || message(input));
+        assert_eq!(diagnostics, vec![]);
+
+        // outside of `collect`, reporting does nothing
+        report(DiagnosticKind::TrailingData, Severity::Error, None);
+        let (_, diagnostics) = collect(input, || ());
+        assert_eq!(diagnostics, vec![]);
+    }
+}
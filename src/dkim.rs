@@ -0,0 +1,666 @@
+// This module computes the exact data that is hashed by a DKIM signer or
+// verifier: the canonicalized header fields selected by a `h=` list, and the
+// canonicalized body (optionally truncated by a `l=` length). Checking the
+// signature itself (`b=`) against a public key is left to the caller: it must
+// be done on the data returned by `header_hash_input` (or its digest,
+// `header_hash`).
+//
+// Header fields are taken as `FieldRaw` values, as returned by
+// `header::header_kv` on the raw header section of a message.
+//
+// Only SHA-256 is supported, as RFC 8301 forbids verifiers from accepting
+// `rsa-sha1` signatures.
+
+#[cfg(feature = "arbitrary")]
+use arbitrary::Arbitrary;
+use base64::{engine::general_purpose, Engine as _};
+use bounded_static::ToStatic;
+use memchr::memmem;
+use nom::{
+    bytes::complete::{tag, take_while, take_while1},
+    combinator::{map, opt, recognize},
+    multi::{many0, separated_list1},
+    sequence::{pair, preceded, terminated, tuple},
+    IResult,
+};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::fmt;
+
+#[cfg(feature = "arbitrary")]
+use crate::arbitrary_utils::arbitrary_vec_where;
+#[cfg(feature = "arbitrary")]
+use crate::fuzz_eq::FuzzEq;
+use crate::header::FieldRaw;
+use crate::i18n::ContainsUtf8;
+use crate::print::{Formatter, Print};
+use crate::text::ascii;
+use crate::text::whitespace::fws;
+use eml_codec_derives::instrument_input;
+
+/// Canonicalization algorithms (RFC 6376, section 3.4)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Canonicalization {
+    Simple,
+    Relaxed,
+}
+
+impl Canonicalization {
+    fn from_bytes(v: &[u8]) -> Option<Self> {
+        match v.to_ascii_lowercase().as_slice() {
+            b"simple" => Some(Self::Simple),
+            b"relaxed" => Some(Self::Relaxed),
+            _ => None,
+        }
+    }
+
+    /// Parse the value of a `c=` tag, returning the header and body
+    /// canonicalizations (in this order).
+    ///
+    /// ```abnf
+    /// sig-c-tag = %x63 [FWS] "=" [FWS] sig-c-tag-alg ["/" sig-c-tag-alg]
+    /// ```
+    ///
+    /// If the body algorithm is omitted, it defaults to "simple".
+    pub fn from_tag(value: &[u8]) -> Option<(Self, Self)> {
+        let value = trim_fws(value);
+        match value.iter().position(|c| *c == b'/') {
+            Some(i) => Some((
+                Self::from_bytes(&value[..i])?,
+                Self::from_bytes(&value[i + 1..])?,
+            )),
+            None => Some((Self::from_bytes(value)?, Self::Simple)),
+        }
+    }
+}
+
+/// A `tag=value` pair of a tag list (RFC 6376, section 3.2), as found in
+/// DKIM-Signature and ARC header fields.
+///
+/// The value is represented as the list of its `tval` chunks, which are
+/// separated by folding whitespace in the input.
+#[derive(Clone, ContainsUtf8, PartialEq, ToStatic)]
+#[contains_utf8(false)]
+pub struct Tag<'a> {
+    pub name: Cow<'a, [u8]>,
+    pub value: Vec<Cow<'a, [u8]>>,
+}
+impl<'a> fmt::Debug for Tag<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Tag")
+            .field("name", &String::from_utf8_lossy(&self.name))
+            .field(
+                "value",
+                &self
+                    .value
+                    .iter()
+                    .map(|v| String::from_utf8_lossy(v))
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+impl<'a> Print for Tag<'a> {
+    fn print(&self, fmt: &mut impl Formatter) {
+        fmt.write_bytes(&self.name);
+        fmt.write_bytes(b"=");
+        for (i, v) in self.value.iter().enumerate() {
+            if i > 0 {
+                fmt.write_fws();
+            }
+            fmt.write_bytes(v);
+        }
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for Tag<'a> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut name = vec![*u.choose(b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ")?];
+        name.extend(arbitrary_vec_where(u, |c: &u8| is_alnumpunc(*c))?);
+        let value = arbitrary_vec_where(u, |v: &Cow<'a, [u8]>| {
+            !v.is_empty() && v.iter().all(|c| is_valchar(*c))
+        })?;
+        Ok(Tag {
+            name: Cow::Owned(name),
+            value,
+        })
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> FuzzEq for Tag<'a> {
+    fn fuzz_eq(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+fn is_alnumpunc(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == ascii::UNDERSCORE
+}
+
+fn is_valchar(c: u8) -> bool {
+    (ascii::EXCLAMATION..=ascii::TILDE).contains(&c) && c != ascii::SEM_COL
+}
+
+/// ```abnf
+/// tag-spec  =  [FWS] tag-name [FWS] "=" [FWS] tag-value [FWS]
+/// tag-name  =  ALPHA *ALNUMPUNC
+/// tag-value =  [ tval *( 1*(WSP / FWS) tval ) ]
+/// tval      =  1*VALCHAR
+/// ```
+#[instrument_input("tracing")]
+fn tag_spec(input: &[u8]) -> IResult<&[u8], Tag<'_>> {
+    map(
+        tuple((
+            opt(fws),
+            recognize(pair(
+                take_while1(|c: u8| c.is_ascii_alphabetic()),
+                take_while(is_alnumpunc),
+            )),
+            opt(fws),
+            tag(b"="),
+            opt(fws),
+            opt(pair(tval, many0(preceded(fws, tval)))),
+            opt(fws),
+        )),
+        |(_, name, _, _, _, value, _)| Tag {
+            name: Cow::Borrowed(name),
+            value: match value {
+                Some((first, rest)) => std::iter::once(first)
+                    .chain(rest)
+                    .map(Cow::Borrowed)
+                    .collect(),
+                None => vec![],
+            },
+        },
+    )(input)
+}
+
+fn tval(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    take_while1(is_valchar)(input)
+}
+
+/// Parse a tag list (e.g. the body of a `DKIM-Signature` header).
+///
+/// ```abnf
+/// tag-list  =  tag-spec *( ";" tag-spec ) [ ";" ]
+/// ```
+#[instrument_input("tracing")]
+pub fn tag_list(input: &[u8]) -> IResult<&[u8], Vec<Tag<'_>>> {
+    terminated(
+        separated_list1(tag(b";"), tag_spec),
+        opt(pair(tag(b";"), opt(fws))),
+    )(input)
+}
+
+/// Find the value of the first tag named `name` in a tag list. Whitespace
+/// inside the value (e.g. folding in `b=`) is removed.
+pub fn tag_value(tags: &[Tag], name: &[u8]) -> Option<Vec<u8>> {
+    tags.iter()
+        .find(|t| t.name == name)
+        .map(|t| t.value.concat())
+}
+
+/// Split the value of a `h=` tag into the list of signed header names.
+pub fn signed_header_names(value: &[u8]) -> Vec<&[u8]> {
+    value
+        .split(|c| *c == b':')
+        .map(trim_fws)
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Select the header fields covered by a `h=` list, in the order in which
+/// they must be hashed.
+///
+/// When a name appears several times in `names`, successive occurrences
+/// select instances of that header field from the bottom of the header
+/// section upwards (RFC 6376, section 5.4.2). Names that do not match any
+/// (remaining) field are skipped: they are hashed as the empty string.
+pub fn select_headers<'a, 'b>(
+    fields: &'b [FieldRaw<'a>],
+    names: &[&[u8]],
+) -> Vec<&'b FieldRaw<'a>> {
+    let mut used = vec![false; fields.len()];
+    let mut selected = vec![];
+    for name in names {
+        let found = fields
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(i, f)| !used[*i] && f.name.bytes().eq_ignore_ascii_case(name));
+        if let Some((i, f)) = found {
+            used[i] = true;
+            selected.push(f);
+        }
+    }
+    selected
+}
+
+/// Canonicalize a header field (RFC 6376, section 3.4.1 and 3.4.2), including
+/// its final CRLF, and append the result to `out`.
+pub fn canonicalize_header(c: Canonicalization, f: &FieldRaw, out: &mut Vec<u8>) {
+    canonicalize_header_parts(c, f, f.body, out);
+    out.extend_from_slice(ascii::CRLF);
+}
+
+// `body` replaces the body of `f`, which is otherwise used for its name (and
+// its raw bytes before the body in the simple case).
+fn canonicalize_header_parts(c: Canonicalization, f: &FieldRaw, body: &[u8], out: &mut Vec<u8>) {
+    match c {
+        Canonicalization::Simple => {
+            out.extend_from_slice(&f.raw[..f.raw.len() - f.body.len()]);
+            out.extend_from_slice(body);
+        }
+        Canonicalization::Relaxed => {
+            out.extend(f.name.bytes().iter().map(u8::to_ascii_lowercase));
+            out.push(b':');
+            // unfold, reduce sequences of WSP to a single SP, and remove WSP
+            // at the beginning and the end of the value.
+            let mut pending_wsp = false;
+            let mut started = false;
+            for &b in body {
+                match b {
+                    ascii::CR | ascii::LF => (),
+                    ascii::SP | ascii::HT => pending_wsp = true,
+                    _ => {
+                        if pending_wsp && started {
+                            out.push(ascii::SP);
+                        }
+                        pending_wsp = false;
+                        started = true;
+                        out.push(b);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Remove the value of the `b=` tag of a signature header body, keeping
+// everything else (including the tag name and equal sign) untouched.
+fn without_signature_value(body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len());
+    for (i, spec) in body.split(|c| *c == b';').enumerate() {
+        if i > 0 {
+            out.push(b';');
+        }
+        match spec.iter().position(|c| *c == b'=') {
+            Some(eq) if trim_fws(&spec[..eq]) == b"b" => out.extend_from_slice(&spec[..=eq]),
+            _ => out.extend_from_slice(spec),
+        }
+    }
+    out
+}
+
+/// Compute the data that is signed by a DKIM signature (RFC 6376, section 3.7).
+///
+/// # Arguments
+///
+/// * `c` - The header canonicalization (first half of the `c=` tag)
+/// * `fields` - All the header fields of the message, in order
+/// * `names` - The signed header names (from the `h=` tag)
+/// * `signature` - The signature header field (`DKIM-Signature`, or
+///   `ARC-Message-Signature`/`ARC-Seal` for ARC)
+///
+/// # Returns
+///
+/// The canonicalized selected headers, followed by the canonicalized signature
+/// header with an empty `b=` value and without its final CRLF.
+pub fn header_hash_input(
+    c: Canonicalization,
+    fields: &[FieldRaw],
+    names: &[&[u8]],
+    signature: &FieldRaw,
+) -> Vec<u8> {
+    let mut out = vec![];
+    for f in select_headers(fields, names) {
+        canonicalize_header(c, f, &mut out);
+    }
+    canonicalize_header_parts(
+        c,
+        signature,
+        &without_signature_value(signature.body),
+        &mut out,
+    );
+    out
+}
+
+/// SHA-256 digest of [`header_hash_input`].
+pub fn header_hash(
+    c: Canonicalization,
+    fields: &[FieldRaw],
+    names: &[&[u8]],
+    signature: &FieldRaw,
+) -> [u8; 32] {
+    Sha256::digest(header_hash_input(c, fields, names, signature)).into()
+}
+
+/// Canonicalize a message body (RFC 6376, section 3.4.3 and 3.4.4).
+pub fn canonicalize_body(c: Canonicalization, body: &[u8]) -> Vec<u8> {
+    let mut lines: Vec<&[u8]> = vec![];
+    let mut start = 0;
+    for end in memmem::find_iter(body, ascii::CRLF) {
+        lines.push(&body[start..end]);
+        start = end + ascii::CRLF.len();
+    }
+    // a missing final CRLF is added
+    if start < body.len() {
+        lines.push(&body[start..]);
+    }
+
+    let mut out = Vec::with_capacity(body.len() + ascii::CRLF.len());
+    match c {
+        Canonicalization::Simple => {
+            while lines.last().is_some_and(|l| l.is_empty()) {
+                lines.pop();
+            }
+            for line in lines {
+                out.extend_from_slice(line);
+                out.extend_from_slice(ascii::CRLF);
+            }
+            if out.is_empty() {
+                out.extend_from_slice(ascii::CRLF);
+            }
+        }
+        Canonicalization::Relaxed => {
+            let mut pending_lines = 0;
+            for line in lines {
+                let mut canon = vec![];
+                let mut pending_wsp = false;
+                for &b in line {
+                    if b == ascii::SP || b == ascii::HT {
+                        pending_wsp = true;
+                    } else {
+                        if pending_wsp {
+                            canon.push(ascii::SP);
+                        }
+                        pending_wsp = false;
+                        canon.push(b);
+                    }
+                }
+                // empty lines are only kept if they are followed by a
+                // non-empty line
+                if canon.is_empty() {
+                    pending_lines += 1;
+                } else {
+                    for _ in 0..pending_lines {
+                        out.extend_from_slice(ascii::CRLF);
+                    }
+                    pending_lines = 0;
+                    out.extend_from_slice(&canon);
+                    out.extend_from_slice(ascii::CRLF);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Compute the body hash of a message (the `bh=` tag).
+///
+/// `length` is the value of the `l=` tag, if any: only this many octets of
+/// the canonicalized body are hashed. Returns `None` if `length` exceeds the
+/// length of the canonicalized body.
+pub fn body_hash(c: Canonicalization, body: &[u8], length: Option<usize>) -> Option<[u8; 32]> {
+    let canon = canonicalize_body(c, body);
+    let canon = match length {
+        Some(l) => canon.get(..l)?,
+        None => &canon[..],
+    };
+    Some(Sha256::digest(canon).into())
+}
+
+/// Check a body hash against the (base64-encoded) value of a `bh=` tag.
+pub fn verify_body_hash(
+    c: Canonicalization,
+    body: &[u8],
+    length: Option<usize>,
+    bh: &[u8],
+) -> bool {
+    let bh: Vec<u8> = bh.iter().copied().filter(|b| !is_fws(*b)).collect();
+    match (
+        body_hash(c, body, length),
+        general_purpose::STANDARD.decode(bh),
+    ) {
+        (Some(h), Ok(expected)) => h[..] == expected[..],
+        _ => false,
+    }
+}
+
+fn is_fws(c: u8) -> bool {
+    matches!(c, ascii::SP | ascii::HT | ascii::CR | ascii::LF)
+}
+
+fn trim_fws(mut s: &[u8]) -> &[u8] {
+    while let [first, rest @ ..] = s {
+        if !is_fws(*first) {
+            break;
+        }
+        s = rest;
+    }
+    while let [rest @ .., last] = s {
+        if !is_fws(*last) {
+            break;
+        }
+        s = rest;
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header;
+    use pretty_assertions::assert_eq;
+
+    // RFC 6376, section 3.4.5
+    const EXAMPLE: &[u8] = b"A: X\r\nB : Y\t\r\n\tZ  \r\n\r\n C \r\nD \t E\r\n\r\n\r\n";
+
+    #[test]
+    fn test_canonicalize_headers_example() {
+        let (body, fields) = header::header_kv(EXAMPLE);
+        let mut relaxed = vec![];
+        let mut simple = vec![];
+        for f in &fields {
+            canonicalize_header(Canonicalization::Relaxed, f, &mut relaxed);
+            canonicalize_header(Canonicalization::Simple, f, &mut simple);
+        }
+        assert_eq!(String::from_utf8_lossy(&relaxed), "a:X\r\nb:Y Z\r\n");
+        assert_eq!(
+            String::from_utf8_lossy(&simple),
+            "A: X\r\nB : Y\t\r\n\tZ  \r\n"
+        );
+        assert_eq!(body, b" C \r\nD \t E\r\n\r\n\r\n");
+    }
+
+    #[test]
+    fn test_canonicalize_body_example() {
+        let (body, _) = header::header_kv(EXAMPLE);
+        assert_eq!(
+            String::from_utf8_lossy(&canonicalize_body(Canonicalization::Relaxed, body)),
+            " C\r\nD E\r\n"
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&canonicalize_body(Canonicalization::Simple, body)),
+            " C \r\nD \t E\r\n"
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_empty_body() {
+        assert_eq!(canonicalize_body(Canonicalization::Simple, b""), b"\r\n");
+        assert_eq!(
+            canonicalize_body(Canonicalization::Simple, b"\r\n\r\n"),
+            b"\r\n"
+        );
+        assert_eq!(canonicalize_body(Canonicalization::Relaxed, b""), b"");
+        assert_eq!(
+            canonicalize_body(Canonicalization::Relaxed, b" \r\n\t\r\n"),
+            b""
+        );
+        assert_eq!(
+            canonicalize_body(Canonicalization::Relaxed, b"a\r\n \r\nb"),
+            b"a\r\n\r\nb\r\n"
+        );
+    }
+
+    #[test]
+    fn test_c_tag() {
+        use Canonicalization::*;
+        assert_eq!(
+            Canonicalization::from_tag(b"relaxed/simple"),
+            Some((Relaxed, Simple))
+        );
+        assert_eq!(
+            Canonicalization::from_tag(b"Relaxed"),
+            Some((Relaxed, Simple))
+        );
+        assert_eq!(
+            Canonicalization::from_tag(b" simple/relaxed "),
+            Some((Simple, Relaxed))
+        );
+        assert_eq!(Canonicalization::from_tag(b"simple/other"), None);
+    }
+
+    #[test]
+    fn test_select_headers_bottom_up() {
+        let (_, fields) = header::header_kv(
+            b"From: a@example.com\r\nTo: b@example.com\r\nfrom: c@example.com\r\n\r\n",
+        );
+        let selected = select_headers(&fields, &[b"from", b"to", b"FROM", b"from", b"cc"]);
+        let bodies: Vec<_> = selected.iter().map(|f| f.body).collect();
+        assert_eq!(
+            bodies,
+            vec![
+                &b" c@example.com"[..],
+                &b" b@example.com"[..],
+                &b" a@example.com"[..],
+            ]
+        );
+    }
+
+    // RFC 6376, appendix A
+    const SIGNED: &[u8] = b"DKIM-Signature: v=1; a=rsa-sha256; s=brisbane; d=example.com;\r
+      c=simple/simple; q=dns/txt; i=joe@football.example.com;\r
+      h=Received : From : To : Subject : Date : Message-ID;\r
+      bh=2jUSOH9NhtVGCQWNr9BrIAPreKQjO6Sn7XIkfJVOzv8=;\r
+      b=AuUoFEfDxTDkHlLXSZEpZj79LICEps6eda7W3deTVFOk4yAUoqOB\r
+        4nujc7YopdG5dWLSdNg6xNAZpOPr+kHxt1IrE+NahM6L/LbvaHut\r
+        KVdkLLkpVaVVQPzeRDI009SO2Il5Lu7rDNH6mZckBdrIx0orEtZV\r
+        4bmp/YzhwvcubU4=;\r
+Received: from client1.football.example.com  [192.0.2.1]\r
+      by submitserver.example.com with SUBMISSION;\r
+      Fri, 11 Jul 2003 21:01:54 -0700 (PDT)\r
+From: Joe SixPack <joe@football.example.com>\r
+To: Suzie Q <suzie@shopping.example.net>\r
+Subject: Is dinner ready?\r
+Date: Fri, 11 Jul 2003 21:00:37 -0700 (PDT)\r
+Message-ID: <20030712040037.46341.5F8J@football.example.com>\r
+\r
+Hi.\r
+\r
+We lost the game. Are you hungry yet?\r
+\r
+Joe.\r
+";
+
+    #[test]
+    fn test_rfc6376_example() {
+        let (body, fields) = header::header_kv(SIGNED);
+        let sig = &fields[0];
+        let (_, tags) = tag_list(sig.body).unwrap();
+        let (hc, bc) = Canonicalization::from_tag(&tag_value(&tags, b"c").unwrap()).unwrap();
+        assert_eq!(hc, Canonicalization::Simple);
+
+        let bh = tag_value(&tags, b"bh").unwrap();
+        assert!(verify_body_hash(bc, body, None, &bh));
+        assert!(!verify_body_hash(bc, b"Hi.\r\n", None, &bh));
+
+        let h = tag_value(&tags, b"h").unwrap();
+        let names = signed_header_names(&h);
+        assert_eq!(
+            names,
+            vec![
+                &b"Received"[..],
+                b"From",
+                b"To",
+                b"Subject",
+                b"Date",
+                b"Message-ID"
+            ]
+        );
+        let input = header_hash_input(hc, &fields, &names, sig);
+        let received = memmem::find(SIGNED, b"Received:").unwrap();
+        let expected = [
+            &SIGNED[received..SIGNED.len() - body.len() - 2],
+            b"DKIM-Signature: v=1; a=rsa-sha256; s=brisbane; d=example.com;\r
+      c=simple/simple; q=dns/txt; i=joe@football.example.com;\r
+      h=Received : From : To : Subject : Date : Message-ID;\r
+      bh=2jUSOH9NhtVGCQWNr9BrIAPreKQjO6Sn7XIkfJVOzv8=;\r
+      b=;",
+        ]
+        .concat();
+        assert_eq!(
+            String::from_utf8_lossy(&input),
+            String::from_utf8_lossy(&expected)
+        );
+    }
+
+    // RFC 6376, appendix C
+    const PUBLIC_KEY: &str = "MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQDwIRP/UC3SBsEmGqZ9ZJW3/DkMoGeL\
+        nQg1fWn7/zYtIxN2SnFCjxOCKG9v3b4jYfcTNh5ijSsq631uBItLa7od+v/RtdC2UzJ1lWT947qR+Rcac2g\
+        bto/NMqJ0fzfVjH4OuKhitdY9tf6mcwGjaNBcWToIMmPSPDdQPNUYckcQ2QIDAQAB";
+
+    #[test]
+    fn test_rfc6376_signature() {
+        use base64::Engine;
+        use rsa::pkcs8::DecodePublicKey;
+
+        let b64 = base64::engine::general_purpose::STANDARD;
+        let key = rsa::RsaPublicKey::from_public_key_der(&b64.decode(PUBLIC_KEY).unwrap()).unwrap();
+
+        let (_, fields) = header::header_kv(SIGNED);
+        let sig = &fields[0];
+        let (_, tags) = tag_list(sig.body).unwrap();
+        let (hc, _) = Canonicalization::from_tag(&tag_value(&tags, b"c").unwrap()).unwrap();
+        let h = tag_value(&tags, b"h").unwrap();
+        let names = signed_header_names(&h);
+        let b = b64.decode(tag_value(&tags, b"b").unwrap()).unwrap();
+
+        let hash = header_hash(hc, &fields, &names, sig);
+        let scheme = || rsa::Pkcs1v15Sign::new::<sha2::Sha256>();
+        assert!(key.verify(scheme(), &hash, &b).is_ok());
+
+        // altering a signed header field invalidates the signature
+        let (_, subject) = header::header_kv(b"Subject: Is lunch ready?\r\n\r\n");
+        let mut fields = fields.clone();
+        fields[4] = subject[0].clone();
+        let hash = header_hash(hc, &fields, &names, &fields[0]);
+        assert!(key.verify(scheme(), &hash, &b).is_err());
+    }
+
+    #[test]
+    fn test_body_length() {
+        let body = b"Hi.\r\n\r\nmore text appended by a mailing list\r\n";
+        let full = body_hash(Canonicalization::Relaxed, body, None);
+        let truncated = body_hash(Canonicalization::Relaxed, body, Some(5));
+        assert_eq!(
+            truncated,
+            body_hash(Canonicalization::Relaxed, b"Hi.", None)
+        );
+        assert_ne!(full, truncated);
+        assert_eq!(body_hash(Canonicalization::Relaxed, body, Some(1000)), None);
+    }
+
+    #[test]
+    fn test_relaxed_signature_header() {
+        let (_, fields) = header::header_kv(
+            b"From: a@example.com\r\nDKIM-Signature: v=1;\r\n  b=abcd\r\n efgh ; h=from\r\n\r\n",
+        );
+        let input = header_hash_input(Canonicalization::Relaxed, &fields, &[b"from"], &fields[1]);
+        assert_eq!(
+            String::from_utf8_lossy(&input),
+            "from:a@example.com\r\ndkim-signature:v=1; b=; h=from"
+        );
+    }
+}
@@ -12,9 +12,11 @@ use nom::{
 };
 use std::borrow::Cow;
 use std::fmt;
+use std::ops::Range;
 #[cfg(feature = "tracing")]
 use tracing::warn;
 
+use crate::diagnostics::{self, DiagnosticKind, Severity};
 use crate::i18n::ContainsUtf8;
 use crate::print::{Formatter, Print};
 use crate::raw_input::RawInput;
@@ -73,16 +75,21 @@ impl<'a> FuzzEq for FieldName<'a> {
 //
 // - `Bad` corresponds to a header field that could not be split into a name and
 // body; it basically contains arbitrary data.
+//
+// `raw` holds the complete field as found in the input (name, colon and body),
+// without its final line terminator. `body` is always a suffix of `raw`.
 #[derive(PartialEq, Clone)]
 pub struct FieldRaw<'a> {
     pub name: FieldName<'a>,
     pub body: &'a [u8],
+    pub raw: &'a [u8],
 }
 impl<'a> fmt::Debug for FieldRaw<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt.debug_struct("header::FieldRaw")
             .field("name", &self.name)
             .field("body", &String::from_utf8_lossy(self.body))
+            .field("raw", &String::from_utf8_lossy(self.raw))
             .finish()
     }
 }
@@ -92,6 +99,53 @@ impl<'a> ContainsUtf8 for FieldRaw<'a> {
     }
 }
 
+/// What to do with a header field that may only appear once in a header
+/// section, when it is found several times (e.g. two `From` fields).
+///
+/// Fields that can legitimately appear several times (`To`, `Received`,
+/// `Comments`, unstructured fields...) are not affected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ToStatic)]
+pub enum DuplicatePolicy {
+    /// Keep the first occurrence of the field and discard the later ones.
+    #[default]
+    KeepFirst,
+    /// Keep the last occurrence of the field and discard the earlier ones.
+    KeepLast,
+    /// Discard every occurrence of the field.
+    Reject,
+}
+
+/// A header field that was discarded by the `DuplicatePolicy` used when
+/// parsing.
+#[derive(Clone, Debug, PartialEq, ToStatic)]
+pub struct DuplicateField<'a> {
+    pub name: FieldName<'a>,
+    /// The complete field as found in the input, without its final line
+    /// terminator.
+    pub raw: RawInput<'a>,
+}
+impl<'a> From<&FieldRaw<'a>> for DuplicateField<'a> {
+    fn from(f: &FieldRaw<'a>) -> Self {
+        DuplicateField {
+            name: f.name.clone(),
+            raw: f.raw.into(),
+        }
+    }
+}
+
+// Remove the first entry matching `pred` (if any) and record its field as a
+// duplicate. Used while collecting the fields of a header section, where each
+// entry is paired with the field it comes from.
+pub(crate) fn discard_entry<'a, E>(
+    entries: &mut Vec<(E, DuplicateField<'a>)>,
+    duplicates: &mut Vec<DuplicateField<'a>>,
+    pred: impl Fn(&E) -> bool,
+) {
+    if let Some(i) = entries.iter().position(|(e, _)| pred(e)) {
+        duplicates.push(entries.remove(i).1);
+    }
+}
+
 /// Parse headers as raw key/values.
 /// Stop at an empty line or at EOF.
 #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
@@ -107,14 +161,24 @@ pub fn header_kv(input: &[u8]) -> (&[u8], Vec<FieldRaw<'_>>) {
         map(eof, |_| None),
         // For best-effort parsing, we also try to parse any remaining bytes before
         // EOF (as if EOF was a CRLF).
-        map(consumed(pair(field_name, rest)), |(_i, (name, body))| {
-            #[cfg(feature = "tracing-recover")]
-            warn!(input = %bytes_to_trace_string(_i), "raw field before EOF");
-            Some(FieldRaw { name, body })
+        map(consumed(pair(field_name, rest)), |(raw, (name, body))| {
+            diagnostics::diagnose!(
+                "tracing-recover",
+                (input = %bytes_to_trace_string(raw)),
+                Recovered("raw field before EOF"),
+                Severity::Warning,
+                Some(raw),
+            );
+            Some(FieldRaw { name, body, raw })
         }),
-        map(rest, |_i: &[u8]| {
-            #[cfg(feature = "tracing-unsupported")]
-            warn!(input = %bytes_to_trace_string(_i), "raw bytes before EOF");
+        map(rest, |i: &[u8]| {
+            diagnostics::diagnose!(
+                "tracing-unsupported",
+                (input = %bytes_to_trace_string(i), "raw bytes before EOF"),
+                DiagnosticKind::MalformedHeaderLine,
+                Severity::Error,
+                Some(i),
+            );
             None
         }),
     ))(input)
@@ -130,9 +194,11 @@ pub fn header_kv(input: &[u8]) -> (&[u8], Vec<FieldRaw<'_>>) {
 
 // NOTE: field_raw only recognizes non-empty inputs.
 fn field_raw(input: &[u8]) -> IResult<&[u8], FieldRaw<'_>> {
-    map(pair(field_name, foldable_line(false)), |(name, body)| {
-        FieldRaw { name, body }
-    })(input)
+    let (after_name, name) = field_name(input)?;
+    let (rest, body) = foldable_line(false)(after_name)?;
+    // `body` ends right before the line terminator consumed by `foldable_line`
+    let raw = &input[..input.len() - after_name.len() + body.len()];
+    Ok((rest, FieldRaw { name, body, raw }))
 }
 
 // A best-effort version of `field_raw` that also recognizes lines that cannot
@@ -146,9 +212,14 @@ fn field_raw_opt(input: &[u8]) -> IResult<&[u8], Option<FieldRaw<'_>>> {
         map(field_raw, Some),
         // best-effort: a (non-empty) foldable line that cannot even be parsed as
         // a field name and body. We drop it afterwards.
-        map(foldable_line(true), |_i| {
-            #[cfg(feature = "tracing-unsupported")]
-            warn!(input = %bytes_to_trace_string(_i), "malformed raw header line");
+        map(foldable_line(true), |i| {
+            diagnostics::diagnose!(
+                "tracing-unsupported",
+                (input = %bytes_to_trace_string(i), "malformed raw header line"),
+                DiagnosticKind::MalformedHeaderLine,
+                Severity::Error,
+                Some(i),
+            );
             None
         }),
     ))(input)
@@ -200,6 +271,77 @@ impl<'a> Print for Unstructured<'a> {
     }
 }
 
+/// A header field whose body could not be parsed: either a structured field
+/// (IMF or MIME) whose body is invalid, or a field that cannot even be parsed
+/// as unstructured.
+///
+/// The body is kept as found in the input. It is printed back verbatim
+/// (modulo line folding), unless the `Formatter` sanitizes its output.
+#[derive(PartialEq, Clone, ToStatic)]
+pub struct Invalid<'a> {
+    pub name: FieldName<'a>,
+    pub body: Cow<'a, [u8]>,
+    pub raw_body: RawInput<'a>,
+}
+impl<'a> Invalid<'a> {
+    pub fn from_raw(f: &FieldRaw<'a>) -> Self {
+        Invalid {
+            name: f.name.clone(),
+            body: Cow::Borrowed(f.body),
+            raw_body: f.body.into(),
+        }
+    }
+}
+impl<'a> fmt::Debug for Invalid<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("header::Invalid")
+            .field("name", &self.name)
+            .field("body", &String::from_utf8_lossy(&self.body))
+            .field("raw_body", &self.raw_body)
+            .finish()
+    }
+}
+impl<'a> ContainsUtf8 for Invalid<'a> {
+    fn contains_utf8(&self) -> bool {
+        self.body.iter().any(|c| !c.is_ascii())
+    }
+}
+impl<'a> Print for Invalid<'a> {
+    fn print(&self, fmt: &mut impl Formatter) {
+        if fmt.sanitize() {
+            return;
+        }
+        fmt.write_bytes(&self.name.0);
+        fmt.write_bytes(b":");
+        // Unfold the body (dropping line terminators), and let the
+        // formatter fold it again on whitespace.
+        let body: Vec<u8> = self
+            .body
+            .iter()
+            .copied()
+            .filter(|c| *c != b'\r' && *c != b'\n')
+            .collect();
+        for chunk in body.chunk_by(|a, b| is_wsp(*a) == is_wsp(*b)) {
+            if is_wsp(chunk[0]) {
+                fmt.write_fws_bytes(chunk)
+            } else {
+                fmt.write_bytes(chunk)
+            }
+        }
+        fmt.write_crlf();
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> FuzzEq for Invalid<'a> {
+    fn fuzz_eq(&self, other: &Self) -> bool {
+        self.name.fuzz_eq(&other.name) && self.body == other.body
+    }
+}
+
+fn is_wsp(c: u8) -> bool {
+    c == b' ' || c == b'\t'
+}
+
 // Helper to print structured headers
 
 pub fn print<T: Print>(fmt: &mut impl Formatter, name: &[u8], body: T) {
@@ -221,6 +363,55 @@ pub fn print_unstructured<'a>(
     fmt.write_crlf();
 }
 
+// Byte offsets in `input` of a complete header field (name, colon and body,
+// without its final line terminator), given the raw input of its body.
+pub(crate) fn field_span(input: &[u8], raw_body: &RawInput) -> Option<Range<usize>> {
+    let body = raw_body.span_in(input)?;
+    // the field name starts at the beginning of the line holding the start of
+    // the body
+    let start = input[..body.start]
+        .iter()
+        .rposition(|c| *c == b'\r' || *c == b'\n')
+        .map_or(0, |i| i + 1);
+    Some(start..body.end)
+}
+
+// Prints header fields (but not the empty line ending the header section) for
+// minimal-diff printing: fields that can be found unchanged in `original` are
+// copied from `input`, the other ones are printed. Returns whether each field
+// was copied.
+pub(crate) fn print_fields_diff<F: Print + PartialEq>(
+    fmt: &mut impl Formatter,
+    input: &[u8],
+    fields: &[F],
+    original: &[F],
+    raw_body: impl Fn(&F) -> RawInput,
+) -> Vec<bool> {
+    fields
+        .iter()
+        .map(|f| {
+            let span = if original.contains(f) {
+                field_span(input, &raw_body(f))
+            } else {
+                None
+            };
+            match span {
+                Some(span) => {
+                    fmt.write_bytes(&input[span]);
+                    fmt.write_crlf();
+                    true
+                }
+                None => {
+                    fmt.begin_line_folding();
+                    f.print(fmt);
+                    fmt.end_line_folding();
+                    false
+                }
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -235,6 +426,7 @@ mod tests {
             FieldRaw {
                 name: FieldName(b"X-Unknown".into()),
                 body: &b" something something"[..],
+                raw: &b"X-Unknown: something something"[..],
             }
         );
 
@@ -245,6 +437,7 @@ mod tests {
             FieldRaw {
                 name: FieldName(b"X-Foo".into()),
                 body: &b""[..],
+                raw: &b"X-Foo:"[..],
             }
         );
 
@@ -256,6 +449,7 @@ mod tests {
             FieldRaw {
                 name: FieldName(b"From".into()),
                 body: &b"\r\n foo@example.com\r\n abcd"[..],
+                raw: &b"From:\r\n foo@example.com\r\n abcd"[..],
             }
         );
     }
@@ -265,6 +459,7 @@ mod tests {
         let u = Unstructured::from_raw(&FieldRaw {
             name: FieldName(b"X-Unknown".into()),
             body: &b" something something"[..],
+            raw: &b"X-Unknown: something something"[..],
         })
         .unwrap();
         assert_eq!(
@@ -291,11 +486,13 @@ mod tests {
             vec![
                 FieldRaw {
                     name: FieldName(b"X-Foo".into()),
-                    body: b" something something"
+                    body: b" something something",
+                    raw: b"X-Foo: something something",
                 },
                 FieldRaw {
                     name: FieldName(b"X-Bar".into()),
-                    body: b" something else"
+                    body: b" something else",
+                    raw: b"X-Bar: something else",
                 },
             ]
         )
@@ -329,11 +526,13 @@ mod tests {
             vec![
                 FieldRaw {
                     name: FieldName(b"X-Foo".into()),
-                    body: b" something something"
+                    body: b" something something",
+                    raw: b"X-Foo: something something",
                 },
                 FieldRaw {
                     name: FieldName(b"X-Bar".into()),
-                    body: b" something else"
+                    body: b" something else",
+                    raw: b"X-Bar: something else",
                 },
             ]
         )
@@ -347,7 +546,8 @@ mod tests {
             fields,
             vec![FieldRaw {
                 name: FieldName(b"X-Foo".into()),
-                body: b" something something"
+                body: b" something something",
+                raw: b"X-Foo: something something",
             },]
         )
     }
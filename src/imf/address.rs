@@ -4,27 +4,42 @@ use bounded_static::ToStatic;
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    combinator::{into, map, map_opt, opt},
+    combinator::{consumed, into, map, map_opt, opt},
     multi::separated_list1,
     sequence::tuple,
     IResult,
 };
 
+use crate::diagnostics::discard_on_failure;
 #[cfg(feature = "arbitrary")]
 use crate::fuzz_eq::FuzzEq;
 use crate::i18n::ContainsUtf8;
-use crate::imf::mailbox::{mailbox, mailbox_list_nullable, MailboxList, MailboxRef};
+use crate::imf::mailbox::{
+    juxtaposed, lenient_list_separator, mailbox, mailbox_list_nullable, report_obs_list,
+    MailboxList, MailboxRef,
+};
 use crate::print::{print_seq, Formatter, Print};
+use crate::raw_input::RawInput;
 use crate::text::misc_token::{phrase, Phrase};
 use crate::text::whitespace::cfws;
-use crate::utils::vec_filter_none_nonempty;
+use crate::utils::vec_flatten_nonempty;
 use eml_codec_derives::instrument_input;
 
-#[derive(Clone, ContainsUtf8, Debug, PartialEq, ToStatic)]
+#[derive(Clone, ContainsUtf8, Debug, ToStatic)]
 #[cfg_attr(feature = "arbitrary", derive(Arbitrary, FuzzEq))]
 pub struct GroupRef<'a> {
     pub name: Phrase<'a>,
     pub participants: Option<MailboxList<'a>>,
+    // The input the group was parsed from, without surrounding whitespace.
+    // Use `Message::span` to get its position in the message.
+    #[cfg_attr(feature = "arbitrary", fuzz_eq(ignore))]
+    pub raw: RawInput<'a>,
+}
+// `raw` only records where the group comes from and is not compared.
+impl<'a> PartialEq for GroupRef<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.participants == other.participants
+    }
 }
 impl<'a> Print for GroupRef<'a> {
     fn print(&self, fmt: &mut impl Formatter) {
@@ -53,6 +68,16 @@ impl<'a> From<GroupRef<'a>> for AddressRef<'a> {
         AddressRef::Many(grp)
     }
 }
+impl<'a> AddressRef<'a> {
+    /// The input the address was parsed from, see `MailboxRef::raw` and
+    /// `GroupRef::raw`.
+    pub fn raw(&self) -> &RawInput<'a> {
+        match self {
+            AddressRef::Single(mbox) => &mbox.raw,
+            AddressRef::Many(group) => &group.raw,
+        }
+    }
+}
 impl<'a> Print for AddressRef<'a> {
     fn print(&self, fmt: &mut impl Formatter) {
         match self {
@@ -80,7 +105,7 @@ impl<'a> Print for AddressList<'a> {
 /// ```
 #[instrument_input("tracing")]
 pub fn address(input: &[u8]) -> IResult<&[u8], AddressRef<'_>> {
-    alt((into(mailbox), into(group)))(input)
+    alt((discard_on_failure(into(mailbox)), into(group)))(input)
 }
 
 /// Group
@@ -91,14 +116,20 @@ pub fn address(input: &[u8]) -> IResult<&[u8], AddressRef<'_>> {
 /// ```
 #[instrument_input("tracing")]
 pub fn group(input: &[u8]) -> IResult<&[u8], GroupRef<'_>> {
-    let (input, (grp_name, _, grp_list, _, _)) =
-        tuple((phrase, tag(":"), opt(group_list), tag(";"), opt(cfws)))(input)?;
+    let (input, (raw, (grp_name, _, grp_list, _, _))) = consumed(tuple((
+        phrase,
+        tag(":"),
+        opt(group_list),
+        tag(";"),
+        opt(cfws),
+    )))(input)?;
 
     Ok((
         input,
         GroupRef {
             name: grp_name,
             participants: grp_list.unwrap_or(None),
+            raw: RawInput::from(raw.trim_ascii()),
         },
     ))
 }
@@ -120,16 +151,21 @@ pub fn group_list(input: &[u8]) -> IResult<&[u8], Option<MailboxList<'_>>> {
 ///   address-list    =   (address *("," address)) / obs-addr-list
 ///   obs-addr-list   =   *([CFWS] ",") address *("," [address / CFWS])
 /// ```
+///
+/// We also recover from semicolons used as separators, missing commas,
+/// and display names that are not valid phrases, in order to salvage as
+/// many mailboxes as possible.
 #[instrument_input("tracing")]
 pub fn address_list(input: &[u8]) -> IResult<&[u8], Vec<AddressRef<'_>>> {
-    // NOTE: should we try to recover from individually broken addresses?
-    // (see e.g. identification::nullable_msg_list)
     map_opt(
-        separated_list1(
-            tag(","),
-            alt((map(address, Some), map(opt(cfws), |_| None))),
-        ),
-        vec_filter_none_nonempty,
+        consumed(separated_list1(
+            lenient_list_separator,
+            alt((juxtaposed(address), map(opt(cfws), |_| vec![]))),
+        )),
+        |(raw, v)| {
+            report_obs_list(raw, &v);
+            vec_flatten_nonempty(v)
+        },
     )(input)
 }
 
@@ -150,6 +186,7 @@ mod tests {
     use crate::print::tests::print_to_vec;
     use crate::text::charset::EmailCharset;
     use crate::text::misc_token::{Phrase, PhraseToken, Word};
+    use crate::text::whitespace::Comments;
     use crate::text::words::Atom;
 
     fn address_list_parsed_printed(addrlist: &[u8], printed: &[u8], parsed: AddressList<'_>) {
@@ -191,14 +228,16 @@ mod tests {
                             addrspec: AddrSpec {
                                 local_part: LocalPart(vec![LocalPartToken::Word(Word::Atom(Atom("c"[..].into())))]),
                                 domain: Domain::Atoms(vec![Atom("a"[..].into()), Atom("test"[..].into())]),
-                            },
+                            }, comments: Comments::default(),
+                            raw: RawInput::none(),
                         },
                         MailboxRef {
                             name: None,
                             addrspec: AddrSpec {
                                 local_part: LocalPart(vec![LocalPartToken::Word(Word::Atom(Atom("joe"[..].into())))]),
                                 domain: Domain::Atoms(vec![Atom("where"[..].into()), Atom("test"[..].into())])
-                            },
+                            }, comments: Comments::default(),
+                            raw: RawInput::none(),
                         },
                         MailboxRef {
                             name: Some(Phrase(vec![
@@ -207,9 +246,11 @@ mod tests {
                             addrspec: AddrSpec {
                                 local_part: LocalPart(vec![LocalPartToken::Word(Word::Atom(Atom("jdoe"[..].into())))]),
                                 domain: Domain::Atoms(vec![Atom("one"[..].into()), Atom("test"[..].into())])
-                            },
+                            }, comments: Comments::default(),
+                            raw: RawInput::none(),
                         },
                     ])),
+                    raw: RawInput::none(),
                 }),
                 AddressRef::Single(MailboxRef {
                     name: Some(Phrase(vec![
@@ -219,7 +260,8 @@ mod tests {
                     addrspec: AddrSpec {
                         local_part: LocalPart(vec![LocalPartToken::Word(Word::Atom(Atom("mary"[..].into())))]),
                         domain: Domain::Atoms(vec![Atom("x"[..].into()), Atom("test"[..].into())])
-                    },
+                    }, comments: Comments::default(),
+                    raw: RawInput::none(),
                 }),
             ],
         );
@@ -233,6 +275,18 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_address_list_recover() {
+        address_list_reprinted(
+            br#"a@x.test; "Bob" <b@x.test> <c@x.test>;G: <d@x.test> <e@x.test>;"#,
+            br#"a@x.test, "Bob" <b@x.test>, c@x.test, G:d@x.test, e@x.test;"#,
+        );
+        address_list_reprinted(
+            br#"f.g@x.test <f.g@x.test>, "Francis G. <fg@x.test>; J. R. Smith <j@x.test>"#,
+            br#""f.g@x.test" <f.g@x.test>, "Francis G." <fg@x.test>, J "." R "." Smith <j@x.test>"#,
+        );
+    }
+
     use crate::text::encoding::{EncodedWord, EncodedWordToken, QuotedChunk, QuotedWord};
     use crate::text::quoted::QuotedString;
 
@@ -259,8 +313,10 @@ mod tests {
                                 Atom("james"[..].into())
                             ))]),
                             domain: Domain::Atoms(vec![Atom("vandelay"[..].into()), Atom("com"[..].into())]),
-                        }
+                        }, comments: Comments::default(),
+                        raw: RawInput::none(),
                     },])),
+                    raw: RawInput::none(),
                 }),
                 AddressRef::Many(GroupRef {
                     name: Phrase(vec![PhraseToken::Word(Word::Atom(Atom("Friends"[..].into())))]),
@@ -272,13 +328,15 @@ mod tests {
                                     Atom("jane"[..].into())
                                 ))]),
                                 domain: Domain::Atoms(vec![Atom("example"[..].into()), Atom("com"[..].into())]),
-                            }
+                            }, comments: Comments::default(),
+                            raw: RawInput::none(),
                         },
                         MailboxRef {
                             name: Some(Phrase(vec![PhraseToken::Encoded(EncodedWord(vec![
                                 EncodedWordToken::Quoted(
                                     QuotedWord {
                                         enc: EmailCharset::utf8(),
+                                        lang: None,
                                         chunks: vec![
                                             QuotedChunk::Safe(b"John"[..].into()),
                                             QuotedChunk::Space,
@@ -294,9 +352,11 @@ mod tests {
                                     Atom("john"[..].into())
                                 ))]),
                                 domain: Domain::Atoms(vec![Atom("example"[..].into()), Atom("com"[..].into())]),
-                            }
+                            }, comments: Comments::default(),
+                            raw: RawInput::none(),
                         },
-                    ]))
+                    ])),
+                    raw: RawInput::none(),
                 }),
             ],
         );
@@ -307,6 +367,7 @@ mod tests {
             vec![AddressRef::Many(GroupRef {
                 name: Phrase(vec![PhraseToken::Word(Word::Atom(Atom("group".into())))]),
                 participants: None,
+                raw: RawInput::none(),
             })],
         );
 
@@ -316,6 +377,7 @@ mod tests {
             vec![AddressRef::Many(GroupRef {
                 name: Phrase(vec![PhraseToken::Word(Word::Atom(Atom("group".into())))]),
                 participants: None,
+                raw: RawInput::none(),
             })],
         );
     }
@@ -328,6 +390,7 @@ mod tests {
             vec![AddressRef::Many(GroupRef {
                 name: Phrase(vec![PhraseToken::Word(Word::Atom(Atom("group".into())))]),
                 participants: None,
+                raw: RawInput::none(),
             })],
         );
 
@@ -337,6 +400,7 @@ mod tests {
             vec![AddressRef::Many(GroupRef {
                 name: Phrase(vec![PhraseToken::Word(Word::Atom(Atom("group".into())))]),
                 participants: None,
+                raw: RawInput::none(),
             })],
         )
     }
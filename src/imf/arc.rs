@@ -0,0 +1,663 @@
+#[cfg(feature = "arbitrary")]
+use arbitrary::Arbitrary;
+use bounded_static::ToStatic;
+use nom::{
+    bytes::complete::{tag, tag_no_case},
+    character::complete::digit1,
+    combinator::{map, map_res, opt},
+    sequence::{delimited, pair, tuple},
+    IResult,
+};
+use std::borrow::Cow;
+
+#[cfg(feature = "arbitrary")]
+use crate::arbitrary_utils::arbitrary_vec_where;
+use crate::dkim::{tag_list, Tag};
+#[cfg(feature = "arbitrary")]
+use crate::fuzz_eq::FuzzEq;
+use crate::i18n::ContainsUtf8;
+use crate::imf::TraceField;
+use crate::print::{Formatter, Print};
+use crate::text::misc_token::{unstructured, Unstructured};
+use crate::text::whitespace::cfws;
+use eml_codec_derives::instrument_input;
+
+// ARC header fields (RFC 8617).
+//
+// The three ARC header fields are trace fields: each intermediary adds a new
+// "ARC set" at the top of the message, made of one instance of each field
+// sharing the same instance number (`i=` tag).
+
+/// Maximum number of ARC sets in a chain (RFC 8617, section 4.2.1)
+pub const MAX_INSTANCE: u32 = 50;
+
+// Prints a tag list, inserting the `fixed` tags back at their position in the
+// list. The order of the tags must be preserved, as ARC header fields are
+// themselves signed.
+fn print_tags(fmt: &mut impl Formatter, mut fixed: Vec<(usize, Tag)>, tags: &[Tag]) {
+    let mut all: Vec<&Tag> = tags.iter().collect();
+    fixed.sort_by_key(|(pos, _)| *pos);
+    for (pos, t) in &fixed {
+        all.insert((*pos).min(all.len()), t);
+    }
+    for (i, t) in all.into_iter().enumerate() {
+        if i > 0 {
+            fmt.write_bytes(b";");
+            fmt.write_fws();
+        }
+        t.print(fmt);
+    }
+}
+
+/// A tag of an ARC header field that is also represented as a typed value
+/// (`i=`, `cv=`), as found in the input.
+#[derive(Clone, Debug, PartialEq, ToStatic)]
+pub struct ParsedTag<'a> {
+    /// Position of the tag in the complete tag list
+    pub position: usize,
+    pub tag: Tag<'a>,
+}
+
+// Returns the tag to print for a typed value, with its position. The tag found
+// in the input is kept as is if it still holds the same value, as relaxed
+// canonicalization does not normalize tag values (e.g. `cv=Pass`). Otherwise,
+// a new tag is built from `value`, at the original position, or at `position`
+// for the header fields built by the caller.
+fn fixed_tag<'a>(
+    parsed: &Option<ParsedTag<'a>>,
+    position: usize,
+    unchanged: impl Fn(&Tag) -> bool,
+    name: &'static [u8],
+    value: &[u8],
+) -> (usize, Tag<'a>) {
+    match parsed {
+        Some(p) if unchanged(&p.tag) => (p.position, p.tag.clone()),
+        _ => (
+            parsed.as_ref().map_or(position, |p| p.position),
+            Tag {
+                name: Cow::Borrowed(name),
+                value: vec![Cow::Owned(value.to_vec())],
+            },
+        ),
+    }
+}
+
+// Extracts the tag named `name` from `tags`, with its position. Fails if the
+// tag is missing or if it appears several times.
+fn take_tag<'a>(tags: &mut Vec<Tag<'a>>, name: &[u8]) -> Option<(usize, Tag<'a>)> {
+    let mut found = tags.iter().enumerate().filter(|(_, t)| t.name == name);
+    let (idx, _) = found.next()?;
+    if found.next().is_some() {
+        return None;
+    }
+    Some((idx, tags.remove(idx)))
+}
+
+fn instance_value(t: &Tag) -> Option<u32> {
+    match t.value.as_slice() {
+        [v] if v.iter().all(u8::is_ascii_digit) => std::str::from_utf8(v).ok()?.parse().ok(),
+        _ => None,
+    }
+}
+
+fn chain_validation_value(t: &Tag) -> Option<ChainValidation> {
+    match t.value.as_slice() {
+        [v] => ChainValidation::from_bytes(v),
+        _ => None,
+    }
+}
+
+/// Chain validation status (`cv=` tag of ARC-Seal)
+#[derive(Clone, Copy, ContainsUtf8, Debug, PartialEq, Eq, ToStatic)]
+#[cfg_attr(feature = "arbitrary", derive(Arbitrary, FuzzEq))]
+#[contains_utf8(false)]
+pub enum ChainValidation {
+    None,
+    Fail,
+    Pass,
+}
+impl ChainValidation {
+    fn from_bytes(v: &[u8]) -> Option<Self> {
+        match v.to_ascii_lowercase().as_slice() {
+            b"none" => Some(Self::None),
+            b"fail" => Some(Self::Fail),
+            b"pass" => Some(Self::Pass),
+            _ => None,
+        }
+    }
+}
+impl ChainValidation {
+    pub fn as_bytes(&self) -> &'static [u8] {
+        match self {
+            Self::None => b"none",
+            Self::Fail => b"fail",
+            Self::Pass => b"pass",
+        }
+    }
+}
+impl Print for ChainValidation {
+    fn print(&self, fmt: &mut impl Formatter) {
+        fmt.write_bytes(self.as_bytes())
+    }
+}
+
+/// ARC-Seal header field body (RFC 8617, section 4.1.3)
+#[derive(Clone, ContainsUtf8, Debug, PartialEq, ToStatic)]
+#[cfg_attr(feature = "arbitrary", derive(FuzzEq))]
+pub struct Seal<'a> {
+    #[contains_utf8(ignore)]
+    #[cfg_attr(feature = "arbitrary", fuzz_eq(use_eq))]
+    pub instance: u32,
+    pub cv: ChainValidation,
+    /// The other tags (`a=`, `b=`, `d=`, `s=`, `t=`, ...), in order
+    pub tags: Vec<Tag<'a>>,
+    /// The `i=` and `cv=` tags as found in the input. `None` for a seal built
+    /// by the caller, whose `i=` and `cv=` tags are printed first.
+    #[cfg_attr(feature = "arbitrary", fuzz_eq(ignore))]
+    #[contains_utf8(ignore)]
+    pub instance_tag: Option<ParsedTag<'a>>,
+    #[cfg_attr(feature = "arbitrary", fuzz_eq(ignore))]
+    #[contains_utf8(ignore)]
+    pub cv_tag: Option<ParsedTag<'a>>,
+}
+impl<'a> Print for Seal<'a> {
+    fn print(&self, fmt: &mut impl Formatter) {
+        let instance = self.instance.to_string();
+        let fixed = vec![
+            fixed_tag(
+                &self.instance_tag,
+                0,
+                |t| instance_value(t) == Some(self.instance),
+                b"i",
+                instance.as_bytes(),
+            ),
+            fixed_tag(
+                &self.cv_tag,
+                1,
+                |t| chain_validation_value(t) == Some(self.cv),
+                b"cv",
+                self.cv.as_bytes(),
+            ),
+        ];
+        print_tags(fmt, fixed, &self.tags);
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for Seal<'a> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Seal {
+            instance: u.arbitrary()?,
+            cv: u.arbitrary()?,
+            tags: arbitrary_vec_where(u, |t: &Tag| &t.name[..] != b"i" && &t.name[..] != b"cv")?,
+            instance_tag: None,
+            cv_tag: None,
+        })
+    }
+}
+
+#[instrument_input("tracing")]
+pub fn seal(input: &[u8]) -> IResult<&[u8], Seal<'_>> {
+    map_res(tag_list, |mut tags| {
+        let (i_pos, i) = take_tag(&mut tags, b"i").ok_or(())?;
+        let instance = instance_value(&i).ok_or(())?;
+        let (mut cv_pos, cv_tag) = take_tag(&mut tags, b"cv").ok_or(())?;
+        let cv = chain_validation_value(&cv_tag).ok_or(())?;
+        // `cv_pos` is relative to the list without the `i=` tag
+        if cv_pos >= i_pos {
+            cv_pos += 1;
+        }
+        Ok::<_, ()>(Seal {
+            instance,
+            cv,
+            tags,
+            instance_tag: Some(ParsedTag {
+                position: i_pos,
+                tag: i,
+            }),
+            cv_tag: Some(ParsedTag {
+                position: cv_pos,
+                tag: cv_tag,
+            }),
+        })
+    })(input)
+}
+
+/// ARC-Message-Signature header field body (RFC 8617, section 4.1.2)
+///
+/// Apart from the instance number, it has the same tags as a DKIM-Signature.
+#[derive(Clone, ContainsUtf8, Debug, PartialEq, ToStatic)]
+#[cfg_attr(feature = "arbitrary", derive(FuzzEq))]
+pub struct MessageSignature<'a> {
+    #[contains_utf8(ignore)]
+    #[cfg_attr(feature = "arbitrary", fuzz_eq(use_eq))]
+    pub instance: u32,
+    /// The other tags (`a=`, `b=`, `bh=`, `h=`, ...), in order
+    pub tags: Vec<Tag<'a>>,
+    /// The `i=` tag as found in the input. `None` for a signature built by
+    /// the caller, whose `i=` tag is printed first.
+    #[cfg_attr(feature = "arbitrary", fuzz_eq(ignore))]
+    #[contains_utf8(ignore)]
+    pub instance_tag: Option<ParsedTag<'a>>,
+}
+impl<'a> Print for MessageSignature<'a> {
+    fn print(&self, fmt: &mut impl Formatter) {
+        let instance = self.instance.to_string();
+        let fixed = vec![fixed_tag(
+            &self.instance_tag,
+            0,
+            |t| instance_value(t) == Some(self.instance),
+            b"i",
+            instance.as_bytes(),
+        )];
+        print_tags(fmt, fixed, &self.tags);
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for MessageSignature<'a> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(MessageSignature {
+            instance: u.arbitrary()?,
+            tags: arbitrary_vec_where(u, |t: &Tag| &t.name[..] != b"i")?,
+            instance_tag: None,
+        })
+    }
+}
+
+#[instrument_input("tracing")]
+pub fn message_signature(input: &[u8]) -> IResult<&[u8], MessageSignature<'_>> {
+    map_res(tag_list, |mut tags| {
+        let (position, i) = take_tag(&mut tags, b"i").ok_or(())?;
+        let instance = instance_value(&i).ok_or(())?;
+        Ok::<_, ()>(MessageSignature {
+            instance,
+            tags,
+            instance_tag: Some(ParsedTag { position, tag: i }),
+        })
+    })(input)
+}
+
+/// ARC-Authentication-Results header field body (RFC 8617, section 4.1.1)
+///
+/// The results themselves (authserv-id and method results, see RFC 8601) are
+/// kept unstructured.
+#[derive(Clone, ContainsUtf8, Debug, PartialEq, ToStatic)]
+#[cfg_attr(feature = "arbitrary", derive(Arbitrary, FuzzEq))]
+pub struct AuthenticationResults<'a> {
+    #[contains_utf8(ignore)]
+    #[cfg_attr(feature = "arbitrary", fuzz_eq(use_eq))]
+    pub instance: u32,
+    pub results: Unstructured<'a>,
+}
+impl<'a> Print for AuthenticationResults<'a> {
+    fn print(&self, fmt: &mut impl Formatter) {
+        fmt.write_bytes(b"i=");
+        fmt.write_bytes(self.instance.to_string().as_bytes());
+        fmt.write_bytes(b";");
+        self.results.print(fmt);
+    }
+}
+
+/// ```abnf
+/// arc-info = instance [CFWS] ";" authres-payload
+/// instance = [CFWS] %s"i" [CFWS] "=" [CFWS] inst-num
+/// ```
+#[instrument_input("tracing")]
+pub fn authentication_results(input: &[u8]) -> IResult<&[u8], AuthenticationResults<'_>> {
+    map(
+        pair(
+            delimited(
+                tuple((
+                    opt(cfws),
+                    tag_no_case(b"i"),
+                    opt(cfws),
+                    tag(b"="),
+                    opt(cfws),
+                )),
+                map_res(digit1, |d| std::str::from_utf8(d).unwrap().parse::<u32>()),
+                pair(opt(cfws), tag(b";")),
+            ),
+            unstructured,
+        ),
+        |(instance, results)| AuthenticationResults { instance, results },
+    )(input)
+}
+
+/// The three kinds of ARC header fields
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Header {
+    Seal,
+    MessageSignature,
+    AuthenticationResults,
+}
+
+/// Structural error in an ARC chain (RFC 8617, section 5.2)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChainError {
+    /// An instance number is 0, above `MAX_INSTANCE`, or (when adding a new
+    /// set) not the next instance number of the chain
+    InvalidInstance(u32),
+    /// A header field is missing from the set with the given instance
+    Missing { instance: u32, header: Header },
+    /// A header field appears several times in the set with the given instance
+    Duplicate { instance: u32, header: Header },
+    /// The `cv=` value of a seal is not the one expected at its position in
+    /// the chain ("none" for instance 1, "pass" otherwise)
+    UnexpectedChainValidation { instance: u32, cv: ChainValidation },
+    /// The most recent seal has `cv=fail`: the chain was already found
+    /// to be broken by the last intermediary
+    Failed { instance: u32 },
+}
+
+/// A complete ARC set: the three header fields with the same instance number.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Set<'a, 'b> {
+    pub instance: u32,
+    pub seal: &'b Seal<'a>,
+    pub message_signature: &'b MessageSignature<'a>,
+    pub authentication_results: &'b AuthenticationResults<'a>,
+}
+
+/// Group the ARC header fields of a trace section into sets, and check the
+/// structure of the chain they form.
+///
+/// On success, the sets are returned ordered by instance number (oldest first).
+/// An empty chain (no ARC header fields) is valid: its chain validation
+/// status is "none".
+pub fn chain<'a, 'b>(trace: &'b [TraceField<'a>]) -> Result<Vec<Set<'a, 'b>>, ChainError> {
+    type Slot<'c, T> = Option<Result<&'c T, ()>>;
+    fn add<'c, T>(slot: &mut Slot<'c, T>, x: &'c T) {
+        *slot = match slot {
+            None => Some(Ok(x)),
+            Some(_) => Some(Err(())),
+        }
+    }
+    fn get<'c, T>(slot: Slot<'c, T>, instance: u32, header: Header) -> Result<&'c T, ChainError> {
+        match slot {
+            None => Err(ChainError::Missing { instance, header }),
+            Some(Err(())) => Err(ChainError::Duplicate { instance, header }),
+            Some(Ok(x)) => Ok(x),
+        }
+    }
+
+    let mut slots: Vec<(
+        Slot<Seal>,
+        Slot<MessageSignature>,
+        Slot<AuthenticationResults>,
+    )> = vec![];
+    for f in trace {
+        let instance = match f {
+            TraceField::ArcSeal(s) => s.instance,
+            TraceField::ArcMessageSignature(s) => s.instance,
+            TraceField::ArcAuthenticationResults(r) => r.instance,
+            TraceField::Received(_) | TraceField::ReturnPath(_) => continue,
+        };
+        if instance == 0 || instance > MAX_INSTANCE {
+            return Err(ChainError::InvalidInstance(instance));
+        }
+        let idx = instance as usize - 1;
+        if slots.len() <= idx {
+            slots.resize(idx + 1, (None, None, None));
+        }
+        match f {
+            TraceField::ArcSeal(s) => add(&mut slots[idx].0, s),
+            TraceField::ArcMessageSignature(s) => add(&mut slots[idx].1, s),
+            TraceField::ArcAuthenticationResults(r) => add(&mut slots[idx].2, r),
+            TraceField::Received(_) | TraceField::ReturnPath(_) => unreachable!(),
+        }
+    }
+
+    let mut sets = Vec::with_capacity(slots.len());
+    for (idx, (seal, ams, aar)) in slots.into_iter().enumerate() {
+        let instance = idx as u32 + 1;
+        let set = Set {
+            instance,
+            seal: get(seal, instance, Header::Seal)?,
+            message_signature: get(ams, instance, Header::MessageSignature)?,
+            authentication_results: get(aar, instance, Header::AuthenticationResults)?,
+        };
+        sets.push(set);
+    }
+
+    if let Some(last) = sets.last() {
+        if last.seal.cv == ChainValidation::Fail {
+            return Err(ChainError::Failed {
+                instance: last.instance,
+            });
+        }
+    }
+    for set in &sets {
+        let expected = if set.instance == 1 {
+            ChainValidation::None
+        } else {
+            ChainValidation::Pass
+        };
+        if set.seal.cv != expected {
+            return Err(ChainError::UnexpectedChainValidation {
+                instance: set.instance,
+                cv: set.seal.cv,
+            });
+        }
+    }
+    Ok(sets)
+}
+
+/// Instance number to use for a new ARC set added on top of `trace`.
+pub fn next_instance(trace: &[TraceField]) -> u32 {
+    trace
+        .iter()
+        .filter_map(|f| match f {
+            TraceField::ArcSeal(s) => Some(s.instance),
+            TraceField::ArcMessageSignature(s) => Some(s.instance),
+            TraceField::ArcAuthenticationResults(r) => Some(r.instance),
+            TraceField::Received(_) | TraceField::ReturnPath(_) => None,
+        })
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::print::tests::print_to_vec;
+    use crate::text::misc_token::{UnstrToken, UnstrTxtKind};
+
+    fn t<'a>(name: &'a [u8], value: &[&'a [u8]]) -> Tag<'a> {
+        Tag {
+            name: name.into(),
+            value: value.iter().map(|v| Cow::Borrowed(*v)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_seal() {
+        let input =
+            b" i=2; cv=Pass; a=rsa-sha256; d=example.org;\r\n s=sel; t=12345;\r\n b=Ab+C\r\n  dE=;";
+        let (rest, s) = seal(input).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            s,
+            Seal {
+                instance: 2,
+                cv: ChainValidation::Pass,
+                tags: vec![
+                    t(b"a", &[b"rsa-sha256"]),
+                    t(b"d", &[b"example.org"]),
+                    t(b"s", &[b"sel"]),
+                    t(b"t", &[b"12345"]),
+                    t(b"b", &[b"Ab+C", b"dE="]),
+                ],
+                instance_tag: Some(ParsedTag {
+                    position: 0,
+                    tag: t(b"i", &[b"2"]),
+                }),
+                cv_tag: Some(ParsedTag {
+                    position: 1,
+                    tag: t(b"cv", &[b"Pass"]),
+                }),
+            }
+        );
+        // the original tag values are kept, as they are signed
+        assert_eq!(
+            String::from_utf8_lossy(&print_to_vec(&s)),
+            "i=2; cv=Pass; a=rsa-sha256; d=example.org; s=sel; t=12345; b=Ab+C dE="
+        );
+
+        // a modified value is printed at the position of the original tag
+        let mut s = s;
+        s.cv = ChainValidation::Fail;
+        assert_eq!(
+            String::from_utf8_lossy(&print_to_vec(s)),
+            "i=2; cv=fail; a=rsa-sha256; d=example.org; s=sel; t=12345; b=Ab+C dE="
+        );
+    }
+
+    #[test]
+    fn test_tag_order() {
+        // the tags are printed back in their original order
+        let input = b"a=rsa-sha256; cv=none; d=example.org; i=1; b=c2VhbA==";
+        let (_, s) = seal(input).unwrap();
+        assert_eq!(s.instance_tag.as_ref().map(|t| t.position), Some(3));
+        assert_eq!(s.cv_tag.as_ref().map(|t| t.position), Some(1));
+        assert_eq!(
+            String::from_utf8_lossy(&print_to_vec(s)),
+            String::from_utf8_lossy(input)
+        );
+
+        let input = b"a=rsa-sha256; d=example.org; i=1; bh=Ym9keQ==";
+        let (_, s) = message_signature(input).unwrap();
+        assert_eq!(s.instance_tag.as_ref().map(|t| t.position), Some(2));
+        assert_eq!(
+            String::from_utf8_lossy(&print_to_vec(s)),
+            String::from_utf8_lossy(input)
+        );
+    }
+
+    #[test]
+    fn test_seal_invalid() {
+        // missing cv
+        assert!(seal(b"i=1; a=rsa-sha256").is_err());
+        // duplicate instance
+        assert!(seal(b"i=1; i=2; cv=none").is_err());
+        // invalid instance
+        assert!(seal(b"i=one; cv=none").is_err());
+        assert!(message_signature(b"a=rsa-sha256; d=example.org").is_err());
+    }
+
+    #[test]
+    fn test_message_signature() {
+        let (rest, s) = message_signature(
+            b" i=1; a=rsa-sha256; c=relaxed/relaxed; d=example.org;\r\n h=from:to:subject; bh=abc=",
+        )
+        .unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(s.instance, 1);
+        assert_eq!(
+            s.tags,
+            vec![
+                t(b"a", &[b"rsa-sha256"]),
+                t(b"c", &[b"relaxed/relaxed"]),
+                t(b"d", &[b"example.org"]),
+                t(b"h", &[b"from:to:subject"]),
+                t(b"bh", &[b"abc="]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_authentication_results() {
+        let (rest, r) =
+            authentication_results(b" i=1; mx.example.org; spf=pass smtp.mailfrom=example.org")
+                .unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(r.instance, 1);
+        assert_eq!(
+            r.results.0[..4],
+            [
+                UnstrToken::from_plain(" ", UnstrTxtKind::Fws),
+                UnstrToken::from_plain("mx.example.org;", UnstrTxtKind::Txt),
+                UnstrToken::from_plain(" ", UnstrTxtKind::Fws),
+                UnstrToken::from_plain("spf=pass", UnstrTxtKind::Txt),
+            ]
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&print_to_vec(r)),
+            "i=1; mx.example.org; spf=pass smtp.mailfrom=example.org"
+        );
+    }
+
+    fn set<'a>(instance: u32, cv: ChainValidation) -> Vec<TraceField<'a>> {
+        vec![
+            TraceField::ArcSeal(Seal {
+                instance,
+                cv,
+                tags: vec![],
+                instance_tag: None,
+                cv_tag: None,
+            }),
+            TraceField::ArcMessageSignature(MessageSignature {
+                instance,
+                tags: vec![],
+                instance_tag: None,
+            }),
+            TraceField::ArcAuthenticationResults(AuthenticationResults {
+                instance,
+                results: Unstructured(vec![]),
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_chain() {
+        assert_eq!(chain(&[]), Ok(vec![]));
+        assert_eq!(next_instance(&[]), 1);
+
+        let trace = [set(2, ChainValidation::Pass), set(1, ChainValidation::None)].concat();
+        let sets = chain(&trace).unwrap();
+        assert_eq!(sets.len(), 2);
+        assert_eq!(sets[0].instance, 1);
+        assert_eq!(sets[1].seal.cv, ChainValidation::Pass);
+        assert_eq!(next_instance(&trace), 3);
+    }
+
+    #[test]
+    fn test_chain_invalid() {
+        // gap
+        let trace = [set(3, ChainValidation::Pass), set(1, ChainValidation::None)].concat();
+        assert_eq!(
+            chain(&trace),
+            Err(ChainError::Missing {
+                instance: 2,
+                header: Header::Seal
+            })
+        );
+
+        // duplicate
+        let mut trace = set(1, ChainValidation::None);
+        trace.push(trace[1].clone());
+        assert_eq!(
+            chain(&trace),
+            Err(ChainError::Duplicate {
+                instance: 1,
+                header: Header::MessageSignature
+            })
+        );
+
+        // cv
+        let trace = [set(2, ChainValidation::None), set(1, ChainValidation::None)].concat();
+        assert_eq!(
+            chain(&trace),
+            Err(ChainError::UnexpectedChainValidation {
+                instance: 2,
+                cv: ChainValidation::None
+            })
+        );
+        let trace = [set(2, ChainValidation::Fail), set(1, ChainValidation::None)].concat();
+        assert_eq!(chain(&trace), Err(ChainError::Failed { instance: 2 }));
+
+        assert_eq!(
+            chain(&set(51, ChainValidation::Pass)),
+            Err(ChainError::InvalidInstance(51))
+        );
+    }
+}
@@ -1,13 +1,14 @@
 #[cfg(feature = "arbitrary")]
 use arbitrary::Arbitrary;
 use bounded_static::{IntoBoundedStatic, ToBoundedStatic};
-use chrono::{Datelike, FixedOffset, NaiveDate, NaiveTime, Timelike};
+use chrono::{Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
 use nom::{
     branch::alt,
     bytes::complete::{is_a, tag, tag_no_case, take_while_m_n},
     character,
-    character::complete::{alphanumeric1, digit0},
-    combinator::{eof, map, map_opt, opt, value},
+    character::complete::{alpha0, alpha1, alphanumeric1, digit0, digit1},
+    combinator::{consumed, eof, map, map_opt, not, opt, value},
+    multi::many0_count,
     sequence::{delimited, pair, preceded, terminated, tuple},
     IResult,
 };
@@ -15,11 +16,16 @@ use std::fmt::{Debug, Formatter};
 #[cfg(feature = "tracing")]
 use tracing::warn;
 
+use crate::diagnostics::{self, discard_on_failure, obsolete, DiagnosticKind, Severity};
 #[cfg(feature = "arbitrary")]
 use crate::fuzz_eq::FuzzEq;
 use crate::i18n::ContainsUtf8;
 use crate::print::{Formatter as PFmt, Print};
-use crate::text::whitespace::{cfws, fws};
+use crate::text::whitespace::{
+    cfws, comments_in, fws, print_comments, print_leading_comments, Comments,
+};
+#[cfg(feature = "tracing-recover")]
+use crate::utils::bytes_to_trace_string;
 use eml_codec_derives::instrument_input;
 
 const MIN: i32 = 60;
@@ -32,26 +38,67 @@ const MONTHS: &[&[u8]] = &[
 // NOTE: must satisfy the following properties:
 // - timezone offset: must be a round hours+minutes (no seconds)
 // - year must be after 1900 or later
+// - if `unknown_offset` is set, the timezone offset must be zero
 #[derive(Clone, ContainsUtf8, PartialEq)]
-#[contains_utf8(false)]
-pub struct DateTime(pub chrono::DateTime<FixedOffset>);
+pub struct DateTime {
+    #[contains_utf8(ignore)]
+    pub datetime: chrono::DateTime<FixedOffset>,
+    // The date is a local time whose offset from UTC is unknown: `datetime`
+    // then holds that local time with a zero offset, which does not mean that
+    // it is in UTC. This is written `-0000` (RFC5322, section 3.3).
+    #[contains_utf8(ignore)]
+    pub unknown_offset: bool,
+    // Comments appearing in the date, typically the name of the time zone,
+    // as in `+0200 (CEST)`
+    pub comments: Comments<'static>,
+}
 
 impl DateTime {
+    pub fn new(datetime: chrono::DateTime<FixedOffset>) -> Self {
+        Self {
+            datetime,
+            unknown_offset: false,
+            comments: Comments::default(),
+        }
+    }
+
+    /// A local time whose offset from UTC is unknown.
+    pub fn with_unknown_offset(local: NaiveDateTime) -> Self {
+        Self {
+            datetime: local.and_utc().into(),
+            unknown_offset: true,
+            comments: Comments::default(),
+        }
+    }
+
+    /// The offset from UTC, or `None` if it is unknown.
+    pub fn offset(&self) -> Option<FixedOffset> {
+        (!self.unknown_offset).then(|| *self.datetime.offset())
+    }
+
     // Used as placeholder value for a missing or invalid date
     pub fn placeholder() -> Self {
-        Self(chrono::DateTime::UNIX_EPOCH.into())
+        Self::new(chrono::DateTime::UNIX_EPOCH.into())
     }
 }
 
 impl Debug for DateTime {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        Debug::fmt(&self.0, f)
+        if self.comments.is_empty() && !self.unknown_offset {
+            Debug::fmt(&self.datetime, f)
+        } else {
+            f.debug_struct("DateTime")
+                .field("datetime", &self.datetime)
+                .field("unknown_offset", &self.unknown_offset)
+                .field("comments", &self.comments)
+                .finish()
+        }
     }
 }
 
 impl AsRef<chrono::DateTime<FixedOffset>> for DateTime {
     fn as_ref(&self) -> &chrono::DateTime<FixedOffset> {
-        &self.0
+        &self.datetime
     }
 }
 
@@ -75,48 +122,65 @@ impl<'a> Arbitrary<'a> for DateTime {
         let timestamp: i64 = u.arbitrary()?;
         let d = chrono::DateTime::from_timestamp_secs(timestamp)
             .ok_or(arbitrary::Error::IncorrectFormat)?;
-        let tz_mins = u.int_in_range(-24 * 60 + 1..=24 * 60 - 1)?;
+        let unknown_offset: bool = u.arbitrary()?;
+        let tz_mins = if unknown_offset {
+            0
+        } else {
+            u.int_in_range(-24 * 60 + 1..=24 * 60 - 1)?
+        };
         let tz = FixedOffset::east_opt(tz_mins * 60).unwrap();
         let d: chrono::DateTime<FixedOffset> = d.with_timezone(&tz);
-        if d.year() < 1900 {
-            Ok(Self(chrono::DateTime::UNIX_EPOCH.into()))
+        let d = if d.year() < 1900 {
+            chrono::DateTime::UNIX_EPOCH.into()
         } else {
-            Ok(Self(d))
-        }
+            d
+        };
+        Ok(Self {
+            datetime: d,
+            unknown_offset,
+            comments: Comments::arbitrary(u)?.into_static(),
+        })
     }
 }
 #[cfg(feature = "arbitrary")]
 impl FuzzEq for DateTime {
     fn fuzz_eq(&self, other: &Self) -> bool {
-        self.0 == other.0
+        self.datetime == other.datetime && self.unknown_offset == other.unknown_offset
     }
 }
 
 impl Print for DateTime {
     fn print(&self, fmt: &mut impl PFmt) {
+        print_leading_comments(fmt, &self.comments);
         // date
-        fmt.write_bytes(format!("{},", self.0.weekday()).as_bytes());
+        fmt.write_bytes(format!("{},", self.datetime.weekday()).as_bytes());
         fmt.write_fws();
-        fmt.write_bytes(format!("{}", self.0.day()).as_bytes());
+        fmt.write_bytes(format!("{}", self.datetime.day()).as_bytes());
         fmt.write_fws();
-        fmt.write_bytes(MONTHS[self.0.month0() as usize]);
+        fmt.write_bytes(MONTHS[self.datetime.month0() as usize]);
         fmt.write_fws();
-        fmt.write_bytes(format!("{}", self.0.year()).as_bytes());
+        fmt.write_bytes(format!("{}", self.datetime.year()).as_bytes());
         fmt.write_fws();
         // time-of-day
-        fmt.write_bytes(format!("{:02}", self.0.hour()).as_bytes());
+        fmt.write_bytes(format!("{:02}", self.datetime.hour()).as_bytes());
         fmt.write_bytes(b":");
-        fmt.write_bytes(format!("{:02}", self.0.minute()).as_bytes());
+        fmt.write_bytes(format!("{:02}", self.datetime.minute()).as_bytes());
         fmt.write_bytes(b":");
-        fmt.write_bytes(format!("{:02}", self.0.second()).as_bytes());
+        fmt.write_bytes(format!("{:02}", self.datetime.second()).as_bytes());
         fmt.write_fws();
         // zone
-        let offset_secs = self.0.offset().local_minus_utc();
+        if self.unknown_offset {
+            fmt.write_bytes(b"-0000");
+            print_comments(fmt, &self.comments);
+            return;
+        }
+        let offset_secs = self.datetime.offset().local_minus_utc();
         let sign = if offset_secs >= 0 { b"+" } else { b"-" };
         let offset_mins = offset_secs.abs().rem_euclid(HOUR).div_euclid(MIN);
         let offset_hours = offset_secs.abs().div_euclid(HOUR);
         fmt.write_bytes(sign);
         fmt.write_bytes(format!("{:02}{:02}", offset_hours, offset_mins).as_bytes());
+        print_comments(fmt, &self.comments)
     }
 }
 
@@ -131,37 +195,61 @@ impl Print for DateTime {
 /// We additionally allow dates with a missing zone (followed by end of input),
 /// which appear in some real world emails.
 ///
-/// ## @FIXME - known bugs
+/// The zone `-0000`, obsolete military zones (which are specified incorrectly
+/// in RFC822), unknown legacy zones and missing zones all result in a date
+/// with an unknown offset from UTC, as specified in RFC5322, section 4.3.
 ///
-///   - `-0000` means NaiveDateTime, a date without a timezone
-/// while this library interprets it as +0000 aka UTC.
-///   - Obsolete military zones should be considered as NaiveTime
-/// due to an error in RFC0822 but are interpreted as their respective
-/// timezone according to the RFC5322 definition
+/// If the input does not follow the RFC grammar, we try to recover common
+/// non-RFC formats found in real world emails (see `recover_date_time`).
 #[instrument_input("tracing")]
 pub fn date_time(input: &[u8]) -> IResult<&[u8], DateTime> {
+    alt((discard_on_failure(rfc_date_time), recover_date_time))(input)
+}
+
+#[instrument_input("tracing")]
+fn rfc_date_time(input: &[u8]) -> IResult<&[u8], DateTime> {
     map_opt(
-        terminated(
+        consumed(terminated(
             tuple((
                 opt(terminated(
-                    alt((strict_day_of_week, obs_day_of_week)),
+                    alt((
+                        strict_day_of_week,
+                        obsolete("obs-day-of-week", obs_day_of_week),
+                    )),
                     tag(","),
                 )),
-                alt((strict_date, obs_date)),
-                alt((strict_time_of_day, obs_time_of_day)),
+                alt((strict_date, obsolete("obs-date", obs_date))),
+                alt((
+                    strict_time_of_day,
+                    obsolete("obs-time-of-day", obs_time_of_day),
+                )),
                 alt((strict_zone, obs_zone, no_zone_eof)),
             )),
             opt(cfws),
-        ),
-        |(_, date, time, tz)| {
-            date.and_time(time)
-                .and_local_timezone(tz)
-                .earliest()
-                .map(DateTime)
-        },
+        )),
+        |(raw, (_, date, time, tz))| make_date_time(raw, date, time, tz),
     )(input)
 }
 
+// `tz` is `None` if the offset is unknown.
+fn make_date_time(
+    raw: &[u8],
+    date: NaiveDate,
+    time: NaiveTime,
+    tz: Option<FixedOffset>,
+) -> Option<DateTime> {
+    // a zero offset stands for an unknown offset
+    let offset = tz.unwrap_or(FixedOffset::east_opt(0).unwrap());
+    date.and_time(time)
+        .and_local_timezone(offset)
+        .earliest()
+        .map(|datetime| DateTime {
+            datetime,
+            unknown_offset: tz.is_none(),
+            comments: comments_in(raw).into_static(),
+        })
+}
+
 ///    day-of-week     =   ([FWS] day-name) / obs-day-of-week
 #[instrument_input("tracing")]
 fn strict_day_of_week(input: &[u8]) -> IResult<&[u8], &[u8]> {
@@ -269,18 +357,22 @@ fn obs_year(input: &[u8]) -> IResult<&[u8], i32> {
         ),
         |cap: &[u8]| {
             let year_txt = encoding_rs::UTF_8.decode_without_bom_handling(cap).0;
-            let d = year_txt.parse::<i32>().unwrap_or(0);
-            if (0..=49).contains(&d) {
-                2000 + d
-            } else if (50..=999).contains(&d) {
-                1900 + d
-            } else {
-                d
-            }
+            interpret_year(year_txt.parse::<i32>().unwrap_or(0))
         },
     )(input)
 }
 
+// Interpretation of two and three digit years (RFC5322, section 4.3)
+fn interpret_year(d: i32) -> i32 {
+    if (0..=49).contains(&d) {
+        2000 + d
+    } else if (50..=999).contains(&d) {
+        1900 + d
+    } else {
+        d
+    }
+}
+
 ///   time-of-day     =   hour ":" minute [ ":" second ]
 #[instrument_input("tracing")]
 fn strict_time_of_day(input: &[u8]) -> IResult<&[u8], NaiveTime> {
@@ -327,8 +419,10 @@ fn obs_time_digit(input: &[u8]) -> IResult<&[u8], u32> {
 /// ```abnf
 ///   zone            =   (FWS ( "+" / "-" ) 4DIGIT) / (FWS obs-zone)
 /// ```
+///
+/// Returns `None` for `-0000`, which indicates an unknown offset.
 #[instrument_input("tracing")]
-fn strict_zone(input: &[u8]) -> IResult<&[u8], FixedOffset> {
+fn strict_zone(input: &[u8]) -> IResult<&[u8], Option<FixedOffset>> {
     map_opt(
         tuple((
             opt(fws),
@@ -348,8 +442,9 @@ fn strict_zone(input: &[u8]) -> IResult<&[u8], FixedOffset> {
                 return None;
             }
             match op {
-                b"+" => FixedOffset::east_opt(zone_hour * HOUR + zone_min * MIN),
-                b"-" => FixedOffset::west_opt(zone_hour * HOUR + zone_min * MIN),
+                b"+" => FixedOffset::east_opt(zone_hour * HOUR + zone_min * MIN).map(Some),
+                b"-" if zone_hour == 0 && zone_min == 0 => Some(None),
+                b"-" => FixedOffset::west_opt(zone_hour * HOUR + zone_min * MIN).map(Some),
                 _ => unreachable!(),
             }
         },
@@ -372,76 +467,316 @@ fn strict_zone(input: &[u8]) -> IResult<&[u8], FixedOffset> {
 ///                       %d107-122 /        ; upper and lower case
 ///                                          ;
 ///                       1*(ALPHA / DIGIT)  ; Unknown legacy timezones
+///
+/// Returns `None` for military zones and unknown zones, which indicate an
+/// unknown offset.
+///
+/// We additionally give their usual meaning to common non-RFC zone names (see
+/// `common_zone`), which are reported as recovered instead of obsolete.
 #[instrument_input("tracing")]
-#[expect(clippy::identity_op)]
-#[expect(clippy::erasing_op)]
-fn obs_zone(input: &[u8]) -> IResult<&[u8], FixedOffset> {
-    // The writing of this function is volontarily verbose
-    // to keep it straightforward to understand.
-    preceded(
-        opt(fws),
-        map_opt(alphanumeric1, |zname: &[u8]| {
-            let zname = zname.to_ascii_lowercase();
-            match zname.as_slice() {
-                // Legacy UTC/GMT
-                b"utc" | b"ut" | b"gmt" => FixedOffset::west_opt(0 * HOUR),
-                // USA Timezones
-                b"edt" => FixedOffset::west_opt(4 * HOUR),
-                b"est" | b"cdt" => FixedOffset::west_opt(5 * HOUR),
-                b"cst" | b"mdt" => FixedOffset::west_opt(6 * HOUR),
-                b"mst" | b"pdt" => FixedOffset::west_opt(7 * HOUR),
-                b"pst" => FixedOffset::west_opt(8 * HOUR),
-                // Military Timezone UTC
-                b"z" => FixedOffset::west_opt(0 * HOUR),
-                // Military Timezones East
-                b"a" => FixedOffset::east_opt(1 * HOUR),
-                b"b" => FixedOffset::east_opt(2 * HOUR),
-                b"c" => FixedOffset::east_opt(3 * HOUR),
-                b"d" => FixedOffset::east_opt(4 * HOUR),
-                b"e" => FixedOffset::east_opt(5 * HOUR),
-                b"f" => FixedOffset::east_opt(6 * HOUR),
-                b"g" => FixedOffset::east_opt(7 * HOUR),
-                b"h" => FixedOffset::east_opt(8 * HOUR),
-                b"i" => FixedOffset::east_opt(9 * HOUR),
-                b"k" => FixedOffset::east_opt(10 * HOUR),
-                b"l" => FixedOffset::east_opt(11 * HOUR),
-                b"m" => FixedOffset::east_opt(12 * HOUR),
-                // Military Timezones West
-                b"n" => FixedOffset::west_opt(1 * HOUR),
-                b"o" => FixedOffset::west_opt(2 * HOUR),
-                b"p" => FixedOffset::west_opt(3 * HOUR),
-                b"q" => FixedOffset::west_opt(4 * HOUR),
-                b"r" => FixedOffset::west_opt(5 * HOUR),
-                b"s" => FixedOffset::west_opt(6 * HOUR),
-                b"t" => FixedOffset::west_opt(7 * HOUR),
-                b"u" => FixedOffset::west_opt(8 * HOUR),
-                b"v" => FixedOffset::west_opt(9 * HOUR),
-                b"w" => FixedOffset::west_opt(10 * HOUR),
-                b"x" => FixedOffset::west_opt(11 * HOUR),
-                b"y" => FixedOffset::west_opt(12 * HOUR),
-                // Unknown timezone
-                _ => FixedOffset::west_opt(0 * HOUR),
+fn obs_zone(input: &[u8]) -> IResult<&[u8], Option<FixedOffset>> {
+    map(
+        consumed(preceded(opt(fws), alphanumeric1)),
+        |(raw, zname): (&[u8], &[u8])| {
+            let zone = named_zone(zname);
+            if zone.is_some() && common_zone(&zname.to_ascii_lowercase()).is_some() {
+                diagnostics::diagnose!(
+                    "tracing-recover",
+                    (zone = %String::from_utf8_lossy(zname)),
+                    Recovered("non-RFC named zone"),
+                    Severity::Warning,
+                    Some(raw),
+                );
+            } else {
+                diagnostics::report(
+                    DiagnosticKind::Obsolete("obs-zone"),
+                    Severity::Warning,
+                    Some(raw),
+                );
             }
-        }),
+            zone
+        },
     )(input)
 }
 
+// Returns `None` for military zones and unknown zones.
+fn named_zone(zname: &[u8]) -> Option<FixedOffset> {
+    let zname = zname.to_ascii_lowercase();
+    // offset east of UTC, in minutes
+    let mins = match zname.as_slice() {
+        // Legacy UTC/GMT
+        b"utc" | b"ut" | b"gmt" => 0,
+        // USA Timezones
+        b"edt" => -4 * 60,
+        b"est" | b"cdt" => -5 * 60,
+        b"cst" | b"mdt" => -6 * 60,
+        b"mst" | b"pdt" => -7 * 60,
+        b"pst" => -8 * 60,
+        // RFC822 got the sign of military zones wrong, so they cannot be
+        // trusted; like unknown zones, RFC5322 says that they should be
+        // considered as "-0000".
+        [_] => return None,
+        _ => common_zone(&zname)?,
+    };
+    FixedOffset::east_opt(mins * MIN)
+}
+
+// Zone names that are not part of RFC5322 but are common in the wild, and
+// whose meaning is clear enough. (This excludes e.g. "IST", which may stand
+// for India, Ireland or Israel.) Returns an offset east of UTC, in minutes.
+fn common_zone(zname: &[u8]) -> Option<i32> {
+    Some(match zname {
+        // Europe
+        b"wet" => 0,
+        b"west" | b"cet" | b"mez" => 60,
+        b"cest" | b"mesz" | b"eet" => 2 * 60,
+        b"eest" | b"msk" => 3 * 60,
+        // Asia & Oceania
+        b"sgt" | b"hkt" | b"awst" => 8 * 60,
+        b"jst" | b"kst" => 9 * 60,
+        b"acst" => 9 * 60 + 30,
+        b"aest" => 10 * 60,
+        b"acdt" => 10 * 60 + 30,
+        b"aedt" => 11 * 60,
+        b"nzst" => 12 * 60,
+        b"nzdt" => 13 * 60,
+        // America
+        b"akdt" => -8 * 60,
+        b"akst" => -9 * 60,
+        b"hst" => -10 * 60,
+        _ => return None,
+    })
+}
+
 // This is a hack to handle dates that do not specify a timezone. Unfortunately
 // this is quite common.
-#[expect(clippy::erasing_op)]
-fn no_zone_eof(input: &[u8]) -> IResult<&[u8], FixedOffset> {
-    #[cfg(feature = "tracing-recover")]
-    warn!("missing zone from date-time");
+// The offset of such dates is unknown.
+fn no_zone_eof(input: &[u8]) -> IResult<&[u8], Option<FixedOffset>> {
+    map(pair(opt(cfws), eof), |_| {
+        diagnostics::diagnose!(
+            "tracing-recover",
+            Recovered("missing zone from date-time"),
+            Severity::Warning,
+            None,
+        );
+        None
+    })(input)
+}
+
+// Recovery of non-RFC dates ----------------------------------------------------
+
+// Best-effort parsing of dates found in real world emails that do not follow
+// the RFC grammar:
+// - ISO 8601 dates (`2023-11-21T07:07:07+02:00`);
+// - asctime-style dates (`Tue Nov 21 07:07:07 2023`, possibly with a zone
+//   before the year, as printed by `date`);
+// - dates with the month first (`Tuesday, November 21, 2023 7:07 PM`);
+// - dates with commas in odd places, dashes between the day, month and year,
+//   full day or month names, two digit years, missing seconds, fractional
+//   seconds or a 12-hour clock.
+//
+// The whole input must be recognized.
+#[instrument_input("tracing")]
+fn recover_date_time(input: &[u8]) -> IResult<&[u8], DateTime> {
     map_opt(
-        value(FixedOffset::west_opt(0 * HOUR), pair(opt(cfws), eof)),
-        |tz| tz,
+        consumed(delimited(
+            lenient_sep,
+            alt((iso8601, lenient_date_time)),
+            pair(lenient_sep, eof),
+        )),
+        |(raw, (date, time, tz))| {
+            diagnostics::diagnose!(
+                "tracing-recover",
+                (input = %bytes_to_trace_string(raw)),
+                Recovered("recovered non-RFC date-time"),
+                Severity::Warning,
+                Some(raw),
+            );
+            make_date_time(raw, date, time, tz)
+        },
+    )(input)
+}
+
+type DateTimeParts = (NaiveDate, NaiveTime, Option<FixedOffset>);
+
+//   date-time =   year "-" month "-" day ("T" / FWS) time [zone]
+//   zone      =   "Z" / ("+" / "-") 2DIGIT [":"] 2DIGIT / named-zone
+#[instrument_input("tracing")]
+fn iso8601(input: &[u8]) -> IResult<&[u8], DateTimeParts> {
+    map_opt(
+        tuple((
+            digits(4, 4),
+            tag("-"),
+            digits(1, 2),
+            tag("-"),
+            digits(1, 2),
+            alt((value((), tag_no_case("T")), value((), fws))),
+            lenient_time_of_day,
+            opt(preceded(
+                opt(fws),
+                alt((
+                    // "Z" is UTC here, not a military zone
+                    value(FixedOffset::east_opt(0), tag_no_case("Z")),
+                    lenient_zone,
+                )),
+            )),
+        )),
+        |(y, _, m, _, d, _, time, tz)| {
+            Some((NaiveDate::from_ymd_opt(y as i32, m, d)?, time, tz.flatten()))
+        },
+    )(input)
+}
+
+#[instrument_input("tracing")]
+fn lenient_date_time(input: &[u8]) -> IResult<&[u8], DateTimeParts> {
+    let zone = || opt(preceded(lenient_sep, lenient_zone));
+    let date = |y: i32, m, d| NaiveDate::from_ymd_opt(interpret_year(y), m, d);
+    preceded(
+        opt(terminated(lenient_day_name, lenient_sep)),
+        alt((
+            // 21 Nov 2023 07:07:07 +0200
+            map_opt(
+                tuple((
+                    digits(1, 2),
+                    lenient_date_sep,
+                    lenient_month,
+                    lenient_date_sep,
+                    lenient_year,
+                    lenient_sep,
+                    lenient_time_of_day,
+                    zone(),
+                )),
+                move |(d, _, m, _, y, _, time, tz)| Some((date(y, m, d)?, time, tz.flatten())),
+            ),
+            // Nov 21, 2023 07:07:07 +0200
+            map_opt(
+                tuple((
+                    lenient_month,
+                    lenient_date_sep,
+                    digits(1, 2),
+                    lenient_date_sep,
+                    lenient_year,
+                    lenient_sep,
+                    lenient_time_of_day,
+                    zone(),
+                )),
+                move |(m, _, d, _, y, _, time, tz)| Some((date(y, m, d)?, time, tz.flatten())),
+            ),
+            // Nov 21 07:07:07 CET 2023
+            map_opt(
+                tuple((
+                    lenient_month,
+                    lenient_sep,
+                    digits(1, 2),
+                    lenient_sep,
+                    lenient_time_of_day,
+                    zone(),
+                    lenient_sep,
+                    lenient_year,
+                    zone(),
+                )),
+                move |(m, _, d, _, time, tz1, _, y, tz2)| {
+                    Some((date(y, m, d)?, time, tz1.or(tz2).flatten()))
+                },
+            ),
+        )),
     )(input)
 }
 
+// Separator between the components of a date: whitespace, comments or commas.
+fn lenient_sep(input: &[u8]) -> IResult<&[u8], ()> {
+    value((), many0_count(alt((cfws, value((), tag(","))))))(input)
+}
+
+// Separator between the day, month and year, which may also be a dash.
+fn lenient_date_sep(input: &[u8]) -> IResult<&[u8], ()> {
+    value((), many0_count(alt((cfws, value((), is_a(",-"))))))(input)
+}
+
+// Between `m` and `n` digits.
+fn digits<'a>(m: usize, n: usize) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], u32> {
+    map_opt(take_while_m_n(m, n, |c: u8| c.is_ascii_digit()), |d| {
+        std::str::from_utf8(d).ok()?.parse().ok()
+    })
+}
+
+// Abbreviated or full day name
+fn lenient_day_name(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    terminated(day_name, alpha0)(input)
+}
+
+// Abbreviated or full month name
+fn lenient_month(input: &[u8]) -> IResult<&[u8], u32> {
+    terminated(month, alpha0)(input)
+}
+
+// Two to four digits, not part of a time of day
+fn lenient_year(input: &[u8]) -> IResult<&[u8], i32> {
+    map(
+        terminated(digits(2, 4), not(alt((tag(":"), digit1)))),
+        |y| y as i32,
+    )(input)
+}
+
+//   time = hour ":" minute [":" second ["." 1*DIGIT]] [[FWS] ("AM" / "PM")]
+fn lenient_time_of_day(input: &[u8]) -> IResult<&[u8], NaiveTime> {
+    map_opt(
+        tuple((
+            digits(1, 2),
+            tag(":"),
+            digits(1, 2),
+            opt(preceded(tag(":"), digits(1, 2))),
+            // fractions of seconds are ignored
+            opt(preceded(tag("."), digit1)),
+            opt(preceded(
+                opt(fws),
+                terminated(
+                    alt((
+                        value(false, tag_no_case("AM")),
+                        value(true, tag_no_case("PM")),
+                    )),
+                    not(alpha1),
+                ),
+            )),
+        )),
+        |(hour, _, minute, second, _, pm)| {
+            let hour = match pm {
+                None => hour,
+                Some(_) if hour == 0 || hour > 12 => return None,
+                Some(pm) => hour % 12 + if pm { 12 } else { 0 },
+            };
+            NaiveTime::from_hms_opt(hour, minute, second.unwrap_or(0))
+        },
+    )(input)
+}
+
+// Numeric offset, possibly with a colon, or a zone name. Returns `None` for
+// unknown zones.
+fn lenient_zone(input: &[u8]) -> IResult<&[u8], Option<FixedOffset>> {
+    alt((
+        map_opt(
+            tuple((is_a("+-"), digits(2, 2), opt(tag(":")), digits(2, 2))),
+            |(op, hours, _, mins)| {
+                if mins >= 60 {
+                    return None;
+                }
+                let secs = (hours as i32) * HOUR + (mins as i32) * MIN;
+                match op {
+                    b"-" if secs == 0 => Some(None),
+                    b"-" => FixedOffset::west_opt(secs).map(Some),
+                    _ => FixedOffset::east_opt(secs).map(Some),
+                }
+            },
+        ),
+        map(alpha1, named_zone),
+    ))(input)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::print::tests::print_to_vec;
+    use crate::text::whitespace::Comment;
     use chrono::TimeZone;
 
     fn date_parsed_printed(date: &[u8], printed: &[u8], parsed: DateTime) {
@@ -458,7 +793,7 @@ mod tests {
         date_parsed_printed(
             b"Fri, 21 Nov 1997 09:55:06 -0600",
             b"Fri, 21 Nov 1997 09:55:06 -0600",
-            DateTime(
+            DateTime::new(
                 FixedOffset::west_opt(6 * HOUR)
                     .unwrap()
                     .with_ymd_and_hms(1997, 11, 21, 9, 55, 6)
@@ -472,12 +807,32 @@ mod tests {
         date_parsed_printed(
             b"Sun, 18 Jun 2023 15:39:08 +0200 (CEST)",
             b"Sun, 18 Jun 2023 15:39:08 +0200",
-            DateTime(
-                FixedOffset::east_opt(2 * HOUR)
+            DateTime {
+                datetime: FixedOffset::east_opt(2 * HOUR)
                     .unwrap()
                     .with_ymd_and_hms(2023, 6, 18, 15, 39, 8)
                     .unwrap(),
-            ),
+                unknown_offset: false,
+                comments: Comments {
+                    before: vec![],
+                    after: vec![Comment(b"CEST"[..].into())],
+                },
+            },
+        );
+    }
+
+    #[test]
+    fn test_date_time_print_comments() {
+        let (_, date) = date_time(b"Sun, 18 Jun 2023 15:39:08 +0200 (CEST)").unwrap();
+        let printed = crate::print::print_to_vec(
+            crate::print::FMT_DEFAULT
+                .with_seed(Some(0))
+                .with_comments(true),
+            date,
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&printed),
+            "Sun, 18 Jun 2023 15:39:08 +0200 (CEST)"
         );
     }
 
@@ -492,12 +847,17 @@ mod tests {
                   -0330 (Newfoundland Time)"#
                 .as_bytes(),
             b"Thu, 13 Feb 1969 23:32:00 -0330",
-            DateTime(
-                FixedOffset::west_opt(3 * HOUR + 30 * MIN)
+            DateTime {
+                datetime: FixedOffset::west_opt(3 * HOUR + 30 * MIN)
                     .unwrap()
                     .with_ymd_and_hms(1969, 2, 13, 23, 32, 00)
                     .unwrap(),
-            ),
+                unknown_offset: false,
+                comments: Comments {
+                    before: vec![],
+                    after: vec![Comment(b"Newfoundland Time"[..].into())],
+                },
+            },
         );
     }
 
@@ -506,7 +866,7 @@ mod tests {
         date_parsed_printed(
             b"21 Nov 97 09:55:06 GMT",
             b"Fri, 21 Nov 1997 09:55:06 +0000",
-            DateTime(
+            DateTime::new(
                 FixedOffset::east_opt(0)
                     .unwrap()
                     .with_ymd_and_hms(1997, 11, 21, 9, 55, 6)
@@ -520,7 +880,7 @@ mod tests {
         date_parsed_printed(
             b"21 Nov 103 09:55:06 UT",
             b"Fri, 21 Nov 2003 09:55:06 +0000",
-            DateTime(
+            DateTime::new(
                 FixedOffset::east_opt(0)
                     .unwrap()
                     .with_ymd_and_hms(2003, 11, 21, 9, 55, 6)
@@ -534,12 +894,17 @@ mod tests {
         date_parsed_printed(
             b"Fri, 21 Nov 1997 09(comment):   55  :  06 -0600",
             b"Fri, 21 Nov 1997 09:55:06 -0600",
-            DateTime(
-                FixedOffset::west_opt(6 * HOUR)
+            DateTime {
+                datetime: FixedOffset::west_opt(6 * HOUR)
                     .unwrap()
                     .with_ymd_and_hms(1997, 11, 21, 9, 55, 6)
                     .unwrap(),
-            ),
+                unknown_offset: false,
+                comments: Comments {
+                    before: vec![],
+                    after: vec![Comment(b"comment"[..].into())],
+                },
+            },
         );
     }
 
@@ -547,54 +912,30 @@ mod tests {
     fn test_date_time_2digit_year() {
         date_parsed_printed(
             b"21 Nov 23 09:55:06Z",
-            b"Tue, 21 Nov 2023 09:55:06 +0000",
-            DateTime(
-                FixedOffset::east_opt(0)
+            b"Tue, 21 Nov 2023 09:55:06 -0000",
+            DateTime::with_unknown_offset(
+                NaiveDate::from_ymd_opt(2023, 11, 21)
                     .unwrap()
-                    .with_ymd_and_hms(2023, 11, 21, 9, 55, 6)
+                    .and_hms_opt(9, 55, 6)
                     .unwrap(),
             ),
         );
     }
 
     #[test]
-    fn test_date_time_military_zone_east() {
-        ["a", "B", "c", "D", "e", "F", "g", "H", "i", "K", "l", "M"]
+    fn test_date_time_military_zone() {
+        ["a", "B", "i", "K", "m", "N", "q", "y", "Z"]
             .iter()
-            .enumerate()
-            .for_each(|(i, x)| {
-                assert_eq!(
-                    date_time(format!("1 Jan 22 08:00:00 {}", x).as_bytes()),
-                    Ok((
-                        &b""[..],
-                        DateTime(
-                            FixedOffset::east_opt((i as i32 + 1) * HOUR)
-                                .unwrap()
-                                .with_ymd_and_hms(2022, 01, 01, 8, 0, 0)
-                                .unwrap()
-                        )
-                    ))
-                );
-            });
-    }
-
-    #[test]
-    fn test_date_time_military_zone_west() {
-        ["N", "O", "P", "q", "r", "s", "T", "U", "V", "w", "x", "y"]
-            .iter()
-            .enumerate()
-            .for_each(|(i, x)| {
-                assert_eq!(
-                    date_time(format!("1 Jan 22 08:00:00 {}", x).as_bytes()),
-                    Ok((
-                        &b""[..],
-                        DateTime(
-                            FixedOffset::west_opt((i as i32 + 1) * HOUR)
-                                .unwrap()
-                                .with_ymd_and_hms(2022, 01, 01, 8, 0, 0)
-                                .unwrap()
-                        )
-                    ))
+            .for_each(|x| {
+                date_parsed_printed(
+                    format!("1 Jan 22 08:00:00 {}", x).as_bytes(),
+                    b"Sat, 1 Jan 2022 08:00:00 -0000",
+                    DateTime::with_unknown_offset(
+                        NaiveDate::from_ymd_opt(2022, 01, 01)
+                            .unwrap()
+                            .and_hms_opt(8, 0, 0)
+                            .unwrap(),
+                    ),
                 );
             });
     }
@@ -604,27 +945,7 @@ mod tests {
         date_parsed_printed(
             b"21 Nov 2023 07:07:07 +0000",
             b"Tue, 21 Nov 2023 07:07:07 +0000",
-            DateTime(
-                FixedOffset::east_opt(0)
-                    .unwrap()
-                    .with_ymd_and_hms(2023, 11, 21, 7, 7, 7)
-                    .unwrap(),
-            ),
-        );
-        date_parsed_printed(
-            b"21 Nov 2023 07:07:07 -0000",
-            b"Tue, 21 Nov 2023 07:07:07 +0000",
-            DateTime(
-                FixedOffset::east_opt(0)
-                    .unwrap()
-                    .with_ymd_and_hms(2023, 11, 21, 7, 7, 7)
-                    .unwrap(),
-            ),
-        );
-        date_parsed_printed(
-            b"21 Nov 2023 07:07:07 Z",
-            b"Tue, 21 Nov 2023 07:07:07 +0000",
-            DateTime(
+            DateTime::new(
                 FixedOffset::east_opt(0)
                     .unwrap()
                     .with_ymd_and_hms(2023, 11, 21, 7, 7, 7)
@@ -634,7 +955,7 @@ mod tests {
         date_parsed_printed(
             b"21 Nov 2023 07:07:07 GMT",
             b"Tue, 21 Nov 2023 07:07:07 +0000",
-            DateTime(
+            DateTime::new(
                 FixedOffset::east_opt(0)
                     .unwrap()
                     .with_ymd_and_hms(2023, 11, 21, 7, 7, 7)
@@ -644,7 +965,7 @@ mod tests {
         date_parsed_printed(
             b"21 Nov 2023 07:07:07 UT",
             b"Tue, 21 Nov 2023 07:07:07 +0000",
-            DateTime(
+            DateTime::new(
                 FixedOffset::east_opt(0)
                     .unwrap()
                     .with_ymd_and_hms(2023, 11, 21, 7, 7, 7)
@@ -654,7 +975,7 @@ mod tests {
         date_parsed_printed(
             b"21 Nov 2023 07:07:07 UTC",
             b"Tue, 21 Nov 2023 07:07:07 +0000",
-            DateTime(
+            DateTime::new(
                 FixedOffset::east_opt(0)
                     .unwrap()
                     .with_ymd_and_hms(2023, 11, 21, 7, 7, 7)
@@ -663,12 +984,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_date_time_unknown_offset() {
+        let (_, date) = date_time(b"21 Nov 2023 07:07:07 -0000").unwrap();
+        assert_eq!(
+            date,
+            DateTime::with_unknown_offset(
+                NaiveDate::from_ymd_opt(2023, 11, 21)
+                    .unwrap()
+                    .and_hms_opt(7, 7, 7)
+                    .unwrap()
+            )
+        );
+        assert_eq!(date.offset(), None);
+        assert_eq!(
+            String::from_utf8_lossy(&print_to_vec(date)),
+            "Tue, 21 Nov 2023 07:07:07 -0000"
+        );
+
+        // not to be confused with UTC
+        let (_, date) = date_time(b"21 Nov 2023 07:07:07 +0000").unwrap();
+        assert!(!date.unknown_offset);
+        assert_eq!(date.offset(), FixedOffset::east_opt(0));
+    }
+
     #[test]
     fn test_date_time_usa() {
         date_parsed_printed(
             b"21 Nov 2023 4:4:4 CST",
             b"Tue, 21 Nov 2023 04:04:04 -0600",
-            DateTime(
+            DateTime::new(
                 FixedOffset::west_opt(6 * HOUR)
                     .unwrap()
                     .with_ymd_and_hms(2023, 11, 21, 4, 4, 4)
@@ -682,7 +1027,7 @@ mod tests {
         date_parsed_printed(
             b"26 Aug 2316 09:06:21 -4508",
             b"Sat, 26 Aug 2316 09:06:21 -2108",
-            DateTime(
+            DateTime::new(
                 FixedOffset::west_opt(21 * HOUR + 08 * MIN)
                     .unwrap()
                     .with_ymd_and_hms(2316, 08, 26, 9, 6, 21)
@@ -700,11 +1045,11 @@ mod tests {
     fn test_date_time_no_zone() {
         date_parsed_printed(
             b"21 Nov 2023 07:07:07 ",
-            b"Tue, 21 Nov 2023 07:07:07 +0000",
-            DateTime(
-                FixedOffset::east_opt(0)
+            b"Tue, 21 Nov 2023 07:07:07 -0000",
+            DateTime::with_unknown_offset(
+                NaiveDate::from_ymd_opt(2023, 11, 21)
                     .unwrap()
-                    .with_ymd_and_hms(2023, 11, 21, 7, 7, 7)
+                    .and_hms_opt(7, 7, 7)
                     .unwrap(),
             ),
         );
@@ -714,13 +1059,76 @@ mod tests {
     fn test_date_time_unknown_zone() {
         date_parsed_printed(
             b" Mon, 20 Nov 1995 16:54:06 MET",
-            b"Mon, 20 Nov 1995 16:54:06 +0000",
-            DateTime(
-                FixedOffset::east_opt(0)
+            b"Mon, 20 Nov 1995 16:54:06 -0000",
+            DateTime::with_unknown_offset(
+                NaiveDate::from_ymd_opt(1995, 11, 20)
                     .unwrap()
-                    .with_ymd_and_hms(1995, 11, 20, 16, 54, 06)
+                    .and_hms_opt(16, 54, 6)
                     .unwrap(),
             ),
         );
     }
+
+    #[test]
+    fn test_date_time_recover() {
+        let offset = |h| FixedOffset::east_opt(h * HOUR).unwrap();
+        [
+            (
+                &b"2023-11-21T07:07:07Z"[..],
+                "Tue, 21 Nov 2023 07:07:07 +0000",
+            ),
+            (
+                b"2023-11-21 07:07:07.123+02:00",
+                "Tue, 21 Nov 2023 07:07:07 +0200",
+            ),
+            (b"2023-11-21T07:07", "Tue, 21 Nov 2023 07:07:00 -0000"),
+            (
+                b"Tue Nov 21 07:07:07 2023",
+                "Tue, 21 Nov 2023 07:07:07 -0000",
+            ),
+            (
+                b"Tue Nov 21 07:07:07 CET 2023",
+                "Tue, 21 Nov 2023 07:07:07 +0100",
+            ),
+            (
+                b"Tuesday, November 21, 2023 7:07 PM",
+                "Tue, 21 Nov 2023 19:07:00 -0000",
+            ),
+            (
+                b"Tue 21 Nov 2023 07:07:07 +0200",
+                "Tue, 21 Nov 2023 07:07:07 +0200",
+            ),
+            (
+                b"Tue, 21 Nov, 2023, 07:07 JST",
+                "Tue, 21 Nov 2023 07:07:00 +0900",
+            ),
+            (
+                b"21-Nov-23 12:07:07 AM -0800",
+                "Tue, 21 Nov 2023 00:07:07 -0800",
+            ),
+        ]
+        .iter()
+        .for_each(|(input, printed)| {
+            let (rest, date) = date_time(input).unwrap();
+            assert_eq!(rest, b"");
+            assert_eq!(String::from_utf8_lossy(&print_to_vec(date)), *printed);
+        });
+
+        // non-RFC named zones are also recognized in otherwise valid dates
+        let input = b"Sun, 18 Jun 2023 15:39:08 CEST";
+        let (res, diagnostics) = crate::diagnostics::collect(input, || date_time(input));
+        assert_eq!(
+            res.unwrap().1,
+            DateTime::new(offset(2).with_ymd_and_hms(2023, 6, 18, 15, 39, 8).unwrap())
+        );
+        assert_eq!(
+            diagnostics.iter().map(|d| d.kind).collect::<Vec<_>>(),
+            vec![DiagnosticKind::Recovered("non-RFC named zone")]
+        );
+        assert_eq!(diagnostics[0].range, Some(25..30));
+
+        assert!(date_time(b"2023-13-21T07:07:07Z").is_err());
+        assert!(date_time(b"Tue Nov 21 07:07:07 2023 ???").is_err());
+        assert!(date_time(b"13:07 PM 21 Nov 2023").is_err());
+    }
 }
@@ -5,9 +5,16 @@ use tracing::warn;
 #[cfg(feature = "arbitrary")]
 use crate::fuzz_eq::FuzzEq;
 use crate::header;
+use crate::i18n::ContainsUtf8;
 use crate::imf::address::{nullable_address_list, AddressList};
+use crate::imf::arc::{
+    authentication_results, message_signature, seal, AuthenticationResults, MessageSignature, Seal,
+};
 use crate::imf::datetime::{date_time, DateTime};
 use crate::imf::identification::{msg_id, nullable_msg_list, MessageID, MessageIDList};
+use crate::imf::list::{
+    list_id, list_post, list_uris, unsubscribe_post, ListId, ListPost, ListUris, UnsubscribePost,
+};
 use crate::imf::mailbox::{mailbox, mailbox_list, MailboxList, MailboxRef};
 use crate::imf::mime::{version, Version};
 use crate::imf::trace::{return_path, ReturnPath};
@@ -36,10 +43,18 @@ pub enum Entry {
     Keywords(usize),
     #[cfg_attr(feature = "arbitrary", fuzz_eq(use_eq))]
     Trace(usize), // either a Received or ReturnPath field
+    ListId,
+    ListHelp,
+    ListUnsubscribe,
+    ListUnsubscribePost,
+    ListSubscribe,
+    ListPost,
+    ListOwner,
+    ListArchive,
     MIMEVersion,
 }
 
-#[derive(Clone, Debug, PartialEq, ToStatic)]
+#[derive(Clone, ContainsUtf8, Debug, PartialEq, ToStatic)]
 #[cfg_attr(feature = "arbitrary", derive(FuzzEq))]
 pub enum Field<'a> {
     // 3.6.1.  The Origination Date Field
@@ -70,6 +85,21 @@ pub enum Field<'a> {
     Received(Unstructured<'a>),
     ReturnPath(ReturnPath<'a>),
 
+    // RFC 8617 ARC header fields (also trace fields)
+    ArcSeal(Seal<'a>),
+    ArcMessageSignature(MessageSignature<'a>),
+    ArcAuthenticationResults(AuthenticationResults<'a>),
+
+    // RFC 2369 / 2919 / 8058 List Header Fields
+    ListId(ListId<'a>),
+    ListHelp(ListUris<'a>),
+    ListUnsubscribe(ListUris<'a>),
+    ListUnsubscribePost(UnsubscribePost<'a>),
+    ListSubscribe(ListUris<'a>),
+    ListPost(ListPost<'a>),
+    ListOwner(ListUris<'a>),
+    ListArchive(ListUris<'a>),
+
     // MIME
     MIMEVersion(Version),
 }
@@ -92,6 +122,19 @@ impl<'a> Field<'a> {
             Self::Keywords(_) => header::FieldName(b"Keywords".into()),
             Self::Received(_) => header::FieldName(b"Received".into()),
             Self::ReturnPath(_) => header::FieldName(b"Return-Path".into()),
+            Self::ArcSeal(_) => header::FieldName(b"ARC-Seal".into()),
+            Self::ArcMessageSignature(_) => header::FieldName(b"ARC-Message-Signature".into()),
+            Self::ArcAuthenticationResults(_) => {
+                header::FieldName(b"ARC-Authentication-Results".into())
+            }
+            Self::ListId(_) => header::FieldName(b"List-Id".into()),
+            Self::ListHelp(_) => header::FieldName(b"List-Help".into()),
+            Self::ListUnsubscribe(_) => header::FieldName(b"List-Unsubscribe".into()),
+            Self::ListUnsubscribePost(_) => header::FieldName(b"List-Unsubscribe-Post".into()),
+            Self::ListSubscribe(_) => header::FieldName(b"List-Subscribe".into()),
+            Self::ListPost(_) => header::FieldName(b"List-Post".into()),
+            Self::ListOwner(_) => header::FieldName(b"List-Owner".into()),
+            Self::ListArchive(_) => header::FieldName(b"List-Archive".into()),
             Self::MIMEVersion(_) => header::FieldName(b"MIME-Version".into()),
         }
     }
@@ -114,6 +157,19 @@ impl<'a> Print for Field<'a> {
             Self::Keywords(l) => header::print(fmt, b"Keywords", l),
             Self::Received(u) => header::print_unstructured(fmt, b"Received", u),
             Self::ReturnPath(p) => header::print(fmt, b"Return-Path", p),
+            Self::ArcSeal(s) => header::print(fmt, b"ARC-Seal", s),
+            Self::ArcMessageSignature(s) => header::print(fmt, b"ARC-Message-Signature", s),
+            Self::ArcAuthenticationResults(r) => {
+                header::print(fmt, b"ARC-Authentication-Results", r)
+            }
+            Self::ListId(id) => header::print(fmt, b"List-Id", id),
+            Self::ListHelp(uris) => header::print(fmt, b"List-Help", uris),
+            Self::ListUnsubscribe(uris) => header::print(fmt, b"List-Unsubscribe", uris),
+            Self::ListUnsubscribePost(p) => header::print(fmt, b"List-Unsubscribe-Post", p),
+            Self::ListSubscribe(uris) => header::print(fmt, b"List-Subscribe", uris),
+            Self::ListPost(p) => header::print(fmt, b"List-Post", p),
+            Self::ListOwner(uris) => header::print(fmt, b"List-Owner", uris),
+            Self::ListArchive(uris) => header::print(fmt, b"List-Archive", uris),
             Self::MIMEVersion(v) => header::print(fmt, b"MIME-Version", v),
         }
     }
@@ -219,6 +275,24 @@ impl<'a> TryFrom<&header::FieldRaw<'a>> for Field<'a> {
             }),
             b"return-path" => map_res(return_path(f.body), Field::ReturnPath),
             b"received" => map_res(unstructured(f.body), Field::Received),
+            b"arc-seal" => map_res(seal(f.body), Field::ArcSeal),
+            b"arc-message-signature" => {
+                map_res(message_signature(f.body), Field::ArcMessageSignature)
+            }
+            b"arc-authentication-results" => map_res(
+                authentication_results(f.body),
+                Field::ArcAuthenticationResults,
+            ),
+            b"list-id" => map_res(list_id(f.body), Field::ListId),
+            b"list-help" => map_res(list_uris(f.body), Field::ListHelp),
+            b"list-unsubscribe" => map_res(list_uris(f.body), Field::ListUnsubscribe),
+            b"list-unsubscribe-post" => {
+                map_res(unsubscribe_post(f.body), Field::ListUnsubscribePost)
+            }
+            b"list-subscribe" => map_res(list_uris(f.body), Field::ListSubscribe),
+            b"list-post" => map_res(list_post(f.body), Field::ListPost),
+            b"list-owner" => map_res(list_uris(f.body), Field::ListOwner),
+            b"list-archive" => map_res(list_uris(f.body), Field::ListArchive),
             b"mime-version" => map_res(version(f.body), Field::MIMEVersion),
             _ => Err(InvalidField::Name),
         }
@@ -235,9 +309,13 @@ impl<'a> TryFrom<&header::Unstructured<'a>> for Field<'static> {
             Some(s) => s.into(),
             None => u.body.to_string_keep_obs().into_bytes().into(),
         };
+        let mut raw = u.name.bytes().to_vec();
+        raw.push(b':');
+        raw.extend_from_slice(&bytes_body);
         let hdr = header::FieldRaw {
             name: u.name.clone(),
-            body: &bytes_body,
+            body: &raw[raw.len() - bytes_body.len()..],
+            raw: &raw,
         };
         Field::try_from(&hdr).map(IntoBoundedStatic::into_static)
     }
@@ -261,6 +339,17 @@ pub fn is_imf_header(name: &header::FieldName) -> bool {
             | b"keywords"
             | b"return-path"
             | b"received"
+            | b"arc-seal"
+            | b"arc-message-signature"
+            | b"arc-authentication-results"
+            | b"list-id"
+            | b"list-help"
+            | b"list-unsubscribe"
+            | b"list-unsubscribe-post"
+            | b"list-subscribe"
+            | b"list-post"
+            | b"list-owner"
+            | b"list-archive"
             | b"mime-version"
     )
 }
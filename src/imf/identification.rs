@@ -4,7 +4,7 @@ use bounded_static::ToStatic;
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    combinator::{eof, map, opt, recognize},
+    combinator::{consumed, eof, map, opt, recognize},
     multi::many0,
     sequence::{delimited, pair, preceded, tuple},
     IResult,
@@ -13,12 +13,15 @@ use std::borrow::Cow;
 #[cfg(any(feature = "tracing-recover", feature = "tracing-unsupported"))]
 use tracing::warn;
 
+use crate::diagnostics::{self, Severity};
 use crate::i18n::ContainsUtf8;
 use crate::imf::mailbox::{domain, dtext, local_part, Domain, Dtext, LocalPart};
 use crate::print::{print_seq, Formatter, Print, ToStringFromPrint};
 use crate::text::recovery::{take_quoted_encoded_or_until1, take_quoted_or_until};
 use crate::text::utf8::{is_nonascii_or, take_utf8_while1};
-use crate::text::whitespace::cfws;
+use crate::text::whitespace::{
+    cfws, comments_in, print_comments, print_leading_comments, Comments,
+};
 #[cfg(any(feature = "tracing-recover", feature = "tracing-unsupported"))]
 use crate::utils::bytes_to_trace_string;
 #[cfg(feature = "arbitrary")]
@@ -34,16 +37,22 @@ pub enum MessageID<'a> {
     ObsLeftRight {
         left: LocalPart<'a>,
         right: Domain<'a>,
+        #[cfg_attr(feature = "arbitrary", fuzz_eq(ignore))]
+        comments: Comments<'a>,
     },
-    // Non-compliant char sequence (must be non-empty and satisfy is_invalid_msgid_text)
+    // Non-compliant char sequence (must be non-empty and satisfy is_invalid_msgid_text).
+    // Comments are not kept separately as they may be part of the text.
     #[cfg_attr(feature = "arbitrary", fuzz_eq(use_eq))]
     Invalid(Cow<'a, str>),
 }
 impl<'a> Print for MessageID<'a> {
     fn print(&self, fmt: &mut impl Formatter) {
+        if let MessageID::ObsLeftRight { comments, .. } = &self {
+            print_leading_comments(fmt, comments)
+        }
         fmt.write_bytes(b"<");
         match &self {
-            MessageID::ObsLeftRight { left, right } => {
+            MessageID::ObsLeftRight { left, right, .. } => {
                 left.print(fmt);
                 fmt.write_bytes(b"@");
                 right.print(fmt);
@@ -51,6 +60,9 @@ impl<'a> Print for MessageID<'a> {
             MessageID::Invalid(txt) => fmt.write_bytes(txt.as_bytes()),
         }
         fmt.write_bytes(b">");
+        if let MessageID::ObsLeftRight { comments, .. } = &self {
+            print_comments(fmt, comments)
+        }
     }
 }
 #[cfg(feature = "arbitrary")]
@@ -60,6 +72,7 @@ impl<'a> Arbitrary<'a> for MessageID<'a> {
             0 => Ok(MessageID::ObsLeftRight {
                 left: u.arbitrary()?,
                 right: u.arbitrary()?,
+                comments: u.arbitrary()?,
             }),
             1 => {
                 let s = arbitrary_string_nonempty_where(u, is_invalid_msgid_text, 'X')?;
@@ -70,6 +83,20 @@ impl<'a> Arbitrary<'a> for MessageID<'a> {
     }
 }
 
+impl<'a> MessageID<'a> {
+    // `raw` is the input recognized when parsing `self`
+    fn with_comments_in(self, raw: &'a [u8]) -> Self {
+        match self {
+            MessageID::ObsLeftRight { left, right, .. } => MessageID::ObsLeftRight {
+                left,
+                right,
+                comments: comments_in(raw),
+            },
+            MessageID::Invalid(_) => self,
+        }
+    }
+}
+
 // Must be non-empty
 pub type MessageIDList<'a> = Vec<MessageID<'a>>;
 
@@ -101,17 +128,24 @@ impl<'a> Print for MessageIDList<'a> {
 pub fn msg_id(input: &[u8]) -> IResult<&[u8], MessageID<'_>> {
     alt((
         msg_id_angle,
-        map(msg_id_bare(|i: &[u8]| eof(i)), |msg| {
-            #[cfg(feature = "tracing-recover")]
-            warn!("message-id: bare msg-id without <>");
-            msg
+        map(consumed(msg_id_bare(|i: &[u8]| eof(i))), |(raw, msg)| {
+            diagnostics::diagnose!(
+                "tracing-recover",
+                Recovered("message-id: bare msg-id without <>"),
+                Severity::Warning,
+                None,
+            );
+            msg.with_comments_in(raw)
         }),
     ))(input)
 }
 pub fn msg_id_angle(input: &[u8]) -> IResult<&[u8], MessageID<'_>> {
-    preceded(
-        pair(opt(cfws), tag("<")),
-        msg_id_bare(|i: &[u8]| recognize(pair(tag(">"), opt(cfws)))(i)),
+    map(
+        consumed(preceded(
+            pair(opt(cfws), tag("<")),
+            msg_id_bare(|i: &[u8]| recognize(pair(tag(">"), opt(cfws)))(i)),
+        )),
+        |(raw, msg)| msg.with_comments_in(raw),
     )(input)
 }
 pub fn msg_id_bare<F>(terminator: F) -> impl FnMut(&[u8]) -> IResult<&[u8], MessageID<'_>>
@@ -122,7 +156,11 @@ where
         alt((
             map(
                 tuple((id_left, tag("@"), id_right, &terminator)),
-                |(left, _, right, _)| MessageID::ObsLeftRight { left, right },
+                |(left, _, right, _)| MessageID::ObsLeftRight {
+                    left,
+                    right,
+                    comments: Comments::default(),
+                },
             ),
             map(
                 tuple((
@@ -132,8 +170,12 @@ where
                     &terminator,
                 )),
                 |(_, s, _, _)| {
-                    #[cfg(feature = "tracing-recover")]
-                    warn!("message-id: bare string instead of id-left@id-right");
+                    diagnostics::diagnose!(
+                        "tracing-recover",
+                        Recovered("message-id: bare string instead of id-left@id-right"),
+                        Severity::Warning,
+                        None,
+                    );
                     MessageID::Invalid(s)
                 },
             ),
@@ -177,9 +219,13 @@ pub fn nullable_msg_list(input: &[u8]) -> IResult<&[u8], MessageIDList<'_>> {
                 opt(tag(">")),
             ))),
             |_i| {
-                #[cfg(feature = "tracing-unsupported")]
-                warn!(input = %bytes_to_trace_string(_i),
-                      "unsupported msg-id in msg-list");
+                diagnostics::diagnose!(
+                    "tracing-unsupported",
+                    (input = %bytes_to_trace_string(_i)),
+                    Unsupported("unsupported msg-id in msg-list"),
+                    Severity::Error,
+                    None,
+                );
                 None
             },
         ),
@@ -187,9 +233,13 @@ pub fn nullable_msg_list(input: &[u8]) -> IResult<&[u8], MessageIDList<'_>> {
         map(cfws, |_| None),
         // recovery: recognize junk in between msg-ids, skipping to the next <
         map(take_quoted_encoded_or_until1(|c| c == b'<'), |_i| {
-            #[cfg(feature = "tracing-recover")]
-            warn!(input = %bytes_to_trace_string(_i),
-                  "non-compliant text between msg-ids");
+            diagnostics::diagnose!(
+                "tracing-recover",
+                (input = %bytes_to_trace_string(_i)),
+                Recovered("non-compliant text between msg-ids"),
+                Severity::Warning,
+                None,
+            );
             None
         }),
     )))(input)?;
@@ -236,6 +286,7 @@ mod tests {
     use crate::print::tests::print_to_vec;
     use crate::text::misc_token::Word;
     use crate::text::quoted::QuotedString;
+    use crate::text::whitespace::Comment;
     use crate::text::words::Atom;
 
     fn assert_msg_list_reprinted(txt: &[u8], printed: &[u8]) {
@@ -248,6 +299,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_msg_id_comments() {
+        let (rest, id) = msg_id(b" (left) <foo@example.com> (right)").unwrap();
+        assert!(rest.is_empty());
+        match &id {
+            MessageID::ObsLeftRight { comments, .. } => assert_eq!(
+                comments,
+                &Comments {
+                    before: vec![Comment(b"left"[..].into())],
+                    after: vec![Comment(b"right"[..].into())],
+                }
+            ),
+            MessageID::Invalid(_) => panic!("invalid msg-id"),
+        }
+        assert_eq!(print_to_vec(&id), b"<foo@example.com>");
+        let cfg = crate::print::FMT_DEFAULT
+            .with_seed(Some(0))
+            .with_comments(true);
+        assert_eq!(
+            crate::print::print_to_vec(cfg, &id),
+            b"(left) <foo@example.com> (right)"
+        );
+    }
+
     #[test]
     fn test_msg_id() {
         assert_eq!(
@@ -261,6 +336,7 @@ mod tests {
                         LocalPartToken::Word(Word::Atom(Atom("21-Nov-1997".into()))),
                     ]),
                     right: Domain::Atoms(vec![Atom("example".into()), Atom("com".into()),]),
+                    comments: Comments::default(),
                 }
             )),
         );
@@ -281,6 +357,7 @@ mod tests {
                     right: Domain::Atoms(
                         vec![Atom("univ-valenciennes".into()), Atom("fr".into()),]
                     ),
+                    comments: Comments::default(),
                 }
             )),
         );
@@ -306,6 +383,7 @@ mod tests {
                         ]
                     )))]),
                     right: Domain::Atoms(vec![Atom("bnr".into()), Atom("ca".into()),]),
+                    comments: Comments::default(),
                 }
             )),
         );
@@ -341,6 +419,7 @@ mod tests {
                         LocalPartToken::Word(Word::Atom(Atom("1758617731".into()))),
                     ]),
                     right: Domain::Atoms(vec![Atom("alan".into()), Atom("eu".into()),]),
+                    comments: Comments::default(),
                 },
             ))
         );
@@ -377,7 +456,7 @@ mod tests {
                         right: Domain::Atoms(vec![
                             Atom("cnrs".into()),
                             Atom("fr".into()),
-                        ]),
+                        ]), comments: Comments::default(),
                     },
                     MessageID::ObsLeftRight {
                         left: LocalPart(vec![
@@ -386,7 +465,7 @@ mod tests {
                         right: Domain::Atoms(vec![
                             Atom("cnrs".into()),
                             Atom("fr".into()),
-                        ]),
+                        ]), comments: Comments::default(),
                     },
                 ]
             ))
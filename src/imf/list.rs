@@ -0,0 +1,456 @@
+#[cfg(feature = "arbitrary")]
+use arbitrary::Arbitrary;
+use bounded_static::ToStatic;
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, tag_no_case, take_while1},
+    combinator::{map, opt, verify},
+    multi::separated_list1,
+    sequence::{delimited, pair, tuple},
+    IResult,
+};
+use std::borrow::Cow;
+use std::fmt;
+#[cfg(feature = "tracing-recover")]
+use tracing::warn;
+
+use crate::diagnostics::{self, Severity};
+use crate::i18n::ContainsUtf8;
+use crate::print::{print_seq, Formatter, Print, ToStringFromPrint};
+use crate::text::ascii;
+use crate::text::misc_token::{phrase, unstructured, Phrase, Unstructured};
+use crate::text::whitespace::{cfws, cfws_keep, Comment};
+use crate::text::words::{dot_atom_text, DotAtom};
+#[cfg(feature = "arbitrary")]
+use crate::{arbitrary_utils::arbitrary_vec_nonempty, fuzz_eq::FuzzEq};
+use eml_codec_derives::instrument_input;
+
+// Mailing list header fields: RFC 2369 (List-Help, List-Unsubscribe,
+// List-Subscribe, List-Post, List-Owner, List-Archive), RFC 2919 (List-Id)
+// and RFC 8058 (List-Unsubscribe-Post).
+
+/// The contents of an angle-bracketed URI, with whitespace removed.
+#[derive(Clone, PartialEq, ToStatic, ToStringFromPrint)]
+pub struct Uri<'a>(pub Cow<'a, [u8]>);
+
+impl<'a> fmt::Debug for Uri<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_tuple("Uri")
+            .field(&String::from_utf8_lossy(&self.0))
+            .finish()
+    }
+}
+impl<'a> ContainsUtf8 for Uri<'a> {
+    fn contains_utf8(&self) -> bool {
+        !self.0.is_ascii()
+    }
+}
+impl<'a> Print for Uri<'a> {
+    fn print(&self, fmt: &mut impl Formatter) {
+        fmt.write_bytes(&self.0)
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for Uri<'a> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        use crate::arbitrary_utils::arbitrary_vec_nonempty_where;
+        let bytes = arbitrary_vec_nonempty_where(
+            u,
+            |c: &u8| c.is_ascii_graphic() && *c != ascii::LT && *c != ascii::GT,
+            b'X',
+        )?;
+        Ok(Uri(Cow::Owned(bytes)))
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> FuzzEq for Uri<'a> {
+    fn fuzz_eq(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+/// An angle-bracketed URI, with the comments around it (RFC 2369, section 2).
+///
+/// ```text
+/// <mailto:list@host.com?subject=help> (List Instructions)
+/// ```
+#[derive(Clone, ContainsUtf8, Debug, PartialEq, ToStatic, ToStringFromPrint)]
+#[cfg_attr(feature = "arbitrary", derive(Arbitrary, FuzzEq))]
+pub struct ListUri<'a> {
+    pub before: Vec<Comment<'a>>,
+    pub uri: Uri<'a>,
+    pub after: Vec<Comment<'a>>,
+}
+
+impl<'a> Print for ListUri<'a> {
+    fn print(&self, fmt: &mut impl Formatter) {
+        for c in &self.before {
+            c.print(fmt);
+            fmt.write_fws();
+        }
+        fmt.write_bytes(b"<");
+        self.uri.print(fmt);
+        fmt.write_bytes(b">");
+        for c in &self.after {
+            fmt.write_fws();
+            c.print(fmt);
+        }
+    }
+}
+
+/// ```abnf
+/// list-uri = [CFWS] "<" uri ">" [CFWS]
+/// ```
+///
+/// Whitespace inside the angle brackets is ignored.
+///
+/// URIs without angle brackets (`List-Unsubscribe: mailto:x@y`) are not
+/// allowed by the RFC but are found in real-world emails; they are recovered
+/// and printed back with angle brackets.
+#[instrument_input("tracing")]
+pub fn list_uri(input: &[u8]) -> IResult<&[u8], ListUri<'_>> {
+    map(
+        tuple((
+            opt(cfws_keep),
+            alt((
+                delimited(
+                    tag(&[ascii::LT]),
+                    take_while1(|c| c != ascii::GT),
+                    tag(&[ascii::GT]),
+                ),
+                bare_uri,
+            )),
+            opt(cfws_keep),
+        )),
+        |(before, uri, after): (_, &[u8], _)| {
+            let is_ws = |c: &u8| matches!(*c, ascii::SP | ascii::HT | ascii::CR | ascii::LF);
+            let uri = if uri.iter().any(is_ws) {
+                Cow::Owned(uri.iter().copied().filter(|c| !is_ws(c)).collect())
+            } else {
+                Cow::Borrowed(uri)
+            };
+            ListUri {
+                before: before.unwrap_or_default(),
+                uri: Uri(uri),
+                after: after.unwrap_or_default(),
+            }
+        },
+    )(input)
+}
+
+// A URI without angle brackets: a run of printable characters containing a
+// scheme separator, ending at whitespace, a comment or a comma.
+fn bare_uri(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    map(
+        verify(
+            take_while1(|c: u8| {
+                c.is_ascii_graphic() && !matches!(c, ascii::LT | ascii::GT | b'(' | b')' | b',')
+            }),
+            |uri: &[u8]| uri.contains(&b':'),
+        ),
+        |uri| {
+            diagnostics::diagnose!(
+                "tracing-recover",
+                Recovered("list-uri: bare URI without <>"),
+                Severity::Warning,
+                None,
+            );
+            uri
+        },
+    )(input)
+}
+
+/// A non-empty, comma-separated list of URIs, in order of preference.
+#[derive(Clone, ContainsUtf8, Debug, PartialEq, ToStatic, ToStringFromPrint)]
+#[cfg_attr(feature = "arbitrary", derive(FuzzEq))]
+pub struct ListUris<'a>(pub Vec<ListUri<'a>>);
+
+impl<'a> Print for ListUris<'a> {
+    fn print(&self, fmt: &mut impl Formatter) {
+        print_seq(fmt, &self.0, |fmt| {
+            fmt.write_bytes(b",");
+            fmt.write_fws()
+        })
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for ListUris<'a> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ListUris(arbitrary_vec_nonempty(u)?))
+    }
+}
+
+#[instrument_input("tracing")]
+pub fn list_uris(input: &[u8]) -> IResult<&[u8], ListUris<'_>> {
+    map(separated_list1(tag(b","), list_uri), ListUris)(input)
+}
+
+/// List-Post header field body: either a list of URIs, or "NO" if posting to
+/// the list is not allowed (RFC 2369, section 3.4).
+#[derive(Clone, ContainsUtf8, Debug, PartialEq, ToStatic, ToStringFromPrint)]
+#[cfg_attr(feature = "arbitrary", derive(Arbitrary, FuzzEq))]
+pub enum ListPost<'a> {
+    Uris(ListUris<'a>),
+    No(Vec<Comment<'a>>),
+}
+
+impl<'a> Print for ListPost<'a> {
+    fn print(&self, fmt: &mut impl Formatter) {
+        match self {
+            Self::Uris(uris) => uris.print(fmt),
+            Self::No(comments) => {
+                fmt.write_bytes(b"NO");
+                for c in comments {
+                    fmt.write_fws();
+                    c.print(fmt);
+                }
+            }
+        }
+    }
+}
+
+#[instrument_input("tracing")]
+pub fn list_post(input: &[u8]) -> IResult<&[u8], ListPost<'_>> {
+    alt((
+        map(
+            tuple((opt(cfws_keep), tag_no_case(b"NO"), opt(cfws_keep))),
+            |(before, _, after)| {
+                let mut comments = before.unwrap_or_default();
+                comments.extend(after.unwrap_or_default());
+                ListPost::No(comments)
+            },
+        ),
+        map(list_uris, ListPost::Uris),
+    ))(input)
+}
+
+/// List-Id header field body (RFC 2919, section 3)
+///
+/// ```text
+/// List-Id: List Header Mailing List <list-header.nisto.com>
+/// ```
+#[derive(Clone, ContainsUtf8, Debug, PartialEq, ToStatic, ToStringFromPrint)]
+#[cfg_attr(feature = "arbitrary", derive(Arbitrary, FuzzEq))]
+pub struct ListId<'a> {
+    pub name: Option<Phrase<'a>>,
+    /// The list identifier: `list-label "." list-id-namespace`
+    pub id: DotAtom<'a>,
+}
+
+impl<'a> Print for ListId<'a> {
+    fn print(&self, fmt: &mut impl Formatter) {
+        if let Some(name) = &self.name {
+            name.print(fmt);
+            fmt.write_fws();
+        }
+        fmt.write_bytes(b"<");
+        self.id.print(fmt);
+        fmt.write_bytes(b">");
+    }
+}
+
+/// ```abnf
+/// list-id-header = "List-ID:" [phrase] "<" list-id ">" CRLF
+/// list-id        = list-label "." list-id-namespace
+/// ```
+#[instrument_input("tracing")]
+pub fn list_id(input: &[u8]) -> IResult<&[u8], ListId<'_>> {
+    map(
+        pair(
+            opt(phrase),
+            delimited(
+                pair(opt(cfws), tag(&[ascii::LT])),
+                dot_atom_text,
+                pair(tag(&[ascii::GT]), opt(cfws)),
+            ),
+        ),
+        |(name, id)| ListId { name, id },
+    )(input)
+}
+
+/// List-Unsubscribe-Post header field body (RFC 8058, section 3.1)
+#[derive(Clone, ContainsUtf8, Debug, PartialEq, ToStatic, ToStringFromPrint)]
+#[cfg_attr(feature = "arbitrary", derive(FuzzEq))]
+pub enum UnsubscribePost<'a> {
+    /// `List-Unsubscribe=One-Click`, the only value defined by the RFC
+    OneClick,
+    Other(Unstructured<'a>),
+}
+
+impl<'a> Print for UnsubscribePost<'a> {
+    fn print(&self, fmt: &mut impl Formatter) {
+        match self {
+            Self::OneClick => fmt.write_bytes(b"List-Unsubscribe=One-Click"),
+            Self::Other(u) => u.print(fmt),
+        }
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for UnsubscribePost<'a> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        if u.arbitrary()? {
+            return Ok(Self::OneClick);
+        }
+        let other: Unstructured = u.arbitrary()?;
+        if other
+            .to_string()
+            .trim()
+            .eq_ignore_ascii_case("List-Unsubscribe=One-Click")
+        {
+            return Err(arbitrary::Error::IncorrectFormat);
+        }
+        Ok(Self::Other(other))
+    }
+}
+
+#[instrument_input("tracing")]
+pub fn unsubscribe_post(input: &[u8]) -> IResult<&[u8], UnsubscribePost<'_>> {
+    alt((
+        map(
+            delimited(
+                opt(cfws),
+                tag_no_case(b"List-Unsubscribe=One-Click"),
+                opt(cfws),
+            ),
+            |_| UnsubscribePost::OneClick,
+        ),
+        map(unstructured, UnsubscribePost::Other),
+    ))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::print::tests::print_to_vec;
+    use crate::text::misc_token::{PhraseToken, Word};
+    use crate::text::words::Atom;
+
+    fn uri(s: &str) -> Uri<'_> {
+        Uri(s.as_bytes().into())
+    }
+
+    #[test]
+    fn test_list_uris() {
+        let (rest, uris) = list_uris(
+            b" <mailto:list@host.com?subject=help> (List Instructions),\r\n (alt) <http://www.host.com/list/\r\n help.html>",
+        )
+        .unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            uris,
+            ListUris(vec![
+                ListUri {
+                    before: vec![],
+                    uri: uri("mailto:list@host.com?subject=help"),
+                    after: vec![Comment(b"List Instructions"[..].into())],
+                },
+                ListUri {
+                    before: vec![Comment(b"alt"[..].into())],
+                    uri: uri("http://www.host.com/list/help.html"),
+                    after: vec![],
+                },
+            ])
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&print_to_vec(uris)),
+            "<mailto:list@host.com?subject=help> (List Instructions), (alt) <http://www.host.com/list/help.html>"
+        );
+    }
+
+    #[test]
+    fn test_bare_list_uris() {
+        let (uris, diagnostics) = crate::diagnostics::collect(b"", || {
+            list_uris(b" mailto:x@y, (web) https://example.org/unsub").unwrap()
+        });
+        assert!(uris.0.is_empty());
+        assert_eq!(
+            uris.1,
+            ListUris(vec![
+                ListUri {
+                    before: vec![],
+                    uri: uri("mailto:x@y"),
+                    after: vec![],
+                },
+                ListUri {
+                    before: vec![Comment(b"web"[..].into())],
+                    uri: uri("https://example.org/unsub"),
+                    after: vec![],
+                },
+            ])
+        );
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            String::from_utf8_lossy(&print_to_vec(uris.1)),
+            "<mailto:x@y>, (web) <https://example.org/unsub>"
+        );
+
+        // no scheme, this is not a URI
+        assert!(list_uris(b" unsubscribe").is_err());
+        assert_eq!(list_post(b" NO"), Ok((&b""[..], ListPost::No(vec![]))));
+    }
+
+    #[test]
+    fn test_list_post() {
+        assert_eq!(
+            list_post(b" NO (posting not allowed on this list)"),
+            Ok((
+                &b""[..],
+                ListPost::No(vec![Comment(
+                    b"posting not allowed on this list"[..].into()
+                )])
+            ))
+        );
+        assert_eq!(
+            list_post(b" <mailto:list@host.com>"),
+            Ok((
+                &b""[..],
+                ListPost::Uris(ListUris(vec![ListUri {
+                    before: vec![],
+                    uri: uri("mailto:list@host.com"),
+                    after: vec![],
+                }]))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_list_id() {
+        assert_eq!(
+            list_id(b" List Header Mailing List <list-header.nisto.com>"),
+            Ok((
+                &b""[..],
+                ListId {
+                    name: Some(Phrase(vec![
+                        PhraseToken::Word(Word::Atom(Atom("List".into()))),
+                        PhraseToken::Word(Word::Atom(Atom("Header".into()))),
+                        PhraseToken::Word(Word::Atom(Atom("Mailing".into()))),
+                        PhraseToken::Word(Word::Atom(Atom("List".into()))),
+                    ])),
+                    id: DotAtom("list-header.nisto.com".into()),
+                }
+            ))
+        );
+        assert_eq!(
+            list_id(b" <commonspace-users.list-id.within.com>"),
+            Ok((
+                &b""[..],
+                ListId {
+                    name: None,
+                    id: DotAtom("commonspace-users.list-id.within.com".into()),
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_unsubscribe_post() {
+        assert_eq!(
+            unsubscribe_post(b" List-Unsubscribe=One-Click"),
+            Ok((&b""[..], UnsubscribePost::OneClick))
+        );
+        assert!(matches!(
+            unsubscribe_post(b" foo=bar"),
+            Ok((b"", UnsubscribePost::Other(_)))
+        ));
+    }
+}
@@ -4,23 +4,31 @@ use bounded_static::ToStatic;
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    combinator::{all_consuming, into, map, map_opt, opt},
+    combinator::{all_consuming, consumed, into, map, map_opt, opt},
     multi::{many0, many1, separated_list1},
     sequence::{delimited, pair, preceded, terminated, tuple},
-    IResult,
+    IResult, Parser,
 };
 use std::borrow::Cow;
 #[cfg(feature = "tracing")]
 use tracing::warn;
 
+use crate::diagnostics::{self, discard_on_failure, obsolete, DiagnosticKind, Severity};
 use crate::i18n::ContainsUtf8;
 use crate::print::{print_seq, Formatter, Print, ToStringFromPrint};
+use crate::raw_input::RawInput;
 use crate::text::ascii;
-use crate::text::misc_token::{phrase, word, Phrase, Word, WordChars};
-use crate::text::quoted::print_quoted;
+use crate::text::misc_token::{phrase, word, Phrase, PhraseToken, Word, WordChars};
+use crate::text::quoted::{print_quoted, QuotedString};
+use crate::text::recovery::take_quoted_or_until1;
 use crate::text::utf8::{is_ascii_and, is_nonascii_or, take_utf8_while1};
-use crate::text::whitespace::{cfws, fws, is_obs_no_ws_ctl};
+use crate::text::whitespace::{
+    cfws, comments_in, fws, is_obs_no_ws_ctl, print_comments, print_leading_comments, Comments,
+};
 use crate::text::words::{atom, dot_atom_text, Atom};
+#[cfg(feature = "tracing-recover")]
+use crate::utils::bytes_to_trace_string;
+use crate::utils::vec_flatten_nonempty;
 #[cfg(feature = "arbitrary")]
 use crate::{
     arbitrary_utils::{arbitrary_string_nonempty_where, arbitrary_vec_nonempty},
@@ -42,13 +50,29 @@ impl<'a> Print for AddrSpec<'a> {
     }
 }
 
-#[derive(Clone, ContainsUtf8, Debug, PartialEq, ToStatic, ToStringFromPrint)]
+#[derive(Clone, ContainsUtf8, Debug, ToStatic, ToStringFromPrint)]
 #[cfg_attr(feature = "arbitrary", derive(Arbitrary, FuzzEq))]
 pub struct MailboxRef<'a> {
     // The actual "email address" like hello@example.com
     pub addrspec: AddrSpec<'a>,
     // The optional name
     pub name: Option<Phrase<'a>>,
+    // Comments appearing in the mailbox, e.g. "Foo Bar" in
+    // `foo@example.com (Foo Bar)`
+    #[cfg_attr(feature = "arbitrary", fuzz_eq(ignore))]
+    pub comments: Comments<'a>,
+    // The input the mailbox was parsed from, without surrounding whitespace.
+    // Use `Message::span` to get its position in the message.
+    #[cfg_attr(feature = "arbitrary", fuzz_eq(ignore))]
+    pub raw: RawInput<'a>,
+}
+// `raw` only records where the mailbox comes from and is not compared.
+impl<'a> PartialEq for MailboxRef<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.addrspec == other.addrspec
+            && self.name == other.name
+            && self.comments == other.comments
+    }
 }
 impl MailboxRef<'static> {
     // Used as placeholder value for a missing or invalid address.
@@ -62,6 +86,8 @@ impl MailboxRef<'static> {
                 domain: Domain::Atoms(vec![Atom("unknown".into())]),
             },
             name: None,
+            comments: Comments::default(),
+            raw: RawInput::none(),
         }
     }
 }
@@ -70,11 +96,14 @@ impl<'a> From<AddrSpec<'a>> for MailboxRef<'a> {
         MailboxRef {
             name: None,
             addrspec: addr,
+            comments: Comments::default(),
+            raw: RawInput::none(),
         }
     }
 }
 impl<'a> Print for MailboxRef<'a> {
     fn print(&self, fmt: &mut impl Formatter) {
+        print_leading_comments(fmt, &self.comments);
         match &self.name {
             Some(name) => {
                 name.print(fmt);
@@ -85,6 +114,7 @@ impl<'a> Print for MailboxRef<'a> {
             }
             None => self.addrspec.print(fmt),
         }
+        print_comments(fmt, &self.comments)
     }
 }
 
@@ -113,9 +143,23 @@ impl<'a> Arbitrary<'a> for MailboxList<'a> {
 /// ```abnf
 ///    mailbox         =   name-addr / addr-spec
 /// ```
+///
+/// We also recover from display names that are not valid phrases (see
+/// `recover_name_addr`).
 #[instrument_input("tracing")]
 pub fn mailbox(input: &[u8]) -> IResult<&[u8], MailboxRef<'_>> {
-    alt((name_addr, into(addr_spec)))(input)
+    map(
+        consumed(alt((
+            discard_on_failure(name_addr),
+            discard_on_failure(recover_name_addr),
+            into(addr_spec),
+        ))),
+        |(raw, mbox)| MailboxRef {
+            comments: comments_in(raw),
+            raw: RawInput::from(raw.trim_ascii()),
+            ..mbox
+        },
+    )(input)
 }
 
 /// Mailbox list
@@ -124,30 +168,90 @@ pub fn mailbox(input: &[u8]) -> IResult<&[u8], MailboxRef<'_>> {
 ///    mailbox-list    =   (mailbox *("," mailbox)) / obs-mbox-list
 ///    obs-mbox-list   =   *([CFWS] ",") mailbox *("," [mailbox / CFWS])
 /// ```
+///
+/// We also recover from semicolons used as separators and from missing
+/// commas (see `lenient_list_separator` and `juxtaposed`).
 #[instrument_input("tracing")]
 pub fn mailbox_list(input: &[u8]) -> IResult<&[u8], MailboxList<'_>> {
-    map_opt(mailbox_list_nullable, |mlist| mlist)(input)
+    map_opt(
+        consumed(separated_list1(lenient_list_separator, nullable_mailboxes)),
+        |(raw, v)| {
+            report_obs_list(raw, &v);
+            vec_flatten_nonempty(v).map(MailboxList)
+        },
+    )(input)
 }
 
 // mailbox-list but allows the list to only contain "null" elements
+//
+// This is used in groups, which are terminated by a semicolon: we thus
+// do not recover from semicolons used as separators here.
 #[instrument_input("tracing")]
 pub(crate) fn mailbox_list_nullable(input: &[u8]) -> IResult<&[u8], Option<MailboxList<'_>>> {
     map(
-        separated_list1(
-            tag(","),
-            alt((map(mailbox, Some), map(opt(cfws), |_| None))),
-        ),
-        |v: Vec<Option<_>>| {
-            let v: Vec<_> = v.into_iter().flatten().collect();
-            if v.is_empty() {
-                None
-            } else {
-                Some(MailboxList(v))
-            }
+        consumed(separated_list1(tag(","), nullable_mailboxes)),
+        |(raw, v)| {
+            report_obs_list(raw, &v);
+            vec_flatten_nonempty(v).map(MailboxList)
         },
     )(input)
 }
 
+// obs-mbox-list, obs-group-list and obs-addr-list allow empty list elements.
+pub(crate) fn report_obs_list<T>(raw: &[u8], v: &[Vec<T>]) {
+    if v.len() > 1 && v.iter().any(Vec::is_empty) {
+        diagnostics::report(
+            DiagnosticKind::Obsolete("obs-list"),
+            Severity::Warning,
+            Some(raw),
+        );
+    }
+}
+
+fn nullable_mailboxes(input: &[u8]) -> IResult<&[u8], Vec<MailboxRef<'_>>> {
+    alt((juxtaposed(mailbox), map(opt(cfws), |_| vec![])))(input)
+}
+
+// Recovery: list elements that are not separated by a comma, e.g.
+// `<a@example.com> <b@example.com>`.
+pub(crate) fn juxtaposed<'a, O, F>(f: F) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], Vec<O>>
+where
+    F: Parser<&'a [u8], O, nom::error::Error<&'a [u8]>>,
+{
+    map(many1(f), |v| {
+        if v.len() > 1 {
+            diagnostics::diagnose!(
+                "tracing-recover",
+                Recovered("missing comma in list"),
+                Severity::Warning,
+                None,
+            );
+        }
+        v
+    })
+}
+
+// Separator between the elements of a mailbox or address list.
+//
+// Recovery: some clients use semicolons instead of commas (e.g.
+// `a@example.com; b@example.com`). Must not be used in groups, which end
+// with a semicolon.
+#[instrument_input("tracing")]
+pub(crate) fn lenient_list_separator(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    alt((
+        tag(","),
+        map(tag(";"), |i| {
+            diagnostics::diagnose!(
+                "tracing-recover",
+                Recovered("semicolon used as list separator"),
+                Severity::Warning,
+                None,
+            );
+            i
+        }),
+    ))(input)
+}
+
 /// Name of the email address
 ///
 /// ```abnf
@@ -157,7 +261,74 @@ pub(crate) fn mailbox_list_nullable(input: &[u8]) -> IResult<&[u8], Option<Mailb
 fn name_addr(input: &[u8]) -> IResult<&[u8], MailboxRef<'_>> {
     let (input, name) = opt(phrase)(input)?;
     let (input, addrspec) = angle_addr(input)?;
-    Ok((input, MailboxRef { name, addrspec }))
+    Ok((
+        input,
+        MailboxRef {
+            name,
+            addrspec,
+            comments: Comments::default(),
+            raw: RawInput::none(),
+        },
+    ))
+}
+
+// Recovery: display name that is not a valid phrase, e.g. because it
+// contains an unquoted "@" (`john@example.com <john@example.com>`) or an
+// unbalanced quote (`"John Doe <john@example.com>`). We take everything
+// up to the "<" as the display name.
+#[instrument_input("tracing")]
+fn recover_name_addr(input: &[u8]) -> IResult<&[u8], MailboxRef<'_>> {
+    let (input, name) =
+        take_quoted_or_until1(|c| matches!(c, b',' | b';' | b':' | b'<' | b'>' | b'[' | b']'))(
+            input,
+        )?;
+    let (input, addrspec) = angle_addr(input)?;
+    diagnostics::diagnose!(
+        "tracing-recover",
+        (name = %bytes_to_trace_string(name)),
+        Recovered("best-effort display name"),
+        Severity::Warning,
+        None,
+    );
+    Ok((
+        input,
+        MailboxRef {
+            name: recovered_display_name(name),
+            addrspec,
+            comments: Comments::default(),
+            raw: RawInput::none(),
+        },
+    ))
+}
+
+// The display name is stored as a single quoted string: we drop the quotes
+// and backslashes of quoted pairs found in the input, and normalize
+// whitespace.
+fn recovered_display_name(raw: &[u8]) -> Option<Phrase<'static>> {
+    let mut bytes = vec![];
+    let mut escaped = false;
+    for &c in raw {
+        match c {
+            _ if escaped => {
+                bytes.push(c);
+                escaped = false
+            }
+            b'\\' => escaped = true,
+            b'"' => (),
+            _ => bytes.push(c),
+        }
+    }
+    let name = String::from_utf8_lossy(&bytes)
+        .split_ascii_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if name.is_empty() {
+        None
+    } else {
+        Some(Phrase(vec![PhraseToken::Word(Word::Quoted(QuotedString(
+            vec![name.into()],
+        )))]))
+    }
 }
 
 /// Enclosed addr-spec with < and >
@@ -169,7 +340,11 @@ fn name_addr(input: &[u8]) -> IResult<&[u8], MailboxRef<'_>> {
 #[instrument_input("tracing")]
 pub fn angle_addr(input: &[u8]) -> IResult<&[u8], AddrSpec<'_>> {
     delimited(
-        tuple((opt(cfws), tag(&[ascii::LT]), opt(obs_route))),
+        tuple((
+            opt(cfws),
+            tag(&[ascii::LT]),
+            opt(obsolete("obs-route", obs_route)),
+        )),
         addr_spec,
         pair(tag(&[ascii::GT]), opt(cfws)),
     )(input)
@@ -221,8 +396,12 @@ pub fn addr_spec(input: &[u8]) -> IResult<&[u8], AddrSpec<'_>> {
             opt(map(
                 many1(pair(tag(&[ascii::AT]), domain)), // for compatibility reasons with ENRON
                 |_| {
-                    #[cfg(feature = "tracing-recover")]
-                    warn!("addr_spec with multiple @ parts")
+                    diagnostics::diagnose!(
+                        "tracing-recover",
+                        Recovered("addr_spec with multiple @ parts"),
+                        Severity::Warning,
+                        None,
+                    )
                 },
             )),
         )),
@@ -342,6 +521,7 @@ impl<'a> Print for LocalPart<'a> {
 /// ```
 #[instrument_input("tracing")]
 pub fn local_part(input: &[u8]) -> IResult<&[u8], LocalPart<'_>> {
+    let orig_input = input;
     let (input, _) = opt(cfws)(input)?;
     let (input, prefix) = many0(local_part_dot)(input)?;
     let (input, w) = local_part_word(input)?;
@@ -349,12 +529,20 @@ pub fn local_part(input: &[u8]) -> IResult<&[u8], LocalPart<'_>> {
     let (input, suffix) = many0(local_part_dot)(input)?;
 
     if !prefix.is_empty() {
-        #[cfg(feature = "tracing-recover")]
-        warn!("best-effort local-part (leading dots)");
+        diagnostics::diagnose!(
+            "tracing-recover",
+            Recovered("best-effort local-part (leading dots)"),
+            Severity::Warning,
+            None,
+        );
     }
     if !suffix.is_empty() {
-        #[cfg(feature = "tracing-recover")]
-        warn!("best-effort local part (trailing dots)");
+        diagnostics::diagnose!(
+            "tracing-recover",
+            Recovered("best-effort local part (trailing dots)"),
+            Severity::Warning,
+            None,
+        );
     }
 
     let mut v: Vec<LocalPartToken> = vec![];
@@ -362,13 +550,30 @@ pub fn local_part(input: &[u8]) -> IResult<&[u8], LocalPart<'_>> {
     v.push(w);
     for (dots, w) in ws.into_iter() {
         if dots.len() > 1 {
-            #[cfg(feature = "tracing-recover")]
-            warn!("best-effort local part (consecutive dots)");
+            diagnostics::diagnose!(
+                "tracing-recover",
+                Recovered("best-effort local part (consecutive dots)"),
+                Severity::Warning,
+                None,
+            );
         }
         v.extend(dots);
         v.push(w);
     }
     v.extend(suffix);
+
+    // obs-local-part: several words, some of which are quoted strings
+    let mut words = v.iter().filter_map(|t| match t {
+        LocalPartToken::Word(w) => Some(w),
+        LocalPartToken::Dot => None,
+    });
+    if words.clone().count() > 1 && words.any(|w| matches!(w, Word::Quoted(_))) {
+        diagnostics::report(
+            DiagnosticKind::Obsolete("obs-local-part"),
+            Severity::Warning,
+            Some(&orig_input[..orig_input.len() - input.len()]),
+        );
+    }
     Ok((input, LocalPart(v)))
 }
 fn local_part_dot(input: &[u8]) -> IResult<&[u8], LocalPartToken<'_>> {
@@ -435,8 +640,12 @@ pub fn domain(input: &[u8]) -> IResult<&[u8], Domain<'_>> {
             terminated(
                 separated_list1(tag("."), atom),
                 opt(map(tag("."), |i| {
-                    #[cfg(feature = "tracing-recover")]
-                    warn!("trailing dot in domain");
+                    diagnostics::diagnose!(
+                        "tracing-recover",
+                        Recovered("trailing dot in domain"),
+                        Severity::Warning,
+                        None,
+                    );
                     i
                 })),
             ),
@@ -537,7 +746,16 @@ fn is_obs_dtext(c: char) -> bool {
 
 #[instrument_input("tracing")]
 pub fn dtext<'a>(input: &'a [u8]) -> IResult<&'a [u8], Dtext<'a>> {
-    map(take_utf8_while1(is_dtext), Dtext)(input)
+    map(consumed(take_utf8_while1(is_dtext)), |(raw, s)| {
+        if !s.chars().all(is_strict_dtext) {
+            diagnostics::report(
+                DiagnosticKind::Obsolete("obs-dtext"),
+                Severity::Warning,
+                Some(raw),
+            );
+        }
+        Dtext(s)
+    })(input)
 }
 
 #[cfg(test)]
@@ -546,6 +764,7 @@ mod tests {
     use crate::print::tests::print_to_vec;
     use crate::text::misc_token::PhraseToken;
     use crate::text::quoted::QuotedString;
+    use crate::text::whitespace::Comment;
 
     // NOTE: this roundtrip property does not hold in general for all valid
     // 'addr-spec's, in particular because of the obsolete syntax (which gets
@@ -750,6 +969,8 @@ mod tests {
                     ]),
                     domain: Domain::Atoms(vec![Atom("example"[..].into()), Atom("com"[..].into())]),
                 },
+                comments: Comments::default(),
+                raw: RawInput::none(),
             },
         );
 
@@ -768,6 +989,8 @@ mod tests {
                         )))]),
                         domain: Domain::Atoms(vec![Atom("mail".into()), Atom("net".into())]),
                     },
+                    comments: Comments::default(),
+                    raw: RawInput::none(),
                 }
             ))
         );
@@ -785,6 +1008,8 @@ mod tests {
                     )))]),
                     domain: Domain::Atoms(vec![Atom("x"[..].into()), Atom("test"[..].into())]),
                 },
+                comments: Comments::default(),
+                raw: RawInput::none(),
             },
         );
 
@@ -798,6 +1023,8 @@ mod tests {
                     )))]),
                     domain: Domain::Atoms(vec![Atom("example"[..].into()), Atom("org"[..].into())]),
                 },
+                comments: Comments::default(),
+                raw: RawInput::none(),
             },
         );
 
@@ -813,6 +1040,8 @@ mod tests {
                     )))]),
                     domain: Domain::Atoms(vec![Atom("y"[..].into()), Atom("test"[..].into())]),
                 },
+                comments: Comments::default(),
+                raw: RawInput::none(),
             },
         );
 
@@ -826,6 +1055,8 @@ mod tests {
                     )))]),
                     domain: Domain::Atoms(vec![Atom("nil"[..].into()), Atom("test"[..].into())]),
                 },
+                comments: Comments::default(),
+                raw: RawInput::none(),
             },
             r#"boss@nil.test"#.as_bytes(),
         );
@@ -850,6 +1081,8 @@ mod tests {
                     )))]),
                     domain: Domain::Atoms(vec![Atom("example"[..].into()), Atom("net"[..].into())]),
                 },
+                comments: Comments::default(),
+                raw: RawInput::none(),
             },
         );
 
@@ -871,10 +1104,56 @@ mod tests {
                         Dtext("<?@?>"[..].into()),
                     ]),
                 },
+                comments: Comments::default(),
+                raw: RawInput::none(),
             },
         );
     }
 
+    #[test]
+    fn test_mailbox_comments() {
+        let print_with_comments = |mbox: &MailboxRef| {
+            let cfg = crate::print::FMT_DEFAULT
+                .with_seed(Some(0))
+                .with_comments(true);
+            String::from_utf8(crate::print::print_to_vec(cfg, mbox)).unwrap()
+        };
+
+        let (rest, mbox) = mailbox(b"foo@example.com (Foo Bar)").unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(mbox.comments.after, vec![Comment(b"Foo Bar"[..].into())]);
+        assert_eq!(print_to_vec(&mbox), b"foo@example.com");
+        assert_eq!(print_with_comments(&mbox), "foo@example.com (Foo Bar)");
+
+        let (rest, mbox) =
+            mailbox(br#"Pete(A nice \) chap) <pete(his account)@silly.test(his host)>"#).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            mbox.comments.after,
+            vec![
+                Comment(br#"A nice \) chap"#[..].into()),
+                Comment(b"his account"[..].into()),
+                Comment(b"his host"[..].into()),
+            ]
+        );
+        assert_eq!(
+            print_with_comments(&mbox),
+            r#"Pete <pete@silly.test> (A nice \) chap) (his account) (his host)"#
+        );
+
+        // comments before the mailbox are printed back before it
+        let (_, mbox) = mailbox(b"(Foo Bar) foo@example.com (work)").unwrap();
+        assert_eq!(mbox.comments.before, vec![Comment(b"Foo Bar"[..].into())]);
+        assert_eq!(
+            print_with_comments(&mbox),
+            "(Foo Bar) foo@example.com (work)"
+        );
+
+        // parentheses in quoted strings are not comments
+        let (_, mbox) = mailbox(br#""Foo (Bar)" <foo@example.com>"#).unwrap();
+        assert!(mbox.comments.is_empty());
+    }
+
     #[test]
     fn test_domain_list() {
         assert_eq!(
@@ -1013,6 +1292,8 @@ mod tests {
                         Atom("com"[..].into()),
                     ]),
                 },
+                comments: Comments::default(),
+                raw: RawInput::none(),
             },
             b"mark_kopinski/intl/acim/americancentury@americancentury.com",
         );
@@ -1053,6 +1334,71 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_mailbox_list_recover() {
+        mailbox_list_reprint(
+            b"a@example.com; b@example.com;c@example.com",
+            b"a@example.com, b@example.com, c@example.com",
+        );
+        mailbox_list_reprint(
+            b"<a@example.com> Bob <b@example.com>,c@example.com",
+            b"a@example.com, Bob <b@example.com>, c@example.com",
+        );
+    }
+
+    #[test]
+    fn test_mailbox_recover_display_name() {
+        let quoted = |s: &'static str| {
+            Some(Phrase(vec![PhraseToken::Word(Word::Quoted(QuotedString(
+                vec![s.into()],
+            )))]))
+        };
+        let addrspec = AddrSpec {
+            local_part: LocalPart(vec![LocalPartToken::Word(Word::Atom(Atom("john".into())))]),
+            domain: Domain::Atoms(vec![Atom("example".into()), Atom("com".into())]),
+        };
+        mailbox_parsed_printed(
+            b"john@example.com <john@example.com>",
+            MailboxRef {
+                name: quoted("john@example.com"),
+                addrspec: addrspec.clone(),
+                comments: Comments::default(),
+                raw: RawInput::none(),
+            },
+            br#""john@example.com" <john@example.com>"#,
+        );
+        mailbox_parsed_printed(
+            b"John  Doe @ Work <john@example.com>",
+            MailboxRef {
+                name: quoted("John Doe @ Work"),
+                addrspec: addrspec.clone(),
+                comments: Comments::default(),
+                raw: RawInput::none(),
+            },
+            br#""John Doe @ Work" <john@example.com>"#,
+        );
+        mailbox_parsed_printed(
+            br#""John \"Doe <john@example.com>"#,
+            MailboxRef {
+                name: quoted("John \"Doe"),
+                addrspec: addrspec.clone(),
+                comments: Comments::default(),
+                raw: RawInput::none(),
+            },
+            br#""John \"Doe" <john@example.com>"#,
+        );
+        mailbox_parsed_printed(
+            b"\"<john@example.com>",
+            MailboxRef {
+                name: None,
+                addrspec,
+                comments: Comments::default(),
+                raw: RawInput::none(),
+            },
+            b"john@example.com",
+        );
+    }
+
     #[test]
     fn test_dtext_strictify() {
         let s: &str = &Dtext("\x03".into()).to_strict_best_effort().0;
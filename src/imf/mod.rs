@@ -2,9 +2,11 @@
 #[cfg(feature = "arbitrary")]
 use arbitrary::Arbitrary;
 pub mod address;
+pub mod arc;
 pub mod datetime;
 pub mod field;
 pub mod identification;
+pub mod list;
 pub mod mailbox;
 pub mod mime;
 pub mod trace;
@@ -14,15 +16,18 @@ use std::collections::HashSet;
 
 #[cfg(feature = "arbitrary")]
 use crate::fuzz_eq::FuzzEq;
+use crate::header::DuplicatePolicy;
 use crate::i18n::ContainsUtf8;
 use crate::imf::address::AddressRef;
 use crate::imf::datetime::DateTime;
 use crate::imf::field::{Entry, Field};
 use crate::imf::identification::MessageID;
+use crate::imf::list::{ListId, ListPost, ListUris, UnsubscribePost};
 use crate::imf::mailbox::{MailboxList, MailboxRef};
 use crate::imf::mime::Version;
 use crate::imf::trace::ReturnPath;
 use crate::text::misc_token::{PhraseList, Unstructured};
+use crate::utils::{Discard, SingleFields};
 
 #[derive(Clone, ContainsUtf8, Debug, PartialEq, ToStatic)]
 #[cfg_attr(feature = "arbitrary", derive(Arbitrary, FuzzEq))]
@@ -54,6 +59,16 @@ pub struct Imf<'a> {
     // 3.6.7 Trace Fields
     pub trace: Vec<TraceField<'a>>,
 
+    // RFC 2369 / 2919 / 8058 List Header Fields
+    pub list_id: Option<ListId<'a>>,
+    pub list_help: Option<ListUris<'a>>,
+    pub list_unsubscribe: Option<ListUris<'a>>,
+    pub list_unsubscribe_post: Option<UnsubscribePost<'a>>,
+    pub list_subscribe: Option<ListUris<'a>>,
+    pub list_post: Option<ListPost<'a>>,
+    pub list_owner: Option<ListUris<'a>>,
+    pub list_archive: Option<ListUris<'a>>,
+
     // MIME
     pub mime_version: Option<Version>,
 }
@@ -135,6 +150,11 @@ pub enum TraceField<'a> {
     // that do not parse even wrt to the rough tokenization of RFC5322.
     Received(Unstructured<'a>),
     ReturnPath(ReturnPath<'a>),
+    // ARC header fields (RFC 8617) are added at the top of the message by
+    // each intermediary, like other trace fields.
+    ArcSeal(arc::Seal<'a>),
+    ArcMessageSignature(arc::MessageSignature<'a>),
+    ArcAuthenticationResults(arc::AuthenticationResults<'a>),
 }
 
 impl<'a> Imf<'a> {
@@ -153,6 +173,14 @@ impl<'a> Imf<'a> {
             comments: vec![],
             keywords: vec![],
             trace: vec![],
+            list_id: None,
+            list_help: None,
+            list_unsubscribe: None,
+            list_unsubscribe_post: None,
+            list_subscribe: None,
+            list_post: None,
+            list_owner: None,
+            list_archive: None,
             mime_version: None,
         }
     }
@@ -188,6 +216,12 @@ impl<'a> Imf<'a> {
         }
     }
 
+    /// Group the ARC header fields of the trace section into sets, checking
+    /// the structure of the chain (RFC 8617, section 5.2).
+    pub fn arc_chain(&self) -> Result<Vec<arc::Set<'a, '_>>, arc::ChainError> {
+        arc::chain(&self.trace)
+    }
+
     pub fn get_field(&self, f: field::Entry) -> Option<field::Field<'a>> {
         match f {
             field::Entry::Date => match &self.date {
@@ -236,10 +270,33 @@ impl<'a> Imf<'a> {
             field::Entry::Subject => self.subject.clone().map(field::Field::Subject),
             field::Entry::Comments(i) => Some(field::Field::Comments(self.comments[i].clone())),
             field::Entry::Keywords(i) => Some(field::Field::Keywords(self.keywords[i].clone())),
+            field::Entry::ListId => self.list_id.clone().map(field::Field::ListId),
+            field::Entry::ListHelp => self.list_help.clone().map(field::Field::ListHelp),
+            field::Entry::ListUnsubscribe => self
+                .list_unsubscribe
+                .clone()
+                .map(field::Field::ListUnsubscribe),
+            field::Entry::ListUnsubscribePost => self
+                .list_unsubscribe_post
+                .clone()
+                .map(field::Field::ListUnsubscribePost),
+            field::Entry::ListSubscribe => {
+                self.list_subscribe.clone().map(field::Field::ListSubscribe)
+            }
+            field::Entry::ListPost => self.list_post.clone().map(field::Field::ListPost),
+            field::Entry::ListOwner => self.list_owner.clone().map(field::Field::ListOwner),
+            field::Entry::ListArchive => self.list_archive.clone().map(field::Field::ListArchive),
             field::Entry::MIMEVersion => self.mime_version.clone().map(field::Field::MIMEVersion),
             field::Entry::Trace(i) => match &self.trace[i] {
                 TraceField::Received(r) => Some(field::Field::Received(r.clone())),
                 TraceField::ReturnPath(p) => Some(field::Field::ReturnPath(p.clone())),
+                TraceField::ArcSeal(s) => Some(field::Field::ArcSeal(s.clone())),
+                TraceField::ArcMessageSignature(s) => {
+                    Some(field::Field::ArcMessageSignature(s.clone()))
+                }
+                TraceField::ArcAuthenticationResults(r) => {
+                    Some(field::Field::ArcAuthenticationResults(r.clone()))
+                }
             },
         }
     }
@@ -313,6 +370,30 @@ impl<'a> Imf<'a> {
         for i in 0..self.keywords.len() {
             fs.insert(field::Entry::Keywords(i));
         }
+        if self.list_id.is_some() {
+            fs.insert(field::Entry::ListId);
+        }
+        if self.list_help.is_some() {
+            fs.insert(field::Entry::ListHelp);
+        }
+        if self.list_unsubscribe.is_some() {
+            fs.insert(field::Entry::ListUnsubscribe);
+        }
+        if self.list_unsubscribe_post.is_some() {
+            fs.insert(field::Entry::ListUnsubscribePost);
+        }
+        if self.list_subscribe.is_some() {
+            fs.insert(field::Entry::ListSubscribe);
+        }
+        if self.list_post.is_some() {
+            fs.insert(field::Entry::ListPost);
+        }
+        if self.list_owner.is_some() {
+            fs.insert(field::Entry::ListOwner);
+        }
+        if self.list_archive.is_some() {
+            fs.insert(field::Entry::ListArchive);
+        }
         fs.insert(field::Entry::MIMEVersion);
 
         (trace, fs)
@@ -342,24 +423,55 @@ pub struct PartialImf<'a> {
     keywords: Vec<PhraseList<'a>>,
     trace: Vec<TraceField<'a>>,
     trace_complete: bool,
+    list_id: Option<ListId<'a>>,
+    list_help: Option<ListUris<'a>>,
+    list_unsubscribe: Option<ListUris<'a>>,
+    list_unsubscribe_post: Option<UnsubscribePost<'a>>,
+    list_subscribe: Option<ListUris<'a>>,
+    list_post: Option<ListPost<'a>>,
+    list_owner: Option<ListUris<'a>>,
+    list_archive: Option<ListUris<'a>>,
     mime_version: Option<Version>,
+    single: SingleFields<Entry>,
 }
 
 #[derive(Clone, Copy, Debug)]
 pub enum AddFieldErr {
-    // This field results in no entry, but its contents have been taken into
-    // account and there is no loss of data.
+    // This field results in no entry, but its contents have been merged into
+    // the entry of a previous field and there is no loss of data.
     NoEntry,
-    // This field is conflicting with an earlier field and must be dropped, its
-    // data will not be part of the IMF AST.
+    // This field is conflicting with an earlier field (a trace field after
+    // other fields), its data will not be part of the IMF AST. The caller
+    // keeps it as an invalid field.
     Conflict,
+    // This field may only appear once and is discarded by the duplicate
+    // policy.
+    Duplicate,
+    // This field may only appear once and all its occurrences are discarded by
+    // the duplicate policy (`DuplicatePolicy::Reject`), including the one
+    // previously returned as the given entry.
+    Rejected(Entry),
 }
 
 impl<'a> PartialImf<'a> {
+    pub fn new(policy: DuplicatePolicy) -> Self {
+        PartialImf {
+            single: SingleFields::new(policy),
+            ..Default::default()
+        }
+    }
+
+    /// Add a field. With `DuplicatePolicy::KeepLast`, the returned entry can
+    /// be one that was already returned for an earlier field, which is then
+    /// replaced by this one.
     pub fn add_field(&mut self, f: Field<'a>) -> Result<Entry, AddFieldErr> {
         match &f {
             // trace fields
-            Field::ReturnPath(_) | Field::Received(_) => {
+            Field::ReturnPath(_)
+            | Field::Received(_)
+            | Field::ArcSeal(_)
+            | Field::ArcMessageSignature(_)
+            | Field::ArcAuthenticationResults(_) => {
                 if self.trace_complete {
                     // drop trace fields that come after other IMF fields
                     return Err(AddFieldErr::Conflict);
@@ -373,19 +485,38 @@ impl<'a> PartialImf<'a> {
             }
         }
         match f {
-            Field::Date(date) => set_if_new(&mut self.date, date, Entry::Date),
-            Field::From(from) => set_if_new(&mut self.from, from, Entry::From),
-            Field::Sender(sender) => set_if_new(&mut self.sender, sender, Entry::Sender),
-            Field::ReplyTo(reply_to) => set_if_new(&mut self.reply_to, reply_to, Entry::ReplyTo),
+            Field::Date(date) => set_if_new(&mut self.single, &mut self.date, date, Entry::Date),
+            Field::From(from) => set_if_new(&mut self.single, &mut self.from, from, Entry::From),
+            Field::Sender(sender) => {
+                set_if_new(&mut self.single, &mut self.sender, sender, Entry::Sender)
+            }
+            Field::ReplyTo(reply_to) => set_if_new(
+                &mut self.single,
+                &mut self.reply_to,
+                reply_to,
+                Entry::ReplyTo,
+            ),
             Field::To(to) => set_or_extend(&mut self.to, to, Entry::To),
             Field::Cc(cc) => set_or_extend(&mut self.cc, cc, Entry::Cc),
             Field::Bcc(bcc) => set_or_extend(&mut self.bcc, bcc, Entry::Bcc),
-            Field::MessageID(id) => set_if_new(&mut self.msg_id, id, Entry::MessageID),
-            Field::InReplyTo(in_reply_to) => {
-                set_if_new(&mut self.in_reply_to, in_reply_to, Entry::InReplyTo)
+            Field::MessageID(id) => {
+                set_if_new(&mut self.single, &mut self.msg_id, id, Entry::MessageID)
+            }
+            Field::InReplyTo(in_reply_to) => set_if_new(
+                &mut self.single,
+                &mut self.in_reply_to,
+                in_reply_to,
+                Entry::InReplyTo,
+            ),
+            Field::References(refs) => set_if_new(
+                &mut self.single,
+                &mut self.references,
+                refs,
+                Entry::References,
+            ),
+            Field::Subject(subject) => {
+                set_if_new(&mut self.single, &mut self.subject, subject, Entry::Subject)
             }
-            Field::References(refs) => set_if_new(&mut self.references, refs, Entry::References),
-            Field::Subject(subject) => set_if_new(&mut self.subject, subject, Entry::Subject),
             Field::Comments(comments) => {
                 let idx = self.comments.len();
                 self.comments.push(comments);
@@ -406,9 +537,64 @@ impl<'a> PartialImf<'a> {
                 self.trace.push(TraceField::ReturnPath(path));
                 Ok(Entry::Trace(idx))
             }
-            Field::MIMEVersion(version) => {
-                set_if_new(&mut self.mime_version, version, Entry::MIMEVersion)
+            Field::ArcSeal(seal) => {
+                let idx = self.trace.len();
+                self.trace.push(TraceField::ArcSeal(seal));
+                Ok(Entry::Trace(idx))
+            }
+            Field::ArcMessageSignature(sig) => {
+                let idx = self.trace.len();
+                self.trace.push(TraceField::ArcMessageSignature(sig));
+                Ok(Entry::Trace(idx))
+            }
+            Field::ArcAuthenticationResults(res) => {
+                let idx = self.trace.len();
+                self.trace.push(TraceField::ArcAuthenticationResults(res));
+                Ok(Entry::Trace(idx))
+            }
+            Field::ListId(id) => set_if_new(&mut self.single, &mut self.list_id, id, Entry::ListId),
+            Field::ListHelp(uris) => {
+                set_if_new(&mut self.single, &mut self.list_help, uris, Entry::ListHelp)
+            }
+            Field::ListUnsubscribe(uris) => set_if_new(
+                &mut self.single,
+                &mut self.list_unsubscribe,
+                uris,
+                Entry::ListUnsubscribe,
+            ),
+            Field::ListUnsubscribePost(post) => set_if_new(
+                &mut self.single,
+                &mut self.list_unsubscribe_post,
+                post,
+                Entry::ListUnsubscribePost,
+            ),
+            Field::ListSubscribe(uris) => set_if_new(
+                &mut self.single,
+                &mut self.list_subscribe,
+                uris,
+                Entry::ListSubscribe,
+            ),
+            Field::ListPost(post) => {
+                set_if_new(&mut self.single, &mut self.list_post, post, Entry::ListPost)
             }
+            Field::ListOwner(uris) => set_if_new(
+                &mut self.single,
+                &mut self.list_owner,
+                uris,
+                Entry::ListOwner,
+            ),
+            Field::ListArchive(uris) => set_if_new(
+                &mut self.single,
+                &mut self.list_archive,
+                uris,
+                Entry::ListArchive,
+            ),
+            Field::MIMEVersion(version) => set_if_new(
+                &mut self.single,
+                &mut self.mime_version,
+                version,
+                Entry::MIMEVersion,
+            ),
         }
     }
 
@@ -441,18 +627,29 @@ impl<'a> PartialImf<'a> {
             comments: self.comments,
             keywords: self.keywords,
             trace: self.trace,
+            list_id: self.list_id,
+            list_help: self.list_help,
+            list_unsubscribe: self.list_unsubscribe,
+            list_unsubscribe_post: self.list_unsubscribe_post,
+            list_subscribe: self.list_subscribe,
+            list_post: self.list_post,
+            list_owner: self.list_owner,
+            list_archive: self.list_archive,
             mime_version: self.mime_version,
         }
     }
 }
 
-fn set_if_new<T: PartialEq, U>(o: &mut Option<T>, x: T, y: U) -> Result<U, AddFieldErr> {
-    match *o {
-        None => {
-            *o = Some(x);
-            Ok(y)
-        }
-        Some(_) => Err(AddFieldErr::Conflict),
+fn set_if_new<T>(
+    single: &mut SingleFields<Entry>,
+    o: &mut Option<T>,
+    x: T,
+    e: Entry,
+) -> Result<Entry, AddFieldErr> {
+    match single.set(o, x, e) {
+        Ok(()) => Ok(e),
+        Err(Discard::Field) => Err(AddFieldErr::Duplicate),
+        Err(Discard::All) => Err(AddFieldErr::Rejected(e)),
     }
 }
 
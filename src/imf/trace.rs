@@ -11,6 +11,7 @@ use nom::{
 #[cfg(feature = "tracing")]
 use tracing::warn;
 
+use crate::diagnostics::{self, Severity};
 #[cfg(feature = "arbitrary")]
 use crate::fuzz_eq::FuzzEq;
 use crate::i18n::ContainsUtf8;
@@ -44,14 +45,24 @@ pub fn return_path(input: &[u8]) -> IResult<&[u8], ReturnPath<'_>> {
         map(mailbox::angle_addr, |a| ReturnPath(Some(a))),
         map(consumed(mailbox::addr_spec), |(_i, a)| {
             // This is not allowed by the RFC but happens in real-world emails
-            #[cfg(feature = "tracing-recover")]
-            warn!(input = %bytes_to_trace_string(_i), "bare addr-spec in return-path");
+            diagnostics::diagnose!(
+                "tracing-recover",
+                (input = %bytes_to_trace_string(_i)),
+                Recovered("bare addr-spec in return-path"),
+                Severity::Warning,
+                None,
+            );
             ReturnPath(Some(a))
         }),
         map(consumed(mailbox::mailbox), |(_i, m)| {
             // This is not allowed by the RFC but happens in some real-world emails
-            #[cfg(feature = "tracing-recover")]
-            warn!(input = %bytes_to_trace_string(_i), "mailbox in return-path");
+            diagnostics::diagnose!(
+                "tracing-recover",
+                (input = %bytes_to_trace_string(_i)),
+                Recovered("mailbox in return-path"),
+                Severity::Warning,
+                None,
+            );
             ReturnPath(Some(m.addrspec))
         }),
         empty_path,
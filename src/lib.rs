@@ -21,12 +21,18 @@ pub mod text;
 /// Printing with email-specific line folding
 pub mod print;
 
+/// DKIM canonicalization and hashing (RFC 6376)
+pub mod dkim;
+
 /// Helpers related to UTF-8 support in headers (RFC 6532)
 pub mod i18n;
 
 /// Support for storing references to raw input slices in AST nodes.
 pub mod raw_input;
 
+/// Structured diagnostics about the recovery decisions made while parsing
+pub mod diagnostics;
+
 /// Custom equality trait used for fuzz-checking
 #[cfg(feature = "arbitrary")]
 pub mod fuzz_eq;
@@ -73,6 +79,187 @@ pub fn parse_message(input: &[u8]) -> message::Message<'_> {
     message::message(input)
 }
 
+/// Parse a whole email, also returning the recovery decisions made while
+/// parsing it as a list of diagnostics.
+///
+/// Parsing never fails, but some inputs are only parsed on a best-effort
+/// basis. The diagnostics describe the non-compliant or unsupported input that
+/// was found, in which header field, and at which byte range of `input`. This
+/// does not require a `tracing` subscriber. See `parse_message_with` for the
+/// other parsing options.
+///
+/// # Examples
+///
+/// ```
+/// use eml_codec::diagnostics::{DiagnosticKind, Severity};
+///
+/// let input = b"From: alice@example.com\r
+/// To: bob@example.com; carol@example.com\r
+/// Date: yesterday\r
+/// \r
+/// Hi!";
+///
+/// let (email, diagnostics) = eml_codec::parse_message_with_diagnostics(input);
+/// assert_eq!(email.imf.to.len(), 2);
+/// assert_eq!(diagnostics.len(), 2);
+///
+/// assert_eq!(diagnostics[0].header.as_deref(), Some("To"));
+/// assert_eq!(diagnostics[0].severity, Severity::Warning);
+///
+/// assert_eq!(diagnostics[1].kind, DiagnosticKind::InvalidField);
+/// assert_eq!(diagnostics[1].severity, Severity::Error);
+/// assert_eq!(
+///     &input[diagnostics[1].range.clone().unwrap()],
+///     b"Date: yesterday",
+/// );
+/// ```
+pub fn parse_message_with_diagnostics(
+    input: &[u8],
+) -> (message::Message<'_>, Vec<diagnostics::Diagnostic>) {
+    let parsed = message::message_with_config(input, &message::ParseConfig::default())
+        .expect("parsing only fails in strict mode");
+    (parsed.message, parsed.diagnostics)
+}
+
+/// Parse a whole email, failing if it does not strictly follow RFC 5322 and
+/// RFC 2045 to 2049.
+///
+/// The error lists every violation that was found: the diagnostics returned
+/// by `parse_message_with_diagnostics` (including obsolete syntax), and those
+/// of `diagnostics::validate` (missing mandatory fields, bare CR or LF, lines
+/// over 998 octets, 8-bit header text or 8-bit data in 7-bit parts).
+///
+/// # Examples
+///
+/// ```
+/// use eml_codec::diagnostics::DiagnosticKind;
+///
+/// let input = b"From: alice@example.com\r
+/// Date: Tue, 21 Nov 2023 07:07:07 +0100\r
+/// \r
+/// Hi!";
+/// assert!(eml_codec::parse_message_strict(input).is_ok());
+///
+/// let input = b"From: alice@example.com\r
+/// Date: Tue, 21 Nov 23 07:07:07 GMT\r
+/// \r
+/// Hi!";
+/// let errors = eml_codec::parse_message_strict(input).unwrap_err();
+/// assert_eq!(
+///     errors.iter().map(|e| e.kind).collect::<Vec<_>>(),
+///     vec![
+///         DiagnosticKind::Obsolete("obs-date"),
+///         DiagnosticKind::Obsolete("obs-zone"),
+///     ],
+/// );
+/// ```
+pub fn parse_message_strict(
+    input: &[u8],
+) -> Result<message::Message<'_>, Vec<diagnostics::Diagnostic>> {
+    let cfg = message::ParseConfig::default().with_strict(true);
+    message::message_with_config(input, &cfg).map(|parsed| parsed.message)
+}
+
+/// Parse a whole email with the given configuration (see
+/// `message::ParseConfig`), also returning the recovery decisions made while
+/// parsing it as a list of diagnostics.
+///
+/// Parsing never fails, but some inputs are only parsed on a best-effort
+/// basis. The diagnostics describe the non-compliant or unsupported input that
+/// was found, in which header field, and at which byte range of `input`. This
+/// does not require a `tracing` subscriber. In strict mode, they are returned
+/// as an error instead.
+///
+/// The parsed message can be turned into a `message::LosslessMessage`, whose
+/// printing yields exactly `input`, including the original whitespace, line
+/// folding, comments, duplicate fields and malformed lines, which the regular
+/// printer normalizes or drops.
+///
+/// # Examples
+///
+/// ```
+/// use eml_codec::diagnostics::{DiagnosticKind, Severity};
+/// use eml_codec::header::DuplicatePolicy;
+/// use eml_codec::message::ParseConfig;
+/// use eml_codec::text::charset::EmailCharset;
+///
+/// let input = b"From: Andr\xe9 <andre@example.com>\r
+/// From: mallory@example.com\r
+/// To: bob@example.com; carol@example.com\r
+/// Subject: Caf\xe9 cr\xe8me\r
+/// \r
+/// Bonjour !";
+///
+/// let cfg = ParseConfig::default()
+///     .with_policy(DuplicatePolicy::KeepLast)
+///     .with_charset(Some(EmailCharset::from("iso-8859-1")));
+/// let parsed = eml_codec::parse_message_with(input, &cfg).unwrap();
+/// assert_eq!(parsed.message.imf.subject.as_ref().unwrap().to_string(), " Café crème");
+/// assert_eq!(parsed.message.imf.to.len(), 2);
+/// assert_eq!(
+///     parsed.message.duplicates[0].raw.0,
+///     Some(&b"From: Andr\xe9 <andre@example.com>"[..]),
+/// );
+///
+/// let recovered = parsed
+///     .diagnostics
+///     .iter()
+///     .find(|d| d.header.as_deref() == Some("To"))
+///     .unwrap();
+/// assert_eq!(
+///     recovered.kind,
+///     DiagnosticKind::Recovered("semicolon used as list separator"),
+/// );
+/// assert_eq!(recovered.severity, Severity::Warning);
+///
+/// // lossless mode
+/// let email = parsed.into_lossless();
+/// assert_eq!(eml_codec::print::print_to_vec(eml_codec::print::FMT_DEFAULT, email), input);
+///
+/// // strict mode
+/// let input = b"From: alice@example.com\r
+/// Date: Tue, 21 Nov 23 07:07:07 GMT\r
+/// \r
+/// Hi!";
+/// let errors = eml_codec::parse_message_with(input, &cfg.with_strict(true)).unwrap_err();
+/// assert_eq!(
+///     errors.iter().map(|e| e.kind).collect::<Vec<_>>(),
+///     vec![
+///         DiagnosticKind::Obsolete("obs-date"),
+///         DiagnosticKind::Obsolete("obs-zone"),
+///     ],
+/// );
+/// ```
+pub fn parse_message_with<'a>(
+    input: &'a [u8],
+    cfg: &message::ParseConfig,
+) -> Result<message::ParsedMessage<'a>, Vec<diagnostics::Diagnostic>> {
+    message::message_with_config(input, cfg)
+}
+
+/// Normalize a message stored or transmitted in another format before parsing
+/// it with e.g. `parse_message`.
+///
+/// Bare LF line endings are turned into CRLF. If `dot_stuffed` is set, the
+/// input is assumed to be the data of an SMTP DATA command, whose
+/// dot-stuffing is undone. This is the reverse of the `line_ending` and
+/// `dot_stuffing` options of `print::FmtConfig`.
+///
+/// # Examples
+///
+/// ```
+/// let input = b"From: alice@example.com\nSubject: Hello\n\n..signature\n.\n";
+/// let input = eml_codec::normalize_input(input, true);
+/// assert_eq!(
+///     input.as_ref(),
+///     b"From: alice@example.com\r\nSubject: Hello\r\n\r\n.signature\r\n",
+/// );
+/// let email = eml_codec::parse_message(&input);
+/// ```
+pub fn normalize_input(input: &[u8], dot_stuffed: bool) -> std::borrow::Cow<'_, [u8]> {
+    text::whitespace::normalize(input, dot_stuffed)
+}
+
 /// Print a whole email.
 ///
 /// The `seed` parameter controls the RNG used to generate multipart boundaries.
@@ -81,6 +268,36 @@ pub fn print_message(msg: message::Message<'_>, seed: Option<u64>) -> Vec<u8> {
     print::print_to_vec(print::FMT_DEFAULT.with_seed(seed), msg)
 }
 
+/// Print a whole email to `writer`.
+///
+/// Contrary to `print_message`, the output is not accumulated in memory: it is
+/// written as printing goes (see `print::WriteFmt`). Returns the first error
+/// returned by `writer`, if any.
+///
+/// The `seed` parameter controls the RNG used to generate multipart boundaries.
+/// Passing `None` will use randomness from the operating system.
+///
+/// # Examples
+///
+/// ```
+/// let input = b"From: alice@example.com\r
+/// Subject: Hello\r
+/// \r
+/// Hi!";
+///
+/// let email = eml_codec::parse_message(input);
+/// let mut out = vec![];
+/// eml_codec::print_message_to(&mut out, &email, Some(0)).unwrap();
+/// assert!(out.ends_with(b"MIME-Version: 1.0\r\n\r\nHi!"));
+/// ```
+pub fn print_message_to(
+    writer: impl std::io::Write,
+    msg: &message::Message<'_>,
+    seed: Option<u64>,
+) -> std::io::Result<()> {
+    print::print_to(writer, print::FMT_DEFAULT.with_seed(seed), msg)
+}
+
 /// Only extract the headers of the email that are part of the Internet Message Format spec
 ///
 /// Emails headers contain MIME and IMF (Internet Message Format) headers.
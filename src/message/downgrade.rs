@@ -0,0 +1,648 @@
+//! Downgrade of internationalized messages (RFC 6857), so that they can be
+//! relayed to a server that does not support the SMTPUTF8 extension.
+
+use bounded_static::ToBoundedStatic;
+use nom::combinator::all_consuming;
+use std::borrow::Cow;
+
+use crate::header;
+use crate::i18n::ContainsUtf8;
+use crate::imf::{
+    self,
+    address::{AddressRef, GroupRef},
+    field::Entry,
+    mailbox::{Domain, MailboxList, MailboxRef},
+    trace::ReturnPath,
+    TraceField,
+};
+use crate::message::{field::MessageEntry, Message};
+use crate::mime::{self, mechanism::Mechanism, r#type::MessageSubtype, CommonMIME};
+use crate::part::{field::EntityEntry, AnyPart, MimeBody};
+use crate::print::{print_to_vec, FMT_NOFOLD};
+use crate::raw_input::RawInput;
+use crate::text::{
+    ascii,
+    encoding::{encoded_word_plain, Context, EncodedWord},
+    misc_token::{self, MIMEWord, Phrase, PhraseToken, UnstrToken, UnstrTxtKind},
+    words::{is_mime_atom_text, Atom, MIMEAtom},
+};
+
+// Charset of the encoded words created by the downgrade.
+pub(crate) type Charset = &'static encoding_rs::Encoding;
+
+/// See `Message::downgrade`.
+pub(crate) fn message(msg: &mut Message, cs: Charset) -> bool {
+    let from_valid = !matches!(msg.imf.from, imf::From::InvalidMissingSender { .. });
+    let mime_before = msg.mime_body.mime();
+    let mut ascii = body(&mut msg.mime_body, cs);
+    let mime_after = msg.mime_body.mime();
+
+    let mut entries = Vec::with_capacity(msg.entries.len());
+    let mut dropped_trace = vec![];
+    for entry in std::mem::take(&mut msg.entries) {
+        match entry {
+            MessageEntry::Imf { e, raw_body } => {
+                let original = match msg.imf.get_field(e) {
+                    Some(f) if f.contains_utf8() => f,
+                    _ => {
+                        entries.push(MessageEntry::Imf { e, raw_body });
+                        continue;
+                    }
+                };
+                let (keep, wrap) = imf_field(&mut msg.imf, e, cs);
+                if keep {
+                    entries.push(MessageEntry::Imf {
+                        e,
+                        raw_body: RawInput::none(),
+                    });
+                } else if let Entry::Trace(idx) = e {
+                    dropped_trace.push(idx);
+                }
+                if wrap {
+                    entries.push(MessageEntry::Unstructured(downgraded_field(&original, cs)));
+                }
+                let kept = msg.imf.get_field(e);
+                ascii &= !(keep && kept.contains_utf8());
+            }
+            MessageEntry::MIME { e, raw_body } => {
+                let raw_body = if mime_before.get_field(e) == mime_after.get_field(e) {
+                    raw_body
+                } else {
+                    RawInput::none()
+                };
+                entries.push(MessageEntry::MIME { e, raw_body });
+            }
+            MessageEntry::Unstructured(mut u) => {
+                ascii &= unstructured_field(&mut u, cs);
+                entries.push(MessageEntry::Unstructured(u));
+            }
+            MessageEntry::Invalid(i) => {
+                ascii &= !i.contains_utf8();
+                entries.push(MessageEntry::Invalid(i));
+            }
+        }
+    }
+
+    // Remove the trace fields that were moved to `Downgraded-*` fields, and
+    // renumber the remaining ones.
+    dropped_trace.sort_unstable();
+    for idx in dropped_trace.into_iter().rev() {
+        msg.imf.trace.remove(idx);
+        for entry in entries.iter_mut() {
+            if let MessageEntry::Imf {
+                e: Entry::Trace(i), ..
+            } = entry
+            {
+                if *i > idx {
+                    *i -= 1
+                }
+            }
+        }
+    }
+    msg.entries = entries;
+    // Removing the Sender of a message with several authors makes its
+    // originator fields invalid (RFC 5322, 3.6.2).
+    if from_valid && matches!(msg.imf.from, imf::From::InvalidMissingSender { .. }) {
+        ascii = false
+    }
+    ascii
+}
+
+// Downgrades the header fields of a MIME part, and its body.
+fn part(part: &mut AnyPart, cs: Charset) -> bool {
+    let mime_before = part.mime_body.mime();
+    let mut ascii = body(&mut part.mime_body, cs);
+    let mime_after = part.mime_body.mime();
+
+    for entry in part.entries.iter_mut() {
+        match entry {
+            EntityEntry::MIME { e, raw_body } => {
+                if mime_before.get_field(*e) != mime_after.get_field(*e) {
+                    *raw_body = RawInput::none()
+                }
+            }
+            EntityEntry::Unstructured(u) => ascii &= unstructured_field(u, cs),
+            EntityEntry::Invalid(i) => ascii &= !i.contains_utf8(),
+        }
+    }
+    ascii
+}
+
+// Downgrades the MIME header fields of an entity, then the parts or message
+// it contains.
+fn body(body: &mut MimeBody, cs: Charset) -> bool {
+    let mut ascii = match body {
+        MimeBody::Mult(m) => {
+            parameters(&mut m.mime.ctype.other_params);
+            common_mime(&mut m.mime.fields, cs);
+            let mut ascii = true;
+            for child in m.children.iter_mut() {
+                ascii &= part(child, cs);
+            }
+            ascii
+        }
+        MimeBody::Msg(m) => {
+            parameters(&mut m.mime.ctype.params);
+            common_mime(&mut m.mime.fields, cs);
+            let ascii = message(&mut m.child, cs);
+            // message/rfc822 only allows the identity transfer encodings
+            // (RFC 2046, 5.2.1).
+            if matches!(m.mime.ctype.subtype, MessageSubtype::Global)
+                && matches!(
+                    m.mime.fields.transfer_encoding,
+                    Mechanism::_7Bit | Mechanism::_8Bit | Mechanism::Binary
+                )
+                && !m.child.contains_utf8_headers()
+            {
+                m.mime.ctype.subtype = MessageSubtype::RFC822;
+                m.raw_body = RawInput::none();
+            }
+            ascii
+        }
+        MimeBody::Txt(t) => {
+            parameters(&mut t.mime.ctype.other_params);
+            common_mime(&mut t.mime.fields, cs);
+            true
+        }
+        MimeBody::Bin(b) => {
+            parameters(&mut b.mime.ctype.ctype.params);
+            common_mime(&mut b.mime.fields, cs);
+            true
+        }
+    };
+    ascii &= !body.mime().contains_utf8();
+    ascii
+}
+
+fn common_mime(fields: &mut CommonMIME, cs: Charset) {
+    if let Some(desc) = &mut fields.description {
+        desc.encode_utf8(cs);
+    }
+}
+
+// Rewrites parameter values containing UTF-8 using the extended syntax of
+// RFC 2231, section 4: `name*=utf-8''%E2%82%AC`.
+fn parameters(params: &mut [mime::r#type::Parameter]) {
+    for param in params.iter_mut() {
+        param.comments.retain(|c| !c.contains_utf8());
+        if !param.value.contains_utf8() || param.name.0.ends_with(b"*") {
+            continue;
+        }
+        let mut value = b"utf-8''".to_vec();
+        let text: String = param.value.chars().collect();
+        for b in text.bytes() {
+            if is_mime_atom_text(b) && !matches!(b, b'*' | b'\'' | b'%') {
+                value.push(b)
+            } else {
+                value.extend(format!("%{:02X}", b).bytes())
+            }
+        }
+        param.name.0.to_mut().push(ascii::ASTERISK);
+        param.value = MIMEWord::Atom(MIMEAtom(Cow::Owned(value)));
+    }
+}
+
+// Downgrades the IMF field `e` in place. Returns whether the field is kept,
+// and whether its original version must be preserved in a `Downgraded-*`
+// field because some of its addresses or identifiers could not be converted
+// to ASCII and were removed.
+fn imf_field(imf: &mut imf::Imf, e: Entry, cs: Charset) -> (bool, bool) {
+    match e {
+        Entry::From | Entry::Sender => {
+            let (mut from, mut sender) = split_from(&imf.from);
+            let wrap = if matches!(e, Entry::From) {
+                let len = from.len();
+                from.retain_mut(|m| mailbox(m, cs));
+                from.len() != len
+            } else {
+                let wrap = sender.as_mut().is_some_and(|s| !mailbox(s, cs));
+                if wrap {
+                    sender = None;
+                }
+                wrap
+            };
+            let keep = if matches!(e, Entry::From) {
+                !from.is_empty()
+            } else {
+                sender.is_some()
+            };
+            imf.from = join_from(from, sender);
+            (keep, wrap)
+        }
+        Entry::ReplyTo => (true, !addresses(&mut imf.reply_to, cs)),
+        Entry::To => (true, !addresses(&mut imf.to, cs)),
+        Entry::Cc => (true, !addresses(&mut imf.cc, cs)),
+        Entry::Bcc => (true, !imf.bcc.as_mut().is_none_or(|a| addresses(a, cs))),
+        Entry::MessageID => {
+            imf.msg_id = None;
+            (false, true)
+        }
+        Entry::InReplyTo => {
+            imf.in_reply_to.retain(|id| !id.contains_utf8());
+            (!imf.in_reply_to.is_empty(), true)
+        }
+        Entry::References => {
+            imf.references.retain(|id| !id.contains_utf8());
+            (!imf.references.is_empty(), true)
+        }
+        Entry::Subject => {
+            if let Some(subject) = &mut imf.subject {
+                subject.encode_utf8(cs)
+            }
+            (true, false)
+        }
+        Entry::Comments(i) => {
+            imf.comments[i].encode_utf8(cs);
+            (true, false)
+        }
+        Entry::Keywords(i) => {
+            imf.keywords[i].0.iter_mut().for_each(|p| p.encode_utf8(cs));
+            (true, false)
+        }
+        Entry::Trace(i) => match &mut imf.trace[i] {
+            TraceField::Received(r) => {
+                r.encode_utf8(cs);
+                (true, false)
+            }
+            TraceField::ReturnPath(ReturnPath(Some(addr))) => {
+                let ascii = !addr.local_part.contains_utf8() && domain(&mut addr.domain);
+                (ascii, !ascii)
+            }
+            _ => (true, false),
+        },
+        Entry::ListId => {
+            if let Some(name) = imf.list_id.as_mut().and_then(|l| l.name.as_mut()) {
+                name.encode_utf8(cs)
+            }
+            (true, false)
+        }
+        _ => (true, false),
+    }
+}
+
+fn split_from<'a>(from: &imf::From<'a>) -> (Vec<MailboxRef<'a>>, Option<MailboxRef<'a>>) {
+    match from {
+        imf::From::Single { from, sender } => (vec![from.clone()], sender.clone()),
+        imf::From::Multiple { from, sender } => (from.0.clone(), Some(sender.clone())),
+        imf::From::InvalidMissingFrom { sender } => (vec![], sender.clone()),
+        imf::From::InvalidMissingSender { from } => (from.0.clone(), None),
+    }
+}
+
+fn join_from<'a>(mut from: Vec<MailboxRef<'a>>, sender: Option<MailboxRef<'a>>) -> imf::From<'a> {
+    match (from.len(), sender) {
+        (0, sender) => imf::From::InvalidMissingFrom { sender },
+        (1, sender) => imf::From::Single {
+            from: from.pop().unwrap(),
+            sender,
+        },
+        (_, Some(sender)) => imf::From::Multiple {
+            from: MailboxList(from),
+            sender,
+        },
+        (_, None) => imf::From::InvalidMissingSender {
+            from: MailboxList(from),
+        },
+    }
+}
+
+// Downgrades a list of addresses. A mailbox that cannot be converted is
+// replaced with an empty group, named after the original mailbox using
+// encoded words; it is removed if it was a member of a group. Returns whether
+// all the mailboxes could be converted.
+fn addresses(addrs: &mut Vec<AddressRef>, cs: Charset) -> bool {
+    let mut ascii = true;
+    for addr in addrs.iter_mut() {
+        match addr {
+            AddressRef::Single(mbox) => {
+                let original = mbox.clone();
+                if !mailbox(mbox, cs) {
+                    let text = String::from_utf8(print_to_vec(FMT_NOFOLD, original)).unwrap();
+                    *addr = AddressRef::Many(GroupRef {
+                        name: Phrase(vec![PhraseToken::Encoded(EncodedWord::encode_or_utf8(
+                            text.trim(),
+                            cs,
+                        ))]),
+                        participants: None,
+                        raw: RawInput::none(),
+                    });
+                    ascii = false;
+                }
+            }
+            AddressRef::Many(group) => {
+                group.name.encode_utf8(cs);
+                if let Some(list) = &mut group.participants {
+                    let len = list.0.len();
+                    list.0.retain_mut(|m| mailbox(m, cs));
+                    ascii &= list.0.len() == len;
+                    if list.0.is_empty() {
+                        group.participants = None
+                    }
+                }
+            }
+        }
+    }
+    ascii
+}
+
+// Rewrites the display name of a mailbox using encoded words and its domain
+// using A-labels. Returns whether the mailbox is now ASCII; this is not
+// possible if its local part contains UTF-8.
+fn mailbox(mbox: &mut MailboxRef, cs: Charset) -> bool {
+    if let Some(name) = &mut mbox.name {
+        name.encode_utf8(cs)
+    }
+    mbox.comments.retain(|c| !c.contains_utf8());
+    !mbox.addrspec.local_part.contains_utf8() && domain(&mut mbox.addrspec.domain)
+}
+
+// Converts an internationalized domain name to A-labels (RFC 5890).
+fn domain(domain: &mut Domain) -> bool {
+    let Domain::Atoms(atoms) = domain else {
+        return !domain.contains_utf8();
+    };
+    if !atoms.contains_utf8() {
+        return true;
+    }
+    let name = atoms
+        .iter()
+        .map(|a| a.0.as_ref())
+        .collect::<Vec<_>>()
+        .join(".");
+    match idna::domain_to_ascii_strict(&name) {
+        Ok(name) => {
+            *atoms = name
+                .split('.')
+                .map(|label| Atom(Cow::Owned(label.to_string())))
+                .collect();
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+// Downgrades a field that is not parsed by this crate. Returns whether it is
+// now ASCII. Encoded words are only allowed in unstructured text, so the
+// parameters of Content-Disposition (RFC 2183) are rewritten following
+// RFC 2231, and the other `Content-*` fields, which are structured, are left
+// unchanged.
+fn unstructured_field(f: &mut header::Unstructured, cs: Charset) -> bool {
+    if !f.body.contains_utf8() {
+        return true;
+    }
+    let name = f.name.bytes();
+    if name.eq_ignore_ascii_case(b"Content-Disposition") {
+        let printed = print_to_vec(FMT_NOFOLD, &f.body);
+        let split = printed
+            .iter()
+            .position(|c| *c == b';')
+            .unwrap_or(printed.len());
+        let (kind, rest) = printed.split_at(split);
+        let Ok((_, mut params)) = mime::r#type::parameter_list(rest) else {
+            return false;
+        };
+        parameters(&mut params);
+        if !kind.is_ascii() || params.contains_utf8() {
+            return false;
+        }
+        let mut text = format!(" {}", String::from_utf8_lossy(kind.trim_ascii()));
+        for param in params {
+            text.push_str("; ");
+            text.push_str(&String::from_utf8_lossy(&print_to_vec(FMT_NOFOLD, param)));
+        }
+        f.body = unstructured_text(&text);
+    } else if name.len() >= 8 && name[..8].eq_ignore_ascii_case(b"Content-") {
+        return false;
+    } else {
+        f.body.encode_utf8(cs);
+    }
+    f.raw_body = RawInput::none();
+    true
+}
+
+// Splits `text` into the tokens of an unstructured field body. Words that are
+// encoded words are kept as such, so that `unstructured` keeps the whitespace
+// separating them from the encoded words it creates.
+fn unstructured_text(text: &str) -> misc_token::Unstructured<'static> {
+    let mut toks = vec![];
+    let mut rest = text;
+    while !rest.is_empty() {
+        let n = rest.find(|c| c != ' ' && c != '\t').unwrap_or(rest.len());
+        if n > 0 {
+            toks.push(UnstrToken::Plain(
+                rest[..n].to_string().into(),
+                UnstrTxtKind::Fws,
+            ));
+        }
+        rest = &rest[n..];
+        let n = rest.find([' ', '\t']).unwrap_or(rest.len());
+        if n > 0 {
+            let word = &rest[..n];
+            toks.push(
+                match all_consuming(encoded_word_plain(Context::Unstructured))(word.as_bytes()) {
+                    Ok((_, e)) => UnstrToken::Encoded(e.to_static()),
+                    Err(_) => UnstrToken::Plain(word.to_string().into(), UnstrTxtKind::Txt),
+                },
+            );
+        }
+        rest = &rest[n..];
+    }
+    misc_token::Unstructured(toks)
+}
+
+// Builds the `Downgraded-*` field preserving the original version of an IMF
+// field (RFC 6857), with its UTF-8 text as encoded words.
+fn downgraded_field(f: &imf::field::Field, cs: Charset) -> header::Unstructured<'static> {
+    let mut name = b"Downgraded-".to_vec();
+    name.extend_from_slice(f.raw_name().bytes());
+    let printed = String::from_utf8_lossy(&print_to_vec(FMT_NOFOLD, f.clone())).into_owned();
+    let body = printed.split_once(':').map_or("", |(_, body)| body);
+    let mut body = unstructured_text(body.trim_end());
+    body.encode_utf8(cs);
+    header::Unstructured {
+        name: header::FieldName(name.into()),
+        body,
+        raw_body: RawInput::none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::message;
+    use crate::print::tests::print_to_vec;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_downgrade() {
+        let txt = "From: Jöhn Smîth <john@bücher.example>\r
+To: \"Ivan\" <иван@example.com>, bob@example.com,\r
+ Team: карл@example.com, dave@example.com;\r
+Subject: Привет мир =?utf-8?q?x?= héllo\r
+Message-ID: <abc@example.com>\r
+X-Note: déjà vu\r
+MIME-Version: 1.0\r
+Content-Type: multipart/mixed; boundary=frontier\r
+\r
+--frontier\r
+Content-Type: text/plain; name=\"blåbær.txt\"\r
+Content-Description: résumé\r
+\r
+hello\r
+--frontier\r
+Content-Type: message/global\r
+\r
+From: Zoë <zoe@example.com>\r
+Subject: ça va\r
+\r
+body\r
+--frontier--\r
+";
+        let mut msg = message(txt.as_bytes());
+        assert!(msg.contains_utf8_headers());
+        assert!(msg.downgrade());
+        assert!(!msg.contains_utf8_headers());
+        assert_eq!(
+            String::from_utf8(print_to_vec(&msg)).unwrap(),
+            "From: =?UTF-8?B?SsO2aG4gU23DrnRo?= <john@xn--bcher-kva.example>\r
+To: =?UTF-8?B?Ikl2YW4iIDzQuNCy0LDQvUBleGFtcGxlLmNvbT4=?=:;, bob@example.com,\r
+ Team:dave@example.com;\r
+Downgraded-To: \"Ivan\" =?UTF-8?B?PNC40LLQsNC9QGV4YW1wbGUuY29tPiw=?=\r
+ bob@example.com, =?UTF-8?B?VGVhbTrQutCw0YDQu0BleGFtcGxlLmNvbSw=?=\r
+ dave@example.com;\r
+Subject: =?UTF-8?B?0J/RgNC40LLQtdGCINC80LjRgCA=?= =?UTF-8?Q?x?=\r
+ =?UTF-8?Q?_h=C3=A9llo?=\r
+Message-ID: <abc@example.com>\r
+X-Note: =?UTF-8?B?ZMOpasOg?= vu\r
+MIME-Version: 1.0\r
+Content-Type: multipart/mixed;\r
+ boundary=\"V1Qy0rpB5tWE76WF3UelfGW5K9LZpjHjZ3PKE26vpVNnvofq7BLuYTWxzQB3HrYu7\"\r
+\r
+--V1Qy0rpB5tWE76WF3UelfGW5K9LZpjHjZ3PKE26vpVNnvofq7BLuYTWxzQB3HrYu7\r
+Content-Type: text/plain; charset=us-ascii; name*=utf-8''bl%C3%A5b%C3%A6r.txt\r
+Content-Description: =?UTF-8?B?csOpc3Vtw6k=?=\r
+\r
+hello\r
+--V1Qy0rpB5tWE76WF3UelfGW5K9LZpjHjZ3PKE26vpVNnvofq7BLuYTWxzQB3HrYu7\r
+Content-Type: message/rfc822\r
+\r
+From: =?UTF-8?Q?Zo=C3=AB?= <zoe@example.com>\r
+Subject: =?UTF-8?B?w6dh?= va\r
+MIME-Version: 1.0\r
+\r
+body\r
+--V1Qy0rpB5tWE76WF3UelfGW5K9LZpjHjZ3PKE26vpVNnvofq7BLuYTWxzQB3HrYu7--\r
+"
+        );
+    }
+
+    #[test]
+    fn test_downgrade_originator() {
+        let txt = "Return-Path: <почта@example.com>\r
+Received: from a.example by b.example; Mon, 1 Jan 2024 00:00:00 +0000\r
+From: <почта@example.com>\r
+Sender: bob@example.com\r
+\r
+";
+        let mut msg = message(txt.as_bytes());
+        assert!(msg.downgrade());
+        assert_eq!(msg.imf.trace.len(), 1);
+        assert!(matches!(msg.imf.trace[0], TraceField::Received(_)));
+        assert!(matches!(
+            msg.imf.from,
+            imf::From::InvalidMissingFrom { sender: Some(_) }
+        ));
+        assert_eq!(
+            String::from_utf8(print_to_vec(&msg)).unwrap(),
+            "Downgraded-Return-Path: =?UTF-8?B?PNC/0L7Rh9GC0LBAZXhhbXBsZS5jb20+?=\r
+Received: from a.example by b.example; Mon, 1 Jan 2024 00:00:00 +0000\r
+Downgraded-From: =?UTF-8?B?0L/QvtGH0YLQsEBleGFtcGxlLmNvbQ==?=\r
+Sender: bob@example.com\r
+MIME-Version: 1.0\r
+\r
+"
+        );
+
+        // a local part can not be converted, but a domain can
+        let mut msg = message("From: bob@пример.рф\r\n\r\n".as_bytes());
+        assert!(msg.downgrade());
+        assert_eq!(
+            String::from_utf8(print_to_vec(&msg)).unwrap(),
+            "From: bob@xn--e1afmkfd.xn--p1ai\r\nMIME-Version: 1.0\r\n\r\n"
+        );
+
+        // the Sender of a message with several authors cannot be removed
+        let txt = "From: alice@example.com, bob@example.com\r
+Sender: <почта@example.com>\r
+\r
+";
+        let mut msg = message(txt.as_bytes());
+        assert!(!msg.downgrade());
+        assert!(matches!(
+            msg.imf.from,
+            imf::From::InvalidMissingSender { .. }
+        ));
+    }
+
+    #[test]
+    fn test_downgrade_unstructured() {
+        let txt = "From: bob@example.com\r
+X-Note: =?utf-8?q?d=C3=A9j=C3=A0?= vu, été\r
+Content-Disposition: attachment; filename=\"файл.txt\"; size=42\r
+\r
+";
+        let mut msg = message(txt.as_bytes());
+        assert!(msg.downgrade());
+        assert_eq!(
+            String::from_utf8(print_to_vec(&msg)).unwrap(),
+            "From: bob@example.com\r
+X-Note: =?UTF-8?Q?d=C3=A9j=C3=A0?= vu, =?UTF-8?B?w6l0w6k=?=\r
+Content-Disposition: attachment;\r
+ filename*=utf-8''%D1%84%D0%B0%D0%B9%D0%BB.txt; size=42\r
+MIME-Version: 1.0\r
+\r
+"
+        );
+
+        // other structured fields cannot contain encoded words
+        let txt = "From: bob@example.com\r
+Content-Location: http://пример.рф/\r
+\r
+";
+        let mut msg = message(txt.as_bytes());
+        assert!(!msg.downgrade());
+        assert!(String::from_utf8(print_to_vec(&msg))
+            .unwrap()
+            .contains("Content-Location: http://пример.рф/\r\n"));
+    }
+
+    #[test]
+    fn test_downgrade_charset() {
+        let txt = "From: 山田 <yamada@example.com>\r
+Subject: こんにちは Zoë\r
+\r
+";
+        let mut msg = message(txt.as_bytes());
+        assert!(msg.downgrade_with_charset(encoding_rs::ISO_2022_JP));
+        let printed = String::from_utf8(print_to_vec(&msg)).unwrap();
+        // "Zoë" cannot be represented in ISO-2022-JP
+        assert_eq!(
+            printed,
+            "From: =?ISO-2022-JP?B?GyRCOzNFRBsoQg==?= <yamada@example.com>\r
+Subject: =?UTF-8?B?44GT44KT44Gr44Gh44GvIFpvw6s=?=\r
+MIME-Version: 1.0\r
+\r
+"
+        );
+
+        // the same encoded words are produced at printing time
+        let msg = message(txt.as_bytes());
+        let cfg = crate::print::FMT_DEFAULT
+            .with_seed(Some(0))
+            .with_encoded_word_charset(Some(encoding_rs::ISO_2022_JP));
+        assert_eq!(
+            String::from_utf8(crate::print::print_to_vec(cfg, &msg)).unwrap(),
+            printed
+        );
+    }
+}
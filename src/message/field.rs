@@ -2,6 +2,7 @@ use bounded_static::ToStatic;
 #[cfg(feature = "tracing")]
 use tracing::warn;
 
+use crate::diagnostics::{self, DiagnosticKind, Severity};
 #[cfg(feature = "arbitrary")]
 use crate::fuzz_eq::FuzzEq;
 use crate::print::{Formatter, Print};
@@ -11,7 +12,8 @@ use crate::{header, imf, mime};
 /// Header field of a toplevel message.
 /// Is either an Imf field (RFC 5322),
 /// MIME-defined fields (RFC 2045),
-/// or an unstructured field.
+/// an unstructured field,
+/// or a field whose body could not be parsed.
 #[derive(Clone, Debug, PartialEq, ToStatic)]
 #[cfg_attr(feature = "arbitrary", derive(FuzzEq))]
 pub enum MessageField<'a> {
@@ -25,6 +27,7 @@ pub enum MessageField<'a> {
     },
     // invariant: has a field name that is different from IMF or MIME headers.
     Unstructured(header::Unstructured<'a>),
+    Invalid(header::Invalid<'a>),
 }
 
 impl<'a> MessageField<'a> {
@@ -33,6 +36,7 @@ impl<'a> MessageField<'a> {
             MessageField::MIME { f, .. } => f.raw_name(),
             MessageField::Imf { f, .. } => f.raw_name(),
             MessageField::Unstructured(u) => u.name.clone(),
+            MessageField::Invalid(i) => i.name.clone(),
         }
     }
 
@@ -41,6 +45,7 @@ impl<'a> MessageField<'a> {
             MessageField::MIME { raw_body, .. } => raw_body.clone(),
             MessageField::Imf { raw_body, .. } => raw_body.clone(),
             MessageField::Unstructured(u) => u.raw_body.clone(),
+            MessageField::Invalid(i) => i.raw_body.clone(),
         }
     }
 }
@@ -51,6 +56,7 @@ impl<'a> Print for MessageField<'a> {
             MessageField::MIME { f, .. } => f.print(fmt),
             MessageField::Imf { f, .. } => f.print(fmt),
             MessageField::Unstructured(u) => u.print(fmt),
+            MessageField::Invalid(i) => i.print(fmt),
         }
     }
 }
@@ -69,6 +75,7 @@ pub enum MessageEntry<'a> {
     },
     // invariant: has a field name that is different from IMF or MIME headers.
     Unstructured(header::Unstructured<'a>),
+    Invalid(header::Invalid<'a>),
 }
 
 /// Collects fields and entries for a toplevel message. Only for eml-codec's
@@ -78,36 +85,92 @@ pub(crate) struct NaiveMessageFields<'a> {
     pub mime: mime::NaiveMIME<'a>,
     pub imf: imf::Imf<'a>,
     pub entries: Vec<MessageEntry<'a>>,
+    pub duplicates: Vec<header::DuplicateField<'a>>,
 }
 
 impl<'a> FromIterator<header::FieldRaw<'a>> for NaiveMessageFields<'a> {
+    fn from_iter<I: IntoIterator<Item = header::FieldRaw<'a>>>(it: I) -> Self {
+        Self::new(it, header::DuplicatePolicy::default())
+    }
+}
+
+impl<'a> NaiveMessageFields<'a> {
     #[cfg_attr(
         feature = "tracing",
-        tracing::instrument(name = "MessageFields::from_iter", skip(it))
+        tracing::instrument(name = "MessageFields::new", skip(it))
     )]
-    fn from_iter<I: IntoIterator<Item = header::FieldRaw<'a>>>(it: I) -> Self {
-        let mut mime = mime::NaiveMIME::default();
-        let mut imf = imf::PartialImf::default();
-        let mut entries = vec![];
+    pub fn new<I: IntoIterator<Item = header::FieldRaw<'a>>>(
+        it: I,
+        policy: header::DuplicatePolicy,
+    ) -> Self {
+        let mut mime = mime::NaiveMIME::new(policy);
+        let mut imf = imf::PartialImf::new(policy);
+        // entries, along with the field they come from
+        let mut entries: Vec<(MessageEntry<'a>, header::DuplicateField<'a>)> = vec![];
+        let mut duplicates = vec![];
         for f in it {
+            let _field = diagnostics::enter_field(&f);
             match mime::field::NaiveField::try_from(&f) {
                 Ok(mimef) => {
-                    if let Some(entry) = mime.add_field(mimef) {
-                        entries.push(MessageEntry::MIME {
-                            e: entry,
-                            raw_body: f.body.into(),
-                        })
-                    } else {
-                        // otherwise drop the field
-                        #[cfg(feature = "tracing-recover")]
-                        warn!(field = ?f, "dropping conflicting MIME field")
+                    match mime.add_field(mimef) {
+                        Ok(entry) => {
+                            // with `KeepLast`, this field replaces an earlier one
+                            header::discard_entry(
+                                &mut entries,
+                                &mut duplicates,
+                                |e| matches!(e, MessageEntry::MIME { e, .. } if *e == entry),
+                            );
+                            mime.set_source(entry, (&f).into());
+                            entries.push((
+                                MessageEntry::MIME {
+                                    e: entry,
+                                    raw_body: f.body.into(),
+                                },
+                                (&f).into(),
+                            ))
+                        }
+                        Err(mime::AddFieldErr::Duplicate) => {
+                            diagnostics::diagnose!(
+                                "tracing-recover",
+                                (field = ?f, "discarding duplicate MIME field"),
+                                DiagnosticKind::DuplicateField,
+                                Severity::Warning,
+                                None,
+                            );
+                            duplicates.push((&f).into());
+                        }
+                        Err(mime::AddFieldErr::Rejected(entry)) => {
+                            diagnostics::diagnose!(
+                                "tracing-recover",
+                                (field = ?f, "rejecting duplicated MIME field"),
+                                DiagnosticKind::DuplicateField,
+                                Severity::Warning,
+                                None,
+                            );
+                            header::discard_entry(
+                                &mut entries,
+                                &mut duplicates,
+                                |e| matches!(e, MessageEntry::MIME { e, .. } if *e == entry),
+                            );
+                            duplicates.push((&f).into());
+                        }
                     }
                     continue;
                 }
                 Err(mime::field::InvalidField::Body) => {
-                    // this is a MIME field but its body is invalid; drop it.
-                    #[cfg(feature = "tracing-unsupported")]
-                    warn!(field = ?f, "dropping MIME field with an invalid body");
+                    // this is a MIME field but its body is invalid; keep it
+                    // as an invalid field.
+                    diagnostics::diagnose!(
+                        "tracing-unsupported",
+                        (field = ?f, "MIME field with an invalid body"),
+                        DiagnosticKind::InvalidField,
+                        Severity::Error,
+                        None,
+                    );
+                    entries.push((
+                        MessageEntry::Invalid(header::Invalid::from_raw(&f)),
+                        (&f).into(),
+                    ));
                     continue;
                 }
                 Err(mime::field::InvalidField::Name) => {
@@ -118,32 +181,104 @@ impl<'a> FromIterator<header::FieldRaw<'a>> for NaiveMessageFields<'a> {
             match imf::field::Field::try_from(&f) {
                 Ok(imff) => {
                     match imf.add_field(imff) {
-                        Ok(entry) => entries.push(MessageEntry::Imf {
-                            e: entry,
-                            raw_body: f.body.into(),
-                        }),
+                        Ok(entry) => {
+                            // with `KeepLast`, this field replaces an earlier one
+                            header::discard_entry(
+                                &mut entries,
+                                &mut duplicates,
+                                |e| matches!(e, MessageEntry::Imf { e, .. } if *e == entry),
+                            );
+                            entries.push((
+                                MessageEntry::Imf {
+                                    e: entry,
+                                    raw_body: f.body.into(),
+                                },
+                                (&f).into(),
+                            ))
+                        }
                         Err(imf::AddFieldErr::NoEntry) => {
-                            #[cfg(feature = "tracing-recover")]
-                            warn!(field = ?f, "no new entry for IMF field");
+                            diagnostics::diagnose!(
+                                "tracing-recover",
+                                (field = ?f, "merging repeated IMF field"),
+                                DiagnosticKind::MergedField,
+                                Severity::Warning,
+                                None,
+                            );
                         }
                         Err(imf::AddFieldErr::Conflict) => {
-                            #[cfg(feature = "tracing-recover")]
-                            warn!(field = ?f, "discarding conflicting IMF field");
+                            // keep the field as an invalid field so that it
+                            // is printed back.
+                            diagnostics::diagnose!(
+                                "tracing-recover",
+                                (field = ?f, "misplaced IMF field"),
+                                DiagnosticKind::MisplacedField,
+                                Severity::Warning,
+                                None,
+                            );
+                            entries.push((
+                                MessageEntry::Invalid(header::Invalid::from_raw(&f)),
+                                (&f).into(),
+                            ));
+                        }
+                        Err(imf::AddFieldErr::Duplicate) => {
+                            diagnostics::diagnose!(
+                                "tracing-recover",
+                                (field = ?f, "discarding duplicate IMF field"),
+                                DiagnosticKind::DuplicateField,
+                                Severity::Warning,
+                                None,
+                            );
+                            duplicates.push((&f).into());
+                        }
+                        Err(imf::AddFieldErr::Rejected(entry)) => {
+                            diagnostics::diagnose!(
+                                "tracing-recover",
+                                (field = ?f, "rejecting duplicated IMF field"),
+                                DiagnosticKind::DuplicateField,
+                                Severity::Warning,
+                                None,
+                            );
+                            header::discard_entry(
+                                &mut entries,
+                                &mut duplicates,
+                                |e| matches!(e, MessageEntry::Imf { e, .. } if *e == entry),
+                            );
+                            duplicates.push((&f).into());
                         }
                     }
                     continue;
                 }
                 Err(imf::field::InvalidField::NeedsDiscard) => {
                     // this is an IMF field for which we recognized the body, but the
-                    // body isn't RFC compliant and the fields needs to be dropped.
-                    #[cfg(feature = "tracing-recover")]
-                    warn!(field = ?f, "dropping IMF field with a body to be discarded");
+                    // body isn't RFC compliant and cannot be part of the IMF AST;
+                    // keep it as an invalid field.
+                    diagnostics::diagnose!(
+                        "tracing-recover",
+                        (field = ?f, "IMF field with a body to be discarded"),
+                        DiagnosticKind::InvalidField,
+                        Severity::Warning,
+                        None,
+                    );
+                    entries.push((
+                        MessageEntry::Invalid(header::Invalid::from_raw(&f)),
+                        (&f).into(),
+                    ));
                     continue;
                 }
                 Err(imf::field::InvalidField::Body) => {
-                    // this is an IMF field but its body is invalid; drop it.
-                    #[cfg(feature = "tracing-unsupported")]
-                    warn!(field = ?f, "dropping IMF field with an invalid body");
+                    // this is an IMF field but its body is invalid; keep it as
+                    // an invalid field.
+                    diagnostics::diagnose!(
+                        "tracing-unsupported",
+                        (field = ?f, "IMF field with an invalid body"),
+                        DiagnosticKind::InvalidField,
+                        Severity::Error,
+                        None,
+                    );
+                    entries.push((
+                        MessageEntry::Invalid(header::Invalid::from_raw(&f)),
+                        (&f).into(),
+                    ));
                     continue;
                 }
                 Err(imf::field::InvalidField::Name) => {
@@ -152,18 +287,28 @@ impl<'a> FromIterator<header::FieldRaw<'a>> for NaiveMessageFields<'a> {
             }
 
             if let Some(u) = header::Unstructured::from_raw(&f) {
-                entries.push(MessageEntry::Unstructured(u));
+                entries.push((MessageEntry::Unstructured(u), (&f).into()));
             } else {
-                // otherwise drop the field
-                #[cfg(feature = "tracing-unsupported")]
-                warn!(field = ?f, "dropping field that cannot be parsed as unstructured")
+                // otherwise keep it as an invalid field
+                diagnostics::diagnose!(
+                    "tracing-unsupported",
+                    (field = ?f, "field that cannot be parsed as unstructured"),
+                    DiagnosticKind::InvalidField,
+                    Severity::Error,
+                    None,
+                );
+                entries.push((
+                    MessageEntry::Invalid(header::Invalid::from_raw(&f)),
+                    (&f).into(),
+                ));
             }
         }
 
         NaiveMessageFields {
             mime,
             imf: imf.to_imf(),
-            entries,
+            entries: entries.into_iter().map(|(e, _)| e).collect(),
+            duplicates,
         }
     }
 }
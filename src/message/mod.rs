@@ -1,10 +1,14 @@
 /// Representation of all headers in a toplevel message
 pub mod field;
 
+mod downgrade;
+
 #[cfg(feature = "arbitrary")]
 use arbitrary::Arbitrary;
 use bounded_static::ToStatic;
+use std::ops::Range;
 
+use crate::diagnostics;
 use crate::header;
 use crate::i18n::ContainsUtf8;
 use crate::imf;
@@ -13,6 +17,7 @@ use crate::mime;
 use crate::part;
 use crate::print::{print_seq, Formatter, Print};
 use crate::raw_input::RawInput;
+use crate::text::{self, charset::EmailCharset};
 #[cfg(feature = "arbitrary")]
 use crate::{
     arbitrary_utils::{arbitrary_shuffle, arbitrary_vec_where},
@@ -36,6 +41,10 @@ pub struct Message<'a> {
     pub imf: imf::Imf<'a>,
     pub mime_body: part::MimeBody<'a>,
     pub entries: Vec<MessageEntry<'a>>,
+    // Header fields discarded by the `DuplicatePolicy` used when parsing. They
+    // are not printed.
+    #[cfg_attr(feature = "arbitrary", fuzz_eq(ignore))]
+    pub duplicates: Vec<header::DuplicateField<'a>>,
     pub raw: RawInput<'a>,
     pub raw_headers: RawInput<'a>,
 }
@@ -46,6 +55,7 @@ impl<'a> Message<'a> {
             .iter()
             .find(|f| match f {
                 field::MessageEntry::Unstructured(u) => u.contains_utf8(),
+                field::MessageEntry::Invalid(i) => i.contains_utf8(),
                 _ => false,
             })
             .is_some()
@@ -53,6 +63,90 @@ impl<'a> Message<'a> {
             || self.mime_body.mime().contains_utf8()
     }
 
+    /// Downgrade this message so that it can be sent to a server that does
+    /// not support the SMTPUTF8 extension (RFC 6857), in place. This applies
+    /// to this message and to the parts and messages it contains:
+    ///
+    /// - UTF-8 text in display names, unstructured fields (Subject, Received,
+    ///   Content-Description, unknown fields...) and keywords is rewritten as
+    ///   encoded words;
+    /// - internationalized domain names are converted to A-labels;
+    /// - MIME parameters containing UTF-8, including those of
+    ///   Content-Disposition, are rewritten following RFC 2231;
+    /// - addresses and message identifiers that cannot be converted (because
+    ///   their local part contains UTF-8) are removed from their field, and
+    ///   the original field is kept as a `Downgraded-*` field (e.g.
+    ///   `Downgraded-To`). In address lists, such a mailbox is replaced with
+    ///   an empty group named after it;
+    /// - embedded message/global messages are converted to message/rfc822
+    ///   once their header section is ASCII.
+    ///
+    /// Removing an address may leave the message without a From or Sender
+    /// field. Returns whether the header sections of the message and of its
+    /// parts are ASCII and valid afterwards; this is not the case if they
+    /// contain invalid fields or other structured `Content-*` fields with
+    /// UTF-8, or if the Sender of a message with several From addresses was
+    /// removed, for instance.
+    ///
+    /// Encoded words use the UTF-8 charset; see `downgrade_with_charset`.
+    pub fn downgrade(&mut self) -> bool {
+        downgrade::message(self, encoding_rs::UTF_8)
+    }
+
+    /// Same as `downgrade`, with encoded words in `charset` (e.g.
+    /// ISO-2022-JP) when it can represent their text, and in UTF-8 otherwise.
+    /// See `EncodedWord::encode`.
+    pub fn downgrade_with_charset(&mut self, charset: &'static encoding_rs::Encoding) -> bool {
+        downgrade::message(self, charset)
+    }
+
+    /// Add a new ARC set at the top of the trace section (RFC 8617, section
+    /// 5.1). The instance number of the three header fields must be the one
+    /// returned by `arc::next_instance`.
+    pub fn prepend_arc_set(
+        &mut self,
+        seal: imf::arc::Seal<'a>,
+        message_signature: imf::arc::MessageSignature<'a>,
+        authentication_results: imf::arc::AuthenticationResults<'a>,
+    ) -> Result<(), imf::arc::ChainError> {
+        let instance = imf::arc::next_instance(&self.imf.trace);
+        for i in [
+            seal.instance,
+            message_signature.instance,
+            authentication_results.instance,
+        ] {
+            if i != instance || i > imf::arc::MAX_INSTANCE {
+                return Err(imf::arc::ChainError::InvalidInstance(i));
+            }
+        }
+
+        let set = [
+            imf::TraceField::ArcSeal(seal),
+            imf::TraceField::ArcMessageSignature(message_signature),
+            imf::TraceField::ArcAuthenticationResults(authentication_results),
+        ];
+        let len = set.len();
+        self.imf.trace.splice(0..0, set);
+        // Shift the existing trace entries to keep them in order.
+        for ent in self.entries.iter_mut() {
+            if let MessageEntry::Imf {
+                e: imf::field::Entry::Trace(idx),
+                ..
+            } = ent
+            {
+                *idx += len
+            }
+        }
+        self.entries.splice(
+            0..0,
+            (0..len).map(|idx| MessageEntry::Imf {
+                e: imf::field::Entry::Trace(idx),
+                raw_body: RawInput::none(),
+            }),
+        );
+        Ok(())
+    }
+
     // TODO: return an iterator instead of a Vec?
     pub fn field_list(&self) -> Vec<MessageField<'a>> {
         let mime = self.mime_body.mime();
@@ -70,17 +164,116 @@ impl<'a> Message<'a> {
                     raw_body: raw_body.clone(),
                 },
                 MessageEntry::Unstructured(u) => MessageField::Unstructured(u.clone()),
+                MessageEntry::Invalid(i) => MessageField::Invalid(i.clone()),
             };
             v.push(field);
         }
         v
     }
+
+    /// Byte offsets of `raw` relative to the start of the input this message
+    /// was parsed from.
+    ///
+    /// `raw` can be any raw input found in this message: a header field body,
+    /// an address or mailbox (`AddressRef::raw`, `MailboxRef::raw`), a part
+    /// (`AnyPart::raw`), its header section or body, and so on, including
+    /// those of nested parts and messages. Returns `None` if the
+    /// message or `raw` do not come from parsing (e.g. if the message was
+    /// built programmatically or converted to an owned version).
+    pub fn span(&self, raw: &RawInput) -> Option<Range<usize>> {
+        raw.span_in(self.raw.0?)
+    }
+
+    /// Byte offsets of the header section of the message, including the empty
+    /// line that ends it.
+    pub fn header_span(&self) -> Option<Range<usize>> {
+        self.span(&self.raw_headers)
+    }
+
+    /// Byte offsets of the body of the message.
+    pub fn body_span(&self) -> Option<Range<usize>> {
+        self.span(&self.mime_body.raw_body())
+    }
+
+    /// Byte offsets of a complete header field (name, colon and body, without
+    /// its final line terminator), given the raw input of its body as
+    /// returned by e.g. `MessageField::raw_body`.
+    pub fn field_span(&self, raw_body: &RawInput) -> Option<Range<usize>> {
+        header::field_span(self.raw.0?, raw_body)
+    }
+
+    /// Prints this message, an edited version of `original`, only
+    /// re-serializing what was modified.
+    ///
+    /// Header fields, parts and bodies that are unchanged compared to
+    /// `original` are copied verbatim from the input `original` was parsed
+    /// from; this keeps e.g. DKIM signatures over untouched header fields
+    /// valid. A multipart keeps its boundary unless its `Content-Type` field
+    /// was modified. Unlike `print`, a `MIME-Version` field is not added if it
+    /// is missing.
+    ///
+    /// If `original` was not obtained by parsing (or was converted to an owned
+    /// version), this is the same as `print`.
+    pub fn print_diff(&self, original: &Self, fmt: &mut impl Formatter) {
+        match original.raw.0 {
+            Some(input) => self.print_diff_in(fmt, input, original),
+            None => self.print(fmt),
+        }
+    }
+
+    // `print_diff`, where `input` is the input of the toplevel message
+    pub(crate) fn print_diff_in(&self, fmt: &mut impl Formatter, input: &[u8], original: &Self) {
+        if self == original && !self.mime_body.has_binary_part() {
+            if let Some(span) = self.raw.span_in(input) {
+                fmt.write_bytes(&input[span]);
+                return;
+            }
+        }
+        let type_printed = part::print_headers_diff(
+            fmt,
+            input,
+            &self.field_list(),
+            &original.field_list(),
+            (self.duplicates == original.duplicates).then_some(&self.raw_headers),
+            |f| f.raw_body(),
+            |f| {
+                matches!(
+                    f,
+                    MessageField::MIME {
+                        f: mime::field::Field::Type(_),
+                        ..
+                    }
+                )
+            },
+        );
+        self.mime_body
+            .print_body_diff(fmt, input, &original.mime_body, type_printed);
+    }
 }
 
 impl<'a> Print for Message<'a> {
     fn print(&self, fmt: &mut impl Formatter) {
+        let reencoded = self.mime_body.reencode_long_lines(fmt);
+        let mut fields = self.field_list();
+        if reencoded.is_some() {
+            fields.retain(|f| {
+                !matches!(
+                    f,
+                    MessageField::MIME {
+                        f: mime::field::Field::TransferEncoding(_),
+                        ..
+                    }
+                )
+            });
+            fields.push(MessageField::MIME {
+                f: mime::field::Field::TransferEncoding(
+                    mime::mechanism::Mechanism::QuotedPrintable,
+                ),
+                raw_body: RawInput::none(),
+            });
+        }
         fmt.begin_line_folding();
-        print_seq(fmt, &self.field_list(), |_| ());
+        print_seq(fmt, &fields, |_| ());
         if self.imf.mime_version.is_none() {
             // The RFC requires that an implementation that obeys the MIME RFC
             // always outputs a MIME-Version header. We do this at printing time
@@ -90,7 +283,62 @@ impl<'a> Print for Message<'a> {
         }
         fmt.end_line_folding();
         fmt.write_crlf();
-        self.mime_body.print_body(fmt);
+        match reencoded {
+            Some(body) => fmt.write_bytes(&body),
+            None => self.mime_body.print_body(fmt),
+        }
+    }
+}
+
+/// A toplevel message parsed in "lossless" mode.
+///
+/// The regular printer normalizes the message it prints: header fields are
+/// refolded, comments and obsolete syntax are dropped, duplicate and malformed
+/// header lines are not reproduced, multipart boundaries are regenerated, etc.
+/// A `LosslessMessage` instead keeps the concrete syntax of its input: printing
+/// it yields exactly the bytes it was parsed from, whitespace, folding,
+/// comments, duplicates and invalid lines included.
+///
+/// The typed view of the message is available through `Deref`. It can also be
+/// edited using `message_mut`; printing then only re-serializes the modified
+/// nodes (see `Message::print_diff`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct LosslessMessage<'a> {
+    input: &'a [u8],
+    message: Message<'a>,
+    // the message as parsed, to track modifications
+    original: Message<'a>,
+}
+
+impl<'a> LosslessMessage<'a> {
+    /// The bytes the message was parsed from.
+    pub fn raw(&self) -> &'a [u8] {
+        self.input
+    }
+
+    /// Gives mutable access to the typed view of the message. Modifications
+    /// are tracked by comparison with the message as parsed.
+    pub fn message_mut(&mut self) -> &mut Message<'a> {
+        &mut self.message
+    }
+
+    /// Returns the typed view of the message, giving up on its concrete
+    /// syntax.
+    pub fn into_message(self) -> Message<'a> {
+        self.message
+    }
+}
+
+impl<'a> std::ops::Deref for LosslessMessage<'a> {
+    type Target = Message<'a>;
+    fn deref(&self) -> &Self::Target {
+        &self.message
+    }
+}
+
+impl<'a> Print for LosslessMessage<'a> {
+    fn print(&self, fmt: &mut impl Formatter) {
+        self.message.print_diff(&self.original, fmt)
     }
 }
 
@@ -196,6 +444,7 @@ impl<'a> Arbitrary<'a> for Message<'a> {
             imf,
             mime_body,
             entries,
+            duplicates: vec![],
             raw: RawInput::none(),
             raw_headers: RawInput::none(),
         })
@@ -204,18 +453,162 @@ impl<'a> Arbitrary<'a> for Message<'a> {
 
 /// Parse a toplevel message.
 pub fn message<'a>(input: &'a [u8]) -> Message<'a> {
+    message_with_policy(input, header::DuplicatePolicy::default())
+}
+
+// Parse a toplevel message, with the given policy for header fields that may
+// only appear once but are duplicated. Discarded fields are recorded in
+// `Message::duplicates` (and `AnyPart::duplicates` for nested parts).
+pub(crate) fn message_with_policy<'a>(
+    input: &'a [u8],
+    policy: header::DuplicatePolicy,
+) -> Message<'a> {
+    message_with_charset(input, policy, None)
+}
+
+// Parse a toplevel message, decoding raw 8-bit header text that is not valid
+// UTF-8 with `charset`. If `charset` is `None`, the charset of the toplevel
+// Content-Type field is used instead. Only legacy charsets (see
+// `EmailCharset::is_legacy`) are used for decoding; otherwise, invalid UTF-8
+// is replaced by U+FFFD.
+pub(crate) fn message_with_charset<'a>(
+    input: &'a [u8],
+    policy: header::DuplicatePolicy,
+    charset: Option<EmailCharset>,
+) -> Message<'a> {
     // parse headers
     let (input_body, headers) = header::header_kv(input);
-    let fields: NaiveMessageFields = headers.into_iter().collect();
-    let mime = fields.mime.to_interpreted(mime::DefaultType::Generic);
-    // parse body
-    let mime_body = part::part_body(mime)(input_body);
-    Message {
-        imf: fields.imf,
-        mime_body,
-        entries: fields.entries,
-        raw: input.into(),
-        raw_headers: input[0..input.len() - input_body.len()].into(),
+    let fallback = charset
+        .or_else(|| content_type_charset(&headers))
+        .filter(EmailCharset::is_legacy);
+    text::charset::with_fallback(fallback, || {
+        let fields = NaiveMessageFields::new(headers, policy);
+        let mime = fields.mime.to_interpreted(mime::DefaultType::Generic);
+        // parse body
+        let mime_body = part::part_body_with_policy(mime, policy)(input_body);
+        Message {
+            imf: fields.imf,
+            mime_body,
+            entries: fields.entries,
+            duplicates: fields.duplicates,
+            raw: input.into(),
+            raw_headers: input[0..input.len() - input_body.len()].into(),
+        }
+    })
+}
+
+// Charset parameter of the first Content-Type field in `headers`, if any.
+fn content_type_charset(headers: &[header::FieldRaw]) -> Option<EmailCharset> {
+    let f = headers
+        .iter()
+        .find(|f| f.name.bytes().eq_ignore_ascii_case(b"content-type"))?;
+    // the field is parsed and interpreted again later on: do not report
+    // diagnostics (e.g. an unknown charset) twice
+    let (charset, _) = diagnostics::collect(f.body, || {
+        let (_, ty) = mime::r#type::naive_type(f.body).ok()?;
+        Some(mime::r#type::Text::from(&ty).charset)
+    });
+    charset
+}
+
+/// Parse a toplevel message in lossless mode.
+pub fn lossless<'a>(input: &'a [u8]) -> LosslessMessage<'a> {
+    LosslessMessage::new(input, message(input))
+}
+
+impl<'a> LosslessMessage<'a> {
+    // `message` must have been parsed from `input`
+    fn new(input: &'a [u8], message: Message<'a>) -> Self {
+        LosslessMessage {
+            input,
+            original: message.clone(),
+            message,
+        }
+    }
+}
+
+/// Configuration of the message parser, used by `crate::parse_message_with`.
+///
+/// `policy` defines what to do with header fields that may only appear once
+/// but are duplicated (e.g. two `From` fields). The discarded fields are
+/// recorded in `Message::duplicates`, and in `AnyPart::duplicates` for the
+/// nested parts. The first occurrence is kept by default.
+///
+/// `charset` is used to decode raw 8-bit header text that is not valid UTF-8,
+/// as commonly found in mail predating RFC 6532. By default, the charset of the
+/// toplevel Content-Type field is used for this purpose, if any. Only legacy
+/// charsets (see `EmailCharset::is_legacy`) are used for decoding; otherwise,
+/// invalid UTF-8 is replaced by U+FFFD.
+///
+/// `strict` defines whether parsing fails if the message does not strictly
+/// follow RFC 5322 and RFC 2045 to 2049. The error lists every violation that
+/// was found: the diagnostics reported while parsing (including obsolete
+/// syntax), and those of `diagnostics::validate` (missing mandatory fields,
+/// bare CR or LF, lines over 998 octets, 8-bit header text or 8-bit data in
+/// 7-bit parts).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParseConfig {
+    policy: header::DuplicatePolicy,
+    charset: Option<EmailCharset>,
+    strict: bool,
+}
+
+impl ParseConfig {
+    pub fn with_policy(self, policy: header::DuplicatePolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    pub fn with_charset(self, charset: Option<EmailCharset>) -> Self {
+        Self { charset, ..self }
+    }
+
+    pub fn with_strict(self, strict: bool) -> Self {
+        Self { strict, ..self }
+    }
+}
+
+/// A toplevel message parsed by `crate::parse_message_with`, with the recovery
+/// decisions made while parsing it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParsedMessage<'a> {
+    input: &'a [u8],
+    pub message: Message<'a>,
+    /// The non-compliant or unsupported input that was found, in which header
+    /// field, and at which byte range of the input. Empty in strict mode.
+    pub diagnostics: Vec<diagnostics::Diagnostic>,
+}
+
+impl<'a> ParsedMessage<'a> {
+    /// Keeps the concrete syntax of the input (see `LosslessMessage`).
+    pub fn into_lossless(self) -> LosslessMessage<'a> {
+        LosslessMessage::new(self.input, self.message)
+    }
+}
+
+/// Parse a toplevel message with the given configuration.
+pub fn message_with_config<'a>(
+    input: &'a [u8],
+    cfg: &ParseConfig,
+) -> Result<ParsedMessage<'a>, Vec<diagnostics::Diagnostic>> {
+    let (message, mut diagnostics) = diagnostics::collect(input, || {
+        message_with_charset(input, cfg.policy, cfg.charset.clone())
+    });
+    if !cfg.strict {
+        return Ok(ParsedMessage {
+            input,
+            message,
+            diagnostics,
+        });
+    }
+    diagnostics.extend(diagnostics::validate(&message));
+    if diagnostics.is_empty() {
+        Ok(ParsedMessage {
+            input,
+            message,
+            diagnostics,
+        })
+    } else {
+        Err(diagnostics)
     }
 }
 
@@ -238,12 +631,14 @@ mod tests {
     use crate::part::discrete::Text;
     use crate::part::field::EntityEntry;
     use crate::part::{AnyPart, MimeBody};
-    use crate::print::tests::print_to_vec;
+    use crate::print::tests::{print_to_vec, print_to_vec_with};
     use crate::text::charset::EmailCharset;
     use crate::text::encoding::{
         Base64Word, EncodedWord, EncodedWordToken, QuotedChunk, QuotedWord,
     };
+    use crate::text::html;
     use crate::text::misc_token::*;
+    use crate::text::whitespace::Comments;
     use crate::text::words::Atom;
     use chrono::{FixedOffset, TimeZone};
     use pretty_assertions::assert_eq;
@@ -295,10 +690,12 @@ between the header information and the body of the message.";
                     )))]),
                     domain: Domain::Atoms(vec![Atom("example"[..].into()), Atom("com"[..].into())]),
                 },
+                comments: Comments::default(),
+                raw: RawInput::none(),
             };
             let mut imf = Imf::new();
             imf.from = From::Single { from, sender: None };
-            imf.date = imf::DateTimeOpt::Some(DateTime(
+            imf.date = imf::DateTimeOpt::Some(DateTime::new(
                 FixedOffset::east_opt(2 * 3600)
                     .unwrap()
                     .with_ymd_and_hms(2023, 3, 7, 8, 0, 0)
@@ -312,6 +709,8 @@ between the header information and the body of the message.";
                     )))]),
                     domain: Domain::Atoms(vec![Atom("example"[..].into()), Atom("com"[..].into())]),
                 },
+                comments: Comments::default(),
+                raw: RawInput::none(),
             })];
             imf.subject = Some(Unstructured(vec![
                 UnstrToken::from_plain(" ", UnstrTxtKind::Fws),
@@ -367,6 +766,7 @@ between the header information and the body of the message.";
                 entries,
                 raw: fullmail.into(),
                 raw_headers: RawInput::between(fullmail, b"Date", b"MIME-Version: 1.0\r\n\r\n"),
+                duplicates: vec![],
             }
         });
     }
@@ -440,9 +840,10 @@ OoOoOoOoOoOoOoOoOoOoOoOoOoOoOoOoO<br />
                                         Atom("example"[..].into()),
                                         Atom("org"[..].into()),
                                     ]),
-                                }
+                                }, comments: Comments::default(),
+                                raw: RawInput::none(),
                             };
-                        let date = imf::datetime::DateTime(FixedOffset::east_opt(2 * 3600)
+                        let date = imf::datetime::DateTime::new(FixedOffset::east_opt(2 * 3600)
                             .unwrap()
                             .with_ymd_and_hms(2023, 07, 8, 7, 14, 29)
                             .unwrap());
@@ -463,7 +864,8 @@ OoOoOoOoOoOoOoOoOoOoOoOoOoOoOoOoO<br />
                                         Atom("machine"[..].into()),
                                         Atom("example"[..].into()),
                                     ]),
-                                }
+                                }, comments: Comments::default(),
+                             raw: RawInput::none(),
                          })];
 
                         imf.cc = vec![imf::address::AddressRef::Single(imf::mailbox::MailboxRef {
@@ -471,6 +873,7 @@ OoOoOoOoOoOoOoOoOoOoOoOoOoOoOoOoO<br />
                                 PhraseToken::Encoded(EncodedWord(vec![
                                     EncodedWordToken::Quoted(QuotedWord {
                                         enc: EmailCharset::from(b"iso-8859-1"),
+                                        lang: None,
                                         chunks: vec![
                                             QuotedChunk::Safe(b"Andr"[..].into()),
                                             QuotedChunk::Encoded(vec![0xE9]),
@@ -489,7 +892,8 @@ OoOoOoOoOoOoOoOoOoOoOoOoOoOoOoOoO<br />
                                     Atom("ac"[..].into()),
                                     Atom("be"[..].into()),
                                 ]),
-                            }
+                            }, comments: Comments::default(),
+                            raw: RawInput::none(),
                         })];
 
                         imf.subject = Some(Unstructured(vec![
@@ -497,10 +901,12 @@ OoOoOoOoOoOoOoOoOoOoOoOoOoOoOoOoO<br />
                             UnstrToken::Encoded(EncodedWord(vec![
                                 EncodedWordToken::Base64(Base64Word{
                                     enc: EmailCharset::from(b"iso-8859-1"),
+                                    lang: None,
                                     content: b"SWYgeW91IGNhbiByZWFkIHRoaXMgeW8"[..].into(),
                                 }),
                                 EncodedWordToken::Base64(Base64Word{
                                     enc: EmailCharset::from(b"iso-8859-2"),
+                                    lang: None,
                                     content: b"dSB1bmRlcnN0YW5kIHRoZSBleGFtcGxlLg"[..].into(),
                                 })
                             ])),
@@ -514,7 +920,7 @@ OoOoOoOoOoOoOoOoOoOoOoOoOoOoOoOoO<br />
                                 Atom("www".into()),
                                 Atom("grrrndzero".into()),
                                 Atom("org".into()),
-                            ]),
+                            ]), comments: Comments::default(),
                         });
 
                         imf.mime_version = Some(imf::mime::Version::default());
@@ -614,6 +1020,7 @@ OoOoOoOoOoOoOoOoOoOoOoOoOoOoOoOoO<br />
                                 }),
                                 raw: RawInput::between(fullmail, b"Content-Type: text/plain", b"OoOoOoOoOoOoOoOoOoOoOoOoOoOoOoOoO\n"),
                                 raw_headers: b"Content-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: quoted-printable\n\n".into(),
+                                duplicates: vec![],
                             },
                             AnyPart {
                                 entries: vec![
@@ -653,20 +1060,31 @@ OoOoOoOoOoOoOoOoOoOoOoOoOoOoOoOoO<br />
                                 }),
                                 raw: RawInput::between(fullmail, b"X-Custom", b"</div>\n"),
                                 raw_headers: b"X-Custom: foobar\nContent-Type: text/html; charset=us-ascii\n\n".into(),
+                                duplicates: vec![],
                             },
                         ],
                         raw_body: RawInput::between(fullmail, b"This is a multi-part", b"b1_e376dc71bafc953c0b0fdeb9983a9956--\n"),
                     }),
                 raw: fullmail.into(),
                 raw_headers: RawInput::between(fullmail, b"Date:", b"bad_redundant\n\n"),
+                duplicates: vec![
+                    header::DuplicateField {
+                        name: header::FieldName(b"Subject".into()),
+                        raw: RawInput::between(fullmail, b"Subject: Bad", b"_subject"),
+                    },
+                    header::DuplicateField {
+                        name: header::FieldName(b"Content-Transfer-Encoding".into()),
+                        raw: RawInput::between(fullmail, b"Content-Transfer-Encoding: bad", b"_redundant"),
+                    },
+                ],
             };
 
         let reprinted: &[u8] = "Date: Sat, 8 Jul 2023 07:14:29 +0200\r
 From: Grrrnd Zero <grrrndzero@example.org>\r
 To: John Doe <jdoe@machine.example>\r
 Cc: =?windows-1252?Q?Andr=E9?= Pirard <PIRARD@vm1.ulg.ac.be>\r
-Subject: =?windows-1252?B?SWYgeW91IGNhbiByZWFkIHRoaXMgeW8?=\r
- =?ISO-8859-2?B?dSB1bmRlcnN0YW5kIHRoZSBleGFtcGxlLg?=\r
+Subject: =?windows-1252?B?SWYgeW91IGNhbiByZWFkIHRoaXMgeW8=?=\r
+ =?ISO-8859-2?B?dSB1bmRlcnN0YW5kIHRoZSBleGFtcGxlLg==?=\r
 X-Unknown: something something\r
 Message-ID: <NTAxNzA2AC47634Y366BAMTY4ODc5MzQyODY0ODY5@www.grrrndzero.org>\r
 MIME-Version: 1.0\r
@@ -726,14 +1144,21 @@ hello??";
                     raw_body: b"hello??".into(),
                 });
 
-                let entries = vec![MessageEntry::Unstructured(header::Unstructured {
-                    name: header::FieldName(b"hello".into()),
-                    body: Unstructured(vec![
-                        UnstrToken::from_plain(" ", UnstrTxtKind::Fws),
-                        UnstrToken::from_plain("yolo", UnstrTxtKind::Txt),
-                    ]),
-                    raw_body: b" yolo".into(),
-                })];
+                let entries = vec![
+                    MessageEntry::Invalid(header::Invalid {
+                        name: header::FieldName(b"date".into()),
+                        body: b" uhh"[..].into(),
+                        raw_body: b" uhh".into(),
+                    }),
+                    MessageEntry::Unstructured(header::Unstructured {
+                        name: header::FieldName(b"hello".into()),
+                        body: Unstructured(vec![
+                            UnstrToken::from_plain(" ", UnstrTxtKind::Fws),
+                            UnstrToken::from_plain("yolo", UnstrTxtKind::Txt),
+                        ]),
+                        raw_body: b" yolo".into(),
+                    }),
+                ];
 
                 Message {
                     imf,
@@ -741,15 +1166,106 @@ hello??";
                     entries,
                     raw: input.into(),
                     raw_headers: b"date: uhh\nhello: yolo\n\n".into(),
+                    duplicates: vec![],
                 }
             },
-            b"hello: yolo\r
+            b"date: uhh\r
+hello: yolo\r
 MIME-Version: 1.0\r
 \r
 hello??",
         );
     }
 
+    #[test]
+    fn test_invalid_fields() {
+        let input = b"From: alice@example.com
+To: <<bob>>
+Message-ID: not an
+ identifier
+Content-Type: ;;
+Subject: hello
+
+body";
+        let msg = message(input);
+        let invalid: Vec<_> = msg
+            .entries
+            .iter()
+            .filter_map(|e| match e {
+                MessageEntry::Invalid(i) => Some(String::from_utf8_lossy(&i.name.0).into_owned()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(invalid, vec!["To", "Message-ID", "Content-Type"]);
+
+        assert_eq!(
+            String::from_utf8(print_to_vec(msg.clone())).unwrap(),
+            "From: alice@example.com\r
+To: <<bob>>\r
+Message-ID: not an identifier\r
+Content-Type: ;;\r
+Subject: hello\r
+MIME-Version: 1.0\r
+\r
+body"
+        );
+
+        let printed = crate::print::print_to_vec(
+            crate::print::FMT_DEFAULT
+                .with_seed(Some(0))
+                .with_sanitize(true),
+            msg,
+        );
+        assert_eq!(
+            String::from_utf8(printed).unwrap(),
+            "From: alice@example.com\r
+Subject: hello\r
+MIME-Version: 1.0\r
+\r
+body"
+        );
+    }
+
+    #[test]
+    fn test_no_entry_conflict_fields() {
+        use crate::diagnostics::DiagnosticKind;
+
+        let input = b"From: alice@example.com\r
+To: bob@example.com\r
+Received: from mx.example.org\r
+Cc: carol@example.com\r
+To: dave@example.com\r
+\r
+body";
+        let (msg, diagnostics) = crate::diagnostics::collect(input, || message(input));
+        let kinds: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.kind, d.header.as_deref()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (DiagnosticKind::MisplacedField, Some("Received")),
+                (DiagnosticKind::MergedField, Some("To")),
+            ]
+        );
+        assert_eq!(msg.imf.to.len(), 2);
+        assert!(msg.imf.trace.is_empty());
+
+        // the misplaced trace field is kept, the addresses of the repeated
+        // field are printed with the first one
+        assert_eq!(
+            String::from_utf8(print_to_vec(msg)).unwrap(),
+            "From: alice@example.com\r
+To: bob@example.com, dave@example.com\r
+Received: from mx.example.org\r
+Cc: carol@example.com\r
+MIME-Version: 1.0\r
+\r
+body"
+        );
+    }
+
     #[test]
     fn test_trace_unstructured() {
         test_message_reprint(
@@ -794,6 +1310,105 @@ MIME-Version: 1.0\r
         );
     }
 
+    #[test]
+    fn test_arc_sets() {
+        use crate::imf::arc::*;
+
+        let mut msg = message(
+            b"ARC-Seal: i=1; a=rsa-sha256; cv=none; d=example.org; s=sel;\r
+ t=1700000000; b=c2VhbA==\r
+ARC-Message-Signature: i=1; a=rsa-sha256; c=relaxed/relaxed; d=example.org;\r
+ s=sel; h=from:to:subject; bh=Ym9keQ==; b=c2ln\r
+ARC-Authentication-Results: i=1; mx.example.org; spf=pass\r
+Received: from mx.example.org\r
+From: alice@example.com\r
+\r
+Hello\r
+",
+        );
+        let sets = msg.imf.arc_chain().unwrap();
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].seal.cv, ChainValidation::None);
+        assert_eq!(next_instance(&msg.imf.trace), 2);
+
+        let seal = Seal {
+            instance: 2,
+            cv: ChainValidation::Pass,
+            tags: vec![],
+            instance_tag: None,
+            cv_tag: None,
+        };
+        let ams = MessageSignature {
+            instance: 2,
+            tags: vec![],
+            instance_tag: None,
+        };
+        let aar = AuthenticationResults {
+            instance: 2,
+            results: Unstructured(vec![
+                UnstrToken::from_plain(" ", UnstrTxtKind::Fws),
+                UnstrToken::from_plain("list.example.net;", UnstrTxtKind::Txt),
+                UnstrToken::from_plain(" ", UnstrTxtKind::Fws),
+                UnstrToken::from_plain("arc=pass", UnstrTxtKind::Txt),
+            ]),
+        };
+        assert_eq!(
+            msg.prepend_arc_set(seal.clone(), ams.clone(), aar.clone()),
+            Ok(())
+        );
+        assert_eq!(
+            msg.prepend_arc_set(seal, ams, aar),
+            Err(ChainError::InvalidInstance(2))
+        );
+        assert_eq!(msg.imf.arc_chain().unwrap().len(), 2);
+
+        test_message_reprint(
+            &print_to_vec(msg),
+            b"ARC-Seal: i=2; cv=pass\r
+ARC-Message-Signature: i=2\r
+ARC-Authentication-Results: i=2; list.example.net; arc=pass\r
+ARC-Seal: i=1; a=rsa-sha256; cv=none; d=example.org; s=sel; t=1700000000;\r
+ b=c2VhbA==\r
+ARC-Message-Signature: i=1; a=rsa-sha256; c=relaxed/relaxed; d=example.org;\r
+ s=sel; h=from:to:subject; bh=Ym9keQ==; b=c2ln\r
+ARC-Authentication-Results: i=1; mx.example.org; spf=pass\r
+Received: from mx.example.org\r
+From: alice@example.com\r
+MIME-Version: 1.0\r
+\r
+Hello\r
+",
+        );
+    }
+
+    #[test]
+    fn test_misplaced_arc_field() {
+        let input = b"From: alice@example.com\r
+ARC-Seal: i=1; a=rsa-sha256; cv=none; d=example.org; s=sel; b=c2VhbA==\r
+\r
+Hello\r
+";
+        let (msg, diagnostics) = crate::diagnostics::collect(input, || message(input));
+        assert!(diagnostics.iter().any(|d| d.kind
+            == crate::diagnostics::DiagnosticKind::MisplacedField
+            && d.header.as_deref() == Some("ARC-Seal")));
+        assert!(msg.imf.arc_chain().unwrap().is_empty());
+        assert!(msg.entries.iter().any(|e| matches!(
+            e,
+            MessageEntry::Invalid(i) if i.name.0.as_ref() == b"ARC-Seal"
+        )));
+
+        assert_eq!(
+            String::from_utf8(print_to_vec(msg)).unwrap(),
+            "From: alice@example.com\r
+ARC-Seal: i=1; a=rsa-sha256; cv=none; d=example.org; s=sel; b=c2VhbA==\r
+MIME-Version: 1.0\r
+\r
+Hello\r
+"
+        );
+    }
+
     // tests for UTF8 from https://github.com/arnt/eai-test-messages
 
     #[test]
@@ -888,6 +1503,77 @@ asdf"
         );
     }
 
+    #[test]
+    fn test_legacy_charset_headers() {
+        // KOI8-R subject and display name, decoded using the toplevel
+        // Content-Type charset
+        let input = b"From: \xe9\xd7\xc1\xce <ivan@example.com>\r
+Subject: \xf0\xd2\xc9\xd7\xc5\xd4\r
+Content-Type: text/plain; charset=koi8-r\r
+\r
+Hi";
+        test_message_reprint(
+            input,
+            "From: Иван <ivan@example.com>\r
+Subject: Привет\r
+Content-Type: text/plain; charset=KOI8-R\r
+MIME-Version: 1.0\r
+\r
+Hi"
+            .as_bytes(),
+        );
+
+        // the caller-supplied charset takes precedence
+        let msg = message_with_charset(
+            input,
+            header::DuplicatePolicy::default(),
+            Some(EmailCharset::from("windows-1251")),
+        );
+        assert_eq!(msg.imf.subject.unwrap().to_string(), " рТЙЧЕФ");
+
+        // UTF-8 is not a fallback charset
+        let msg = message_with_charset(
+            b"Subject: Caf\xe9\r\n\r\n",
+            header::DuplicatePolicy::default(),
+            Some(EmailCharset::utf8()),
+        );
+        assert_eq!(msg.imf.subject.unwrap().to_string(), " Caf\u{FFFD}");
+
+        // only invalid UTF-8 is decoded with the fallback charset, and it is
+        // not reported
+        let input = b"Subject: Caf\xc3\xa9 cr\xe8me\r\n\r\n";
+        let cfg = ParseConfig::default().with_charset(Some(EmailCharset::from("iso-8859-1")));
+        let parsed = crate::parse_message_with(input, &cfg).unwrap();
+        assert_eq!(
+            parsed.message.imf.subject.as_ref().unwrap().to_string(),
+            " Café crème"
+        );
+        assert!(parsed.diagnostics.is_empty(), "{:?}", parsed.diagnostics);
+
+        // without a fallback charset, invalid UTF-8 is reported once
+        let parsed = crate::parse_message_with(input, &ParseConfig::default()).unwrap();
+        let invalid: Vec<_> = parsed
+            .diagnostics
+            .iter()
+            .filter(|d| {
+                d.kind == diagnostics::DiagnosticKind::Recovered("input contains invalid UTF-8")
+            })
+            .collect();
+        assert_eq!(invalid.len(), 1, "{:?}", parsed.diagnostics);
+
+        // the Content-Type field used to find the charset is reported once
+        let input = b"Content-Type: text/plain; charset=x-unknown\r\n\r\nHi";
+        let parsed = crate::parse_message_with(input, &ParseConfig::default()).unwrap();
+        assert_eq!(
+            parsed
+                .diagnostics
+                .iter()
+                .filter(|d| d.kind == diagnostics::DiagnosticKind::Recovered("unknown charset"))
+                .count(),
+            1
+        );
+    }
+
     #[test]
     fn test_utf8_mimefield() {
         test_message_reprint(
@@ -948,4 +1634,401 @@ MIME-Version: 1.0\r
             .as_bytes(),
         );
     }
+
+    #[test]
+    fn test_duplicate_policy() {
+        let txt = b"From: alice@example.com
+Subject: first
+To: bob@example.com
+From: mallory@example.com
+Content-Type: text/plain
+To: carol@example.com
+Subject: second
+Content-Type: text/html
+
+Hi!";
+        let raw_duplicates = |msg: &Message| -> Vec<String> {
+            msg.duplicates
+                .iter()
+                .map(|d| String::from_utf8_lossy(d.raw.0.unwrap()).into_owned())
+                .collect()
+        };
+        let reprint = |msg: Message| String::from_utf8(print_to_vec(msg)).unwrap();
+
+        let msg = message_with_policy(txt, header::DuplicatePolicy::KeepFirst);
+        assert_eq!(message(txt), msg);
+        assert_eq!(
+            raw_duplicates(&msg),
+            vec![
+                "From: mallory@example.com",
+                "Subject: second",
+                "Content-Type: text/html",
+            ]
+        );
+        assert_eq!(
+            reprint(msg),
+            "From: alice@example.com\r
+Subject: first\r
+To: bob@example.com, carol@example.com\r
+Content-Type: text/plain; charset=us-ascii\r
+MIME-Version: 1.0\r
+\r
+Hi!"
+        );
+
+        let msg = message_with_policy(txt, header::DuplicatePolicy::KeepLast);
+        assert_eq!(
+            raw_duplicates(&msg),
+            vec![
+                "From: alice@example.com",
+                "Subject: first",
+                "Content-Type: text/plain",
+            ]
+        );
+        assert_eq!(
+            reprint(msg),
+            "To: bob@example.com, carol@example.com\r
+From: mallory@example.com\r
+Subject: second\r
+Content-Type: text/html; charset=us-ascii\r
+MIME-Version: 1.0\r
+\r
+Hi!"
+        );
+
+        let msg = message_with_policy(txt, header::DuplicatePolicy::Reject);
+        assert_eq!(
+            raw_duplicates(&msg),
+            vec![
+                "From: alice@example.com",
+                "From: mallory@example.com",
+                "Subject: first",
+                "Subject: second",
+                "Content-Type: text/plain",
+                "Content-Type: text/html",
+            ]
+        );
+        assert_eq!(msg.imf.from_or_sender(), None);
+        assert_eq!(msg.imf.subject, None);
+    }
+
+    #[test]
+    fn test_spans() {
+        let txt = b"From: alice@example.com\r
+Subject: hello\r
+ world\r
+X-Custom: foo\r
+To: Team: bob@example.com (Bob),\r
+ carol@example.com;, Dave <dave@example.com>\r
+MIME-Version: 1.0\r
+Content-Type: multipart/mixed; boundary=b\r
+\r
+--b\r
+Content-Type: text/plain\r
+\r
+Hi!\r
+--b--\r
+";
+        let msg = message(txt);
+        let at = |r: Range<usize>| String::from_utf8_lossy(&txt[r]).into_owned();
+
+        assert_eq!(msg.span(&msg.raw), Some(0..txt.len()));
+        assert_eq!(at(msg.header_span().unwrap()).lines().count(), 9);
+        assert!(at(msg.body_span().unwrap()).starts_with("--b\r\n"));
+
+        let fields: Vec<_> = msg
+            .field_list()
+            .iter()
+            .map(|f| {
+                (
+                    at(msg.field_span(&f.raw_body()).unwrap()),
+                    at(msg.span(&f.raw_body()).unwrap()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                (
+                    "From: alice@example.com".into(),
+                    " alice@example.com".into()
+                ),
+                ("Subject: hello\r\n world".into(), " hello\r\n world".into()),
+                ("X-Custom: foo".into(), " foo".into()),
+                (
+                    "To: Team: bob@example.com (Bob),\r\n carol@example.com;, Dave <dave@example.com>"
+                        .into(),
+                    " Team: bob@example.com (Bob),\r\n carol@example.com;, Dave <dave@example.com>"
+                        .into()
+                ),
+                ("MIME-Version: 1.0".into(), " 1.0".into()),
+                (
+                    "Content-Type: multipart/mixed; boundary=b".into(),
+                    " multipart/mixed; boundary=b".into()
+                ),
+            ]
+        );
+
+        let imf::From::Single { from, .. } = &msg.imf.from else {
+            panic!("expected a single From mailbox")
+        };
+        assert_eq!(at(msg.span(&from.raw).unwrap()), "alice@example.com");
+        let addresses: Vec<_> = msg
+            .imf
+            .to
+            .iter()
+            .map(|a| at(msg.span(a.raw()).unwrap()))
+            .collect();
+        assert_eq!(
+            addresses,
+            vec![
+                "Team: bob@example.com (Bob),\r\n carol@example.com;",
+                "Dave <dave@example.com>",
+            ]
+        );
+        let imf::address::AddressRef::Many(group) = &msg.imf.to[0] else {
+            panic!("expected a group")
+        };
+        let members: Vec<_> = group
+            .participants
+            .as_ref()
+            .unwrap()
+            .0
+            .iter()
+            .map(|m| at(msg.span(&m.raw).unwrap()))
+            .collect();
+        assert_eq!(members, vec!["bob@example.com (Bob)", "carol@example.com"]);
+
+        let part = &msg.mime_body.as_multipart().unwrap().children[0];
+        let part_span = msg.span(&part.raw).unwrap();
+        assert_eq!(at(part_span.clone()), "Content-Type: text/plain\r\n\r\nHi!");
+        assert_eq!(
+            at(msg.span(&part.mime_body.raw_body()).unwrap()),
+            "Hi!".to_string()
+        );
+        let field = &part.field_list()[0];
+        assert_eq!(
+            msg.field_span(&field.raw_body()),
+            Some(part_span.start..part_span.start + 24)
+        );
+
+        // owned messages no longer refer to the input
+        let owned = bounded_static::ToBoundedStatic::to_static(&msg);
+        assert_eq!(owned.header_span(), None);
+        assert_eq!(owned.field_span(&owned.field_list()[0].raw_body()), None);
+    }
+
+    #[test]
+    fn test_lossless() {
+        let txt = b"Received: from x (x [1.2.3.4]) by y;\r
+\tTue, 21 Nov 23 07:07:07 GMT\r
+From:\talice@example.com (Alice),\r
+ bob@example.com\r
+From: mallory@example.com\r
+Subject:  hello   world\r
+bad line\r
+Content-Type: multipart/alternative; boundary=\"XYZ\"\r
+\r
+preamble\r
+--XYZ\r
+\r
+plain\r
+--XYZ--\r
+epilogue";
+        let msg = lossless(txt);
+        assert_eq!(print_to_vec(msg.clone()), txt);
+        // the typed view is the same as the one of the regular parser
+        assert_eq!(*msg, message(txt));
+        assert_eq!(msg.imf.trace.len(), 1);
+        assert_eq!(msg.duplicates.len(), 1);
+        // while the regular printer normalizes the message
+        assert_ne!(print_to_vec(msg.into_message()), txt);
+    }
+
+    #[test]
+    fn test_print_diff() {
+        let txt = b"DKIM-Signature: v=1; a=rsa-sha256; d=example.com;\r
+\th=from:to; b=abc\r
+From:  alice@example.com\r
+To: bob@example.com (Bob)\r
+Subject:  hello\r
+Content-Type: multipart/mixed;\r
+ boundary=\"XYZ\"\r
+\r
+preamble\r
+--XYZ\r
+Content-Type: text/plain\r
+\r
+first   part\r
+--XYZ\r
+Content-Type: text/plain\r
+\r
+second part\r
+--XYZ--\r
+";
+        // no modification
+        let mut msg = lossless(txt);
+        assert_eq!(print_to_vec(msg.clone()), txt);
+
+        // modifying a header field only re-serializes this field
+        let (_, subject) = unstructured(b" goodbye").unwrap();
+        msg.message_mut().imf.subject = Some(subject);
+        let printed = print_to_vec(msg.clone());
+        assert_eq!(
+            String::from_utf8_lossy(&printed),
+            String::from_utf8_lossy(txt).replace("Subject:  hello", "Subject: goodbye"),
+        );
+
+        // modifying a part only re-serializes this part; the boundary is kept
+        if let MimeBody::Mult(m) = &mut msg.message_mut().mime_body {
+            if let MimeBody::Txt(t) = &mut m.children[1].mime_body {
+                t.body = b"modified"[..].into();
+            }
+        }
+        let printed = print_to_vec(msg.clone());
+        assert_eq!(
+            String::from_utf8_lossy(&printed),
+            String::from_utf8_lossy(txt)
+                .replace("Subject:  hello", "Subject: goodbye")
+                .replace("second part", "modified"),
+        );
+
+        // modifying the Content-Type of the multipart changes its boundary,
+        // but the child parts are still copied
+        let orig = message(txt);
+        let mut edited = orig.clone();
+        if let MimeBody::Mult(m) = &mut edited.mime_body {
+            m.mime.ctype.subtype = mime::r#type::MultipartSubtype::Alternative;
+        }
+        let printed = print_to_vec_with(|f| edited.print_diff(&orig, f));
+        let printed = String::from_utf8_lossy(&printed);
+        assert!(printed.starts_with(
+            "DKIM-Signature: v=1; a=rsa-sha256; d=example.com;\r\n\th=from:to; b=abc\r\n\
+             From:  alice@example.com\r\n\
+             To: bob@example.com (Bob)\r\n\
+             Subject:  hello\r\n\
+             Content-Type: multipart/alternative;\r\n boundary=\""
+        ));
+        assert!(!printed.contains("XYZ"));
+        assert!(printed.contains("\r\nContent-Type: text/plain\r\n\r\nfirst   part\r\n"));
+        let reparsed = message(printed.as_bytes());
+        let m = reparsed.mime_body.as_multipart().unwrap();
+        assert_eq!(
+            m.mime.ctype.subtype,
+            mime::r#type::MultipartSubtype::Alternative
+        );
+        assert_eq!(m.children.len(), 2);
+    }
+
+    #[test]
+    fn test_reconcile_html_charset() {
+        let txt = b"From: alice@example.com\r
+Content-Type: text/html; charset=utf-8\r
+Content-Transfer-Encoding: quoted-printable\r
+\r
+<meta charset=3D\"iso-8859-1\"><p>caf=C3=A9</p>\r
+";
+        let mut msg = message(txt);
+        let MimeBody::Txt(text) = &mut msg.mime_body else {
+            panic!()
+        };
+        let c = text.reconcile_html_charset().unwrap();
+        assert_eq!(c.source, html::CharsetSource::ContentType);
+        assert_eq!(c.meta, Some(EmailCharset::from("iso-8859-1")));
+        assert_eq!(
+            &text.body[..],
+            b"<meta charset=3D\"UTF-8\"><p>caf=C3=A9</p>\r\n"
+        );
+
+        // without a charset parameter, the meta declaration is trusted
+        let txt = b"From: alice@example.com\r
+Content-Type: text/html\r
+\r
+<meta charset=\"koi8-r\"><p>\xf0\xd2\xc9\xd7\xc5\xd4</p>\r
+";
+        let mut msg = message(txt);
+        let MimeBody::Txt(text) = &mut msg.mime_body else {
+            panic!()
+        };
+        assert_eq!(
+            text.reconcile_html_charset().unwrap().charset,
+            EmailCharset::from("koi8-r")
+        );
+        assert_eq!(text.mime.ctype.charset, EmailCharset::from("koi8-r"));
+        let printed = print_to_vec(&msg);
+        assert!(String::from_utf8_lossy(&printed).contains("charset=KOI8-R"));
+
+        // text/plain parts are left alone, whatever their body contains
+        let txt = b"From: alice@example.com\r
+Content-Type: text/plain; charset=utf-8\r
+\r
+<meta charset=\"koi8-r\">\r
+";
+        let mut msg = message(txt);
+        let MimeBody::Txt(text) = &mut msg.mime_body else {
+            panic!()
+        };
+        assert_eq!(text.html_charset(), None);
+        assert_eq!(text.reconcile_html_charset(), None);
+        assert_eq!(text.mime.ctype.charset, EmailCharset::utf8());
+        assert_eq!(&text.body[..], b"<meta charset=\"koi8-r\">\r\n");
+    }
+
+    #[test]
+    fn test_long_body_lines() {
+        let long = "x".repeat(1000);
+        let txt = format!(
+            "From: alice@example.com\r
+Content-Type: multipart/mixed; boundary=\"XYZ\"\r
+\r
+--XYZ\r
+Content-Type: text/plain; charset=utf-8\r
+Content-Transfer-Encoding: 8bit\r
+\r
+{long}\r
+--XYZ\r
+Content-Type: text/plain\r
+\r
+short\r
+--XYZ--\r
+"
+        );
+        let msg = message(txt.as_bytes());
+        let printed = print_to_vec(&msg);
+        assert!(!crate::print::has_long_line(&printed, 0));
+
+        let reparsed = message(&printed);
+        let children = &reparsed.mime_body.as_multipart().unwrap().children;
+        let first = children[0].mime_body.as_text().unwrap();
+        assert_eq!(
+            first.mime.fields.transfer_encoding,
+            mime::mechanism::Mechanism::QuotedPrintable
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&first.body).replace("=\r\n", ""),
+            long
+        );
+        let second = children[1].mime_body.as_text().unwrap();
+        assert_eq!(
+            second.mime.fields.transfer_encoding,
+            mime::mechanism::Mechanism::_7Bit
+        );
+        assert_eq!(second.body.as_ref(), b"short");
+
+        // in strict mode, long lines are reported instead
+        let cfg = || {
+            crate::print::FMT_DEFAULT
+                .with_seed(Some(0))
+                .with_strict(true)
+        };
+        assert_eq!(
+            crate::print::print_checked(cfg(), &msg),
+            Err(crate::print::FmtError::BodyLineTooLong)
+        );
+        let mut fmt = crate::print::Fmt::new(cfg());
+        msg.print(&mut fmt);
+        assert_eq!(fmt.finish(), Err(crate::print::FmtError::BodyLineTooLong));
+        let err = crate::print::print_to(vec![], cfg(), &msg).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
 }
@@ -113,6 +113,7 @@ mod tests {
     use crate::mime::r#type::*;
     use crate::text::misc_token::MIMEWord;
     use crate::text::quoted::QuotedString;
+    use crate::text::whitespace::Comments;
     use crate::text::words::MIMEAtom;
 
     #[test]
@@ -147,6 +148,7 @@ This is a multipart message.
                             value: MIMEWord::Quoted(QuotedString(vec![
                                 "b1_e376dc71bafc953c0b0fdeb9983a9956"[..].into()
                             ])),
+                            comments: Comments::default(),
                         }]
                     }),
                     NaiveField::TransferEncoding(Mechanism::_7Bit),
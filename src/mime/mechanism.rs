@@ -1,9 +1,16 @@
+use crate::diagnostics::{self, Severity};
 use crate::i18n::ContainsUtf8;
 use crate::print::{Formatter, Print, ToStringFromPrint};
+use crate::text::ascii;
 use crate::text::whitespace::cfws;
 use crate::text::words::{mime_atom, MIMEAtom};
 #[cfg(feature = "tracing-recover")]
 use crate::utils::bytes_to_trace_string;
+use base64::{
+    alphabet,
+    engine::{general_purpose, DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine as _,
+};
 use bounded_static::ToStatic;
 use eml_codec_derives::instrument_input;
 use nom::{
@@ -13,6 +20,7 @@ use nom::{
     sequence::{delimited, tuple},
     IResult,
 };
+use std::borrow::Cow;
 #[cfg(feature = "tracing")]
 use tracing::warn;
 #[cfg(feature = "arbitrary")]
@@ -30,6 +38,30 @@ pub enum Mechanism<'a> {
     Other(MIMEAtom<'a>),
 }
 impl<'a> Mechanism<'a> {
+    /// Decodes `body`, encoded with this content transfer encoding.
+    ///
+    /// Decoding is lenient: invalid characters of base64 data are ignored,
+    /// and malformed quoted-printable escapes are kept as is. Bodies using an
+    /// identity (or unknown) encoding are returned unchanged.
+    pub fn decode<'b>(&self, body: &'b [u8]) -> Cow<'b, [u8]> {
+        match self {
+            Self::QuotedPrintable => Cow::Owned(decode_quoted_printable(body)),
+            Self::Base64 => Cow::Owned(decode_base64(body)),
+            _ => Cow::Borrowed(body),
+        }
+    }
+
+    /// Encodes `body` with this content transfer encoding. If `text` is set,
+    /// line breaks of `body` are kept as such with quoted-printable (see
+    /// [`encode_quoted_printable`]).
+    pub fn encode<'b>(&self, body: &'b [u8], text: bool) -> Cow<'b, [u8]> {
+        match self {
+            Self::QuotedPrintable => Cow::Owned(encode_quoted_printable(body, text)),
+            Self::Base64 => Cow::Owned(encode_base64(body)),
+            _ => Cow::Borrowed(body),
+        }
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
         match self {
             Self::_7Bit => b"7bit",
@@ -67,8 +99,13 @@ impl<'a> Mechanism<'a> {
         match self {
             Mechanism::_7Bit | Mechanism::_8Bit | Mechanism::Binary => self.to_static(),
             _ => {
-                #[cfg(feature = "tracing-recover")]
-                warn!(mechanism = ?self, "to_multipart_encoding: ignoring invalid mechanism");
+                diagnostics::diagnose!(
+                    "tracing-recover",
+                    (mechanism = ?self),
+                    Recovered("to_multipart_encoding: ignoring invalid mechanism"),
+                    Severity::Warning,
+                    None,
+                );
                 Mechanism::default()
             }
         }
@@ -88,8 +125,13 @@ impl<'a> Mechanism<'a> {
         match self {
             Mechanism::_7Bit | Mechanism::_8Bit | Mechanism::Binary => self.to_static(),
             _ => {
-                #[cfg(feature = "tracing-unsupported")]
-                warn!(mechanism = ?self, "to_message_encoding: ignoring invalid mechanism");
+                diagnostics::diagnose!(
+                    "tracing-unsupported",
+                    (mechanism = ?self),
+                    Unsupported("to_message_encoding: ignoring invalid mechanism"),
+                    Severity::Error,
+                    None,
+                );
                 Mechanism::default()
             }
         }
@@ -112,13 +154,164 @@ pub fn mechanism(input: &[u8]) -> IResult<&[u8], Mechanism<'_>> {
             tuple((opt(cfws), opt(tag(";")), opt(cfws))),
         ),
         map(consumed(mime_atom), |(_i, tok)| {
-            #[cfg(feature = "tracing-recover")]
-            warn!(input = %bytes_to_trace_string(_i), "unknown mechanism");
+            diagnostics::diagnose!(
+                "tracing-recover",
+                (input = %bytes_to_trace_string(_i)),
+                Recovered("unknown mechanism"),
+                Severity::Warning,
+                None,
+            );
             Mechanism::Other(tok)
         }),
     ))(input)
 }
 
+// RFC 2045, sections 6.7 and 6.8: encoded lines must not be longer than 76
+// characters
+const QP_LINE_LEN: usize = 76;
+
+/// Encodes `body` using the quoted-printable content transfer encoding
+/// (RFC 2045, section 6.7).
+///
+/// If `text` is set, line breaks of `body` (CRLF, or a bare LF) are kept as
+/// line breaks (CRLF) of the output. Otherwise, they are encoded, as required
+/// for binary data.
+pub fn encode_quoted_printable(body: &[u8], text: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len() + body.len() / 8);
+    let mut line_len = 0;
+    let mut i = 0;
+    while i < body.len() {
+        let c = body[i];
+        if text {
+            let line_break = match (c, body.get(i + 1)) {
+                (b'\r', Some(b'\n')) => Some(2),
+                (b'\n', _) => Some(1),
+                _ => None,
+            };
+            if let Some(n) = line_break {
+                out.extend_from_slice(ascii::CRLF);
+                line_len = 0;
+                i += n;
+                continue;
+            }
+        }
+        // whitespace must be encoded at the end of a line
+        let line_end = match body.get(i + 1) {
+            None => true,
+            Some(b'\r' | b'\n') => text,
+            Some(_) => false,
+        };
+        let literal =
+            matches!(c, b'!'..=b'<' | b'>'..=b'~') || (matches!(c, b' ' | b'\t') && !line_end);
+        let len = if literal { 1 } else { 3 };
+        // soft line break, keeping room for the final "="
+        if line_len + len > QP_LINE_LEN - 1 {
+            out.extend_from_slice(b"=\r\n");
+            line_len = 0;
+        }
+        if literal {
+            out.push(c)
+        } else {
+            const HEX: &[u8; 16] = b"0123456789ABCDEF";
+            out.extend_from_slice(&[b'=', HEX[(c >> 4) as usize], HEX[(c & 0xf) as usize]]);
+        }
+        line_len += len;
+        i += 1;
+    }
+    out
+}
+
+/// Decodes `body`, encoded using the quoted-printable content transfer
+/// encoding (RFC 2045, section 6.7).
+///
+/// Whitespace at the end of lines is removed, as it may have been added by
+/// transport agents. Malformed escapes are kept as is.
+pub fn decode_quoted_printable(body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len());
+    for line in body.split_inclusive(|c| *c == b'\n') {
+        let (content, line_break) = match line.strip_suffix(b"\n") {
+            Some(l) => match l.strip_suffix(b"\r") {
+                Some(l) => (l, &b"\r\n"[..]),
+                None => (l, &b"\n"[..]),
+            },
+            None => (line, &b""[..]),
+        };
+        let content = trim_end_whitespace(content);
+        // soft line break
+        let (content, line_break) = match content.strip_suffix(b"=") {
+            Some(c) => (c, &b""[..]),
+            None => (content, line_break),
+        };
+
+        let mut i = 0;
+        while i < content.len() {
+            let hex = content.get(i + 1..i + 3).and_then(|h| {
+                std::str::from_utf8(h)
+                    .ok()
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+            });
+            match (content[i], hex) {
+                (b'=', Some(c)) => {
+                    out.push(c);
+                    i += 3;
+                }
+                (c, _) => {
+                    out.push(c);
+                    i += 1;
+                }
+            }
+        }
+        out.extend_from_slice(line_break);
+    }
+    out
+}
+
+fn trim_end_whitespace(line: &[u8]) -> &[u8] {
+    let end = line
+        .iter()
+        .rposition(|c| *c != ascii::SP && *c != ascii::HT)
+        .map_or(0, |i| i + 1);
+    &line[..end]
+}
+
+/// Encodes `body` using the base64 content transfer encoding (RFC 2045,
+/// section 6.8), in lines of 76 characters.
+pub fn encode_base64(body: &[u8]) -> Vec<u8> {
+    let encoded = general_purpose::STANDARD.encode(body);
+    let mut out = Vec::with_capacity(encoded.len() + encoded.len() / 38);
+    for (i, line) in encoded.as_bytes().chunks(QP_LINE_LEN).enumerate() {
+        if i > 0 {
+            out.extend_from_slice(ascii::CRLF);
+        }
+        out.extend_from_slice(line);
+    }
+    out
+}
+
+/// Decodes `body`, encoded using the base64 content transfer encoding
+/// (RFC 2045, section 6.8).
+///
+/// Characters outside of the base64 alphabet, such as line breaks, are
+/// ignored, and so is the padding.
+pub fn decode_base64(body: &[u8]) -> Vec<u8> {
+    const ENGINE: GeneralPurpose = GeneralPurpose::new(
+        &alphabet::STANDARD,
+        GeneralPurposeConfig::new()
+            .with_decode_padding_mode(DecodePaddingMode::RequireNone)
+            .with_decode_allow_trailing_bits(true),
+    );
+    let mut data: Vec<u8> = body
+        .iter()
+        .copied()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == ascii::PLUS || *c == ascii::SLASH)
+        .collect();
+    // a single trailing character does not encode a full byte
+    if data.len() % 4 == 1 {
+        data.pop();
+    }
+    ENGINE.decode(data).unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -145,4 +338,53 @@ mod tests {
             Ok((&b""[..], Mechanism::QuotedPrintable)),
         );
     }
+
+    #[test]
+    fn test_encode_quoted_printable() {
+        assert_eq!(
+            encode_quoted_printable(b"caf\xc3\xa9 = ok \r\nend \nx\t", true),
+            b"caf=C3=A9 =3D ok=20\r\nend=20\r\nx=09"
+        );
+        assert_eq!(encode_quoted_printable(b"a\r\nb", false), b"a=0D=0Ab");
+
+        let long = [b'x'; 200];
+        let encoded = encode_quoted_printable(&long, true);
+        let lines: Vec<_> = encoded.split(|c| *c == b'\n').collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|l| l.len() <= QP_LINE_LEN + 1));
+        assert_eq!(
+            encoded
+                .split(|c| *c == b'\n')
+                .map(|l| l.strip_suffix(b"=\r").unwrap_or(l))
+                .collect::<Vec<_>>()
+                .concat(),
+            long
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            decode_quoted_printable(b"caf=C3=a9 =3D ok=20\r\nsoft=  \r\nbreak  \nx=09=4"),
+            b"caf\xc3\xa9 = ok \r\nsoftbreak\nx\t=4"
+        );
+        let body = b"caf\xc3\xa9 = ok \r\nend \nx\t";
+        assert_eq!(
+            decode_quoted_printable(&encode_quoted_printable(body, false)),
+            body
+        );
+
+        assert_eq!(decode_base64(b"Y2Fmw6k=\r\n"), b"caf\xc3\xa9");
+        assert_eq!(decode_base64(b"Y2Fm\r\nw6k\r\n"), b"caf\xc3\xa9");
+        assert_eq!(decode_base64(b"Y2Fmw6kx\r\nY"), b"caf\xc3\xa91");
+        let long = [b'x'; 200];
+        let encoded = encode_base64(&long);
+        assert_eq!(encoded.split(|c| *c == b'\n').count(), 4);
+        assert_eq!(decode_base64(&encoded), long);
+
+        assert_eq!(
+            Mechanism::_8Bit.decode(b"caf\xc3\xa9"),
+            Cow::Borrowed(b"caf\xc3\xa9")
+        );
+    }
 }
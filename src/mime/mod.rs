@@ -12,15 +12,17 @@ use arbitrary::Arbitrary;
 use bounded_static::ToStatic;
 use std::collections::HashSet;
 
+use crate::diagnostics;
 #[cfg(feature = "arbitrary")]
 use crate::fuzz_eq::FuzzEq;
+use crate::header::{DuplicateField, DuplicatePolicy};
 use crate::i18n::ContainsUtf8;
 use crate::imf::identification::MessageID;
 use crate::mime::field::NaiveField;
 use crate::mime::mechanism::Mechanism;
-use crate::mime::r#type::{AnyType, MessageSubtype, NaiveType};
+use crate::mime::r#type::{AnyType, MessageSubtype};
 use crate::text::misc_token::Unstructured;
-use crate::utils::set_opt;
+use crate::utils::{Discard, SingleFields};
 
 #[derive(Debug, Default, PartialEq, Clone, ToStatic)]
 #[cfg_attr(feature = "arbitrary", derive(Arbitrary, FuzzEq))]
@@ -197,30 +199,89 @@ pub struct NaiveMIME<'a> {
     transfer_encoding: Option<Mechanism<'a>>,
     id: Option<MessageID<'a>>,
     description: Option<Unstructured<'a>>,
+    #[cfg_attr(feature = "arbitrary", fuzz_eq(ignore))]
+    single: SingleFields<field::Entry>,
+    // the fields `ctype` and `transfer_encoding` come from, to attribute the
+    // diagnostics reported by `to_interpreted`
+    #[cfg_attr(feature = "arbitrary", fuzz_eq(ignore))]
+    sources: Vec<(field::Entry, DuplicateField<'a>)>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum AddFieldErr {
+    // This field is discarded by the duplicate policy.
+    Duplicate,
+    // All the occurrences of this field are discarded by the duplicate policy
+    // (`DuplicatePolicy::Reject`), including the one previously returned as
+    // the given entry.
+    Rejected(field::Entry),
 }
 
 impl<'a> NaiveMIME<'a> {
-    pub fn add_field(&mut self, f: NaiveField<'a>) -> Option<field::Entry> {
-        match f {
-            NaiveField::Type(ctype) => {
-                set_opt(&mut self.ctype, ctype).then_some(field::Entry::Type)
-            }
-            NaiveField::TransferEncoding(enc) => {
-                set_opt(&mut self.transfer_encoding, enc).then_some(field::Entry::TransferEncoding)
-            }
-            NaiveField::ID(id) => set_opt(&mut self.id, id).then_some(field::Entry::ID),
-            NaiveField::Description(desc) => {
-                set_opt(&mut self.description, desc).then_some(field::Entry::Description)
-            }
+    pub fn new(policy: DuplicatePolicy) -> Self {
+        NaiveMIME {
+            single: SingleFields::new(policy),
+            ..Default::default()
         }
     }
 
+    /// Add a field. With `DuplicatePolicy::KeepLast`, the returned entry can
+    /// be one that was already returned for an earlier field, which is then
+    /// replaced by this one.
+    pub fn add_field(&mut self, f: NaiveField<'a>) -> Result<field::Entry, AddFieldErr> {
+        let (res, e) = match f {
+            NaiveField::Type(ctype) => (
+                self.single.set(&mut self.ctype, ctype, field::Entry::Type),
+                field::Entry::Type,
+            ),
+            NaiveField::TransferEncoding(enc) => (
+                self.single.set(
+                    &mut self.transfer_encoding,
+                    enc,
+                    field::Entry::TransferEncoding,
+                ),
+                field::Entry::TransferEncoding,
+            ),
+            NaiveField::ID(id) => (
+                self.single.set(&mut self.id, id, field::Entry::ID),
+                field::Entry::ID,
+            ),
+            NaiveField::Description(desc) => (
+                self.single
+                    .set(&mut self.description, desc, field::Entry::Description),
+                field::Entry::Description,
+            ),
+        };
+        match res {
+            Ok(()) => Ok(e),
+            Err(Discard::Field) => Err(AddFieldErr::Duplicate),
+            Err(Discard::All) => Err(AddFieldErr::Rejected(e)),
+        }
+    }
+
+    /// Records the field that the entry `e` (returned by `add_field`) comes
+    /// from, so that the diagnostics reported when interpreting the field are
+    /// attributed to it.
+    pub fn set_source(&mut self, e: field::Entry, f: DuplicateField<'a>) {
+        self.sources.retain(|(entry, _)| *entry != e);
+        self.sources.push((e, f));
+    }
+
     pub fn to_interpreted(self, default_type: DefaultType) -> AnyMIME<'a> {
-        let typ: AnyType = self
-            .ctype
-            .as_ref()
-            .map(NaiveType::to_type)
-            .unwrap_or(default_type.to_type());
+        let enter_field = |entry| {
+            self.sources
+                .iter()
+                .find(|(e, _)| *e == entry)
+                .map(|(_, f)| diagnostics::enter_recorded_field(f))
+        };
+        let typ: AnyType = match &self.ctype {
+            Some(ctype) => {
+                let _field = enter_field(field::Entry::Type);
+                ctype.to_type()
+            }
+            None => default_type.to_type(),
+        };
+        let _field = enter_field(field::Entry::TransferEncoding);
         let transfer_encoding = self.transfer_encoding.unwrap_or_default();
         let mut fields = CommonMIME {
             transfer_encoding,
@@ -4,7 +4,7 @@ use bounded_static::{IntoBoundedStatic, ToBoundedStatic, ToStatic};
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    combinator::{map, opt},
+    combinator::{consumed, map, opt},
     multi::many0,
     sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
     IResult,
@@ -12,6 +12,7 @@ use nom::{
 #[cfg(feature = "tracing")]
 use tracing::warn;
 
+use crate::diagnostics::{self, DiagnosticKind, Severity};
 #[cfg(feature = "arbitrary")]
 use crate::fuzz_eq::FuzzEq;
 use crate::i18n::ContainsUtf8;
@@ -20,7 +21,9 @@ use crate::text::charset::EmailCharset;
 use crate::text::misc_token::{mime_word, MIMEWord};
 use crate::text::quoted::{print_quoted, QuotedString};
 use crate::text::recovery::take_quoted_or_until;
-use crate::text::whitespace::cfws;
+use crate::text::whitespace::{
+    cfws, comments_in, print_comments, print_leading_comments, Comments,
+};
 use crate::text::words::{mime_atom, MIMEAtom};
 #[cfg(any(feature = "tracing-recover", feature = "tracing-unsupported"))]
 use crate::utils::bytes_to_trace_string;
@@ -55,12 +58,17 @@ pub fn recover_broken_type<'a>(
 ) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], (MIMEAtom<'a>, MIMEAtom<'a>)> {
     move |input: &[u8]| {
         map(delimited(opt(cfws), tag(broken_name), opt(cfws)), |_| {
-            #[cfg(feature = "tracing-recover")]
-            warn!(
-                "use of broken content-type {}, interpreted as {}/{}",
-                String::from_utf8_lossy(broken_name),
-                String::from_utf8_lossy(main),
-                String::from_utf8_lossy(sub)
+            diagnostics::diagnose!(
+                "tracing-recover",
+                (
+                    "use of broken content-type {}, interpreted as {}/{}",
+                    String::from_utf8_lossy(broken_name),
+                    String::from_utf8_lossy(main),
+                    String::from_utf8_lossy(sub)
+                ),
+                DiagnosticKind::Recovered("use of broken content-type"),
+                Severity::Warning,
+                None,
             );
             (MIMEAtom(main.into()), MIMEAtom(sub.into()))
         })(input)
@@ -88,12 +96,16 @@ impl<'a> Print for NaiveType<'a> {
 pub struct Parameter<'a> {
     pub name: MIMEAtom<'a>,
     pub value: MIMEWord<'a>,
+    #[cfg_attr(feature = "arbitrary", fuzz_eq(ignore))]
+    pub comments: Comments<'a>,
 }
 impl<'a> Print for Parameter<'a> {
     fn print(&self, fmt: &mut impl Formatter) {
+        print_leading_comments(fmt, &self.comments);
         self.name.print(fmt);
         fmt.write_bytes(b"=");
-        self.value.print(fmt)
+        self.value.print(fmt);
+        print_comments(fmt, &self.comments)
     }
 }
 
@@ -119,10 +131,14 @@ pub fn parameter_list(input: &[u8]) -> IResult<&[u8], Vec<Parameter<'_>>> {
         pair(
             opt(cfws),
             map(take_quoted_or_until(|c| c == b';'), |i| {
-                #[cfg(feature = "tracing-unsupported")]
                 if !i.is_empty() {
-                    warn!(input = %bytes_to_trace_string(i),
-                          "unsupported segment in parameter list");
+                    diagnostics::diagnose!(
+                        "tracing-unsupported",
+                        (input = %bytes_to_trace_string(i)),
+                        Unsupported("unsupported segment in parameter list"),
+                        Severity::Error,
+                        Some(i),
+                    );
                 }
                 i
             }),
@@ -141,16 +157,24 @@ pub fn parameter(input: &[u8]) -> IResult<&[u8], Parameter<'_>> {
     let separator = alt((
         tag("="),
         map(tag(":"), |i| {
-            #[cfg(feature = "tracing-recover")]
-            warn!(input = %bytes_to_trace_string(input),
-                  "non-compliant use of ':' instead of '=' in parameter");
+            diagnostics::diagnose!(
+                "tracing-recover",
+                (input = %bytes_to_trace_string(input)),
+                Recovered("non-compliant use of ':' instead of '=' in parameter"),
+                Severity::Warning,
+                None,
+            );
             i
         }),
     ));
 
     map(
-        tuple((mime_atom, separator, mime_word)),
-        |(name, _, value)| Parameter { name, value },
+        consumed(tuple((mime_atom, separator, mime_word))),
+        |(raw, (name, _, value))| Parameter {
+            name,
+            value,
+            comments: comments_in(raw),
+        },
     )(input)
 }
 
@@ -255,6 +279,7 @@ impl<'a> Multipart<'a> {
             Some(b) => params.push(Parameter {
                 name: MIMEAtom(b"boundary".into()),
                 value: MIMEWord::Quoted(QuotedString(vec![b.into()])).into_static(),
+                comments: Comments::default(),
             }),
             None => {
                 // XXX in this case there is no boundary parameter returned,
@@ -319,8 +344,13 @@ impl<'a> TryFrom<&NaiveType<'a>> for Multipart<'a> {
                     boundary = Some(s);
                 } else {
                     // drop any redundant "boundary" parameter that is not the first
-                    #[cfg(feature = "tracing-unsupported")]
-                    warn!(boundary = s, "dropping redundant boundary parameter")
+                    diagnostics::diagnose!(
+                        "tracing-unsupported",
+                        (boundary = s),
+                        Unsupported("dropping redundant boundary parameter"),
+                        Severity::Error,
+                        None,
+                    )
                 }
             } else {
                 other_params.push(param.clone())
@@ -496,6 +526,7 @@ impl<'a> Text<'a> {
         params.push(Parameter {
             name: MIMEAtom(b"charset".into()),
             value: MIMEWord::Quoted(QuotedString(vec![self.charset.as_str().into()])).into_static(),
+            comments: Comments::default(),
         });
         params
     }
@@ -551,8 +582,13 @@ impl<'a> From<&NaiveType<'a>> for Text<'a> {
                     charset = Some(EmailCharset::from(&value));
                 } else {
                     // drop any "charset" parameter that is not the first
-                    #[cfg(feature = "tracing-unsupported")]
-                    warn!(param = value, "dropping redundant charset parameter");
+                    diagnostics::diagnose!(
+                        "tracing-unsupported",
+                        (param = value),
+                        Unsupported("dropping redundant charset parameter"),
+                        Severity::Error,
+                        None,
+                    );
                 }
             } else {
                 other_params.push(param.clone())
@@ -657,6 +693,7 @@ mod tests {
     use super::*;
     use crate::text::charset::EmailCharset;
     use crate::text::quoted::QuotedString;
+    use crate::text::whitespace::Comment;
 
     #[test]
     fn test_parameter() {
@@ -667,6 +704,7 @@ mod tests {
                 Parameter {
                     name: MIMEAtom(b"charset"[..].into()),
                     value: MIMEWord::Atom(MIMEAtom(b"utf-8"[..].into())),
+                    comments: Comments::default(),
                 }
             )),
         );
@@ -677,6 +715,7 @@ mod tests {
                 Parameter {
                     name: MIMEAtom(b"charset"[..].into()),
                     value: MIMEWord::Quoted(QuotedString(vec!["utf-8"[..].into()])),
+                    comments: Comments::default(),
                 }
             )),
         );
@@ -695,6 +734,7 @@ mod tests {
                 other_params: vec![Parameter {
                     name: MIMEAtom(b"hello"[..].into()),
                     value: MIMEWord::Atom(MIMEAtom(b"yolo"[..].into())),
+                    comments: Comments::default(),
                 }],
             })
         );
@@ -724,6 +764,7 @@ mod tests {
                 other_params: vec![Parameter {
                     name: MIMEAtom(b"hello"[..].into()),
                     value: MIMEWord::Atom(MIMEAtom(b"yolo"[..].into())),
+                    comments: Comments::default(),
                 }],
             })
         );
@@ -741,6 +782,7 @@ mod tests {
                 other_params: vec![Parameter {
                     name: MIMEAtom(b"charset"[..].into()),
                     value: MIMEWord::Atom(MIMEAtom(b"UTF-8"[..].into())),
+                    comments: Comments::default(),
                 }],
             })
         );
@@ -802,6 +844,7 @@ mod tests {
                 params: vec![Parameter {
                     name: MIMEAtom(b"charset"[..].into()),
                     value: MIMEWord::Atom(MIMEAtom(b"us-ascii"[..].into())),
+                    comments: Comments::default(),
                 }],
             }
         );
@@ -816,6 +859,13 @@ mod tests {
                 Parameter {
                     name: MIMEAtom(b"charset"[..].into()),
                     value: MIMEWord::Atom(MIMEAtom(b"us-ascii"[..].into())),
+                    comments: Comments {
+                        before: vec![],
+                        after: vec![
+                            Comment(b"simple"[..].into()),
+                            Comment(b"Plain text"[..].into()),
+                        ],
+                    },
                 }
             ))
         );
@@ -831,6 +881,7 @@ mod tests {
                 vec![Parameter {
                     name: MIMEAtom(b"boundary"[..].into()),
                     value: MIMEWord::Quoted(QuotedString(vec!["festivus"[..].into()])),
+                    comments: Comments::default(),
                 }],
             ))
         );
@@ -843,10 +894,12 @@ mod tests {
                     Parameter {
                         name: MIMEAtom(b"charset"[..].into()),
                         value: MIMEWord::Atom(MIMEAtom(b"UTF-8"[..].into())),
+                        comments: Comments::default(),
                     },
                     Parameter {
                         name: MIMEAtom(b"format"[..].into()),
                         value: MIMEWord::Atom(MIMEAtom(b"flowed"[..].into())),
+                        comments: Comments::default(),
                     },
                 ],
             ))
@@ -862,10 +915,12 @@ mod tests {
                     Parameter {
                         name: MIMEAtom(b"boundary"[..].into()),
                         value: MIMEWord::Quoted(QuotedString(vec!["abc;def"[..].into()])),
+                        comments: Comments::default(),
                     },
                     Parameter {
                         name: MIMEAtom(b"foo"[..].into()),
                         value: MIMEWord::Atom(MIMEAtom(b"bar"[..].into())),
+                        comments: Comments::default(),
                     },
                 ],
             ))
@@ -882,6 +937,7 @@ mod tests {
                 vec![Parameter {
                     name: MIMEAtom(b"name".into()),
                     value: MIMEWord::Atom(MIMEAtom(b"threadTest.ml".into())),
+                    comments: Comments::default(),
                 },]
             ))
         );
@@ -895,10 +951,12 @@ mod tests {
                     Parameter {
                         name: MIMEAtom(b"charset".into()),
                         value: MIMEWord::Atom(MIMEAtom(b"UTF-8".into())),
+                        comments: Comments::default(),
                     },
                     Parameter {
                         name: MIMEAtom(b"foo".into()),
                         value: MIMEWord::Atom(MIMEAtom(b"bar".into())),
+                        comments: Comments::default(),
                     },
                 ]
             ))
@@ -919,14 +977,17 @@ mod tests {
                     Parameter {
                         name: MIMEAtom(b"name".into()),
                         value: MIMEWord::Quoted(QuotedString(vec!["calendar.ics".into()])),
+                        comments: Comments::default(),
                     },
                     Parameter {
                         name: MIMEAtom(b"method".into()),
                         value: MIMEWord::Atom(MIMEAtom(b"REQUEST".into())),
+                        comments: Comments::default(),
                     },
                     Parameter {
                         name: MIMEAtom(b"Content-Transfer-Encoding".into()),
                         value: MIMEWord::Atom(MIMEAtom(b"8bit".into())),
+                        comments: Comments::default(),
                     },
                 ]
             ))
@@ -940,10 +1001,12 @@ mod tests {
                     Parameter {
                         name: MIMEAtom(b"name".into()),
                         value: MIMEWord::Atom(MIMEAtom(b"threadTest.ml".into())),
+                        comments: Comments::default(),
                     },
                     Parameter {
                         name: MIMEAtom(b"baz".into()),
                         value: MIMEWord::Atom(MIMEAtom(b"qux".into())),
+                        comments: Comments::default(),
                     },
                 ]
             ))
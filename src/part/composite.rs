@@ -3,11 +3,10 @@ use arbitrary::Arbitrary;
 use bounded_static::ToStatic;
 use std::borrow::Cow;
 use std::fmt;
-#[cfg(feature = "tracing-recover")]
-use tracing::warn;
 #[cfg(feature = "tracing")]
 use tracing::{span, Level};
 
+use crate::diagnostics::{self, Severity};
 use crate::header;
 use crate::message;
 use crate::mime;
@@ -58,6 +57,15 @@ impl<'a> Arbitrary<'a> for Multipart<'a> {
 // the parser for `mime::MIME<_, Multipart>`.
 pub fn multipart<'a>(
     m: mime::MIME<'a, mime::r#type::Multipart<'a>>,
+) -> impl Fn(&'a [u8]) -> (&'a [u8], Multipart<'a>) {
+    multipart_with_policy(m, header::DuplicatePolicy::default())
+}
+
+/// Same as `multipart`, with the given policy for duplicated header fields in
+/// the parts.
+pub fn multipart_with_policy<'a>(
+    m: mime::MIME<'a, mime::r#type::Multipart<'a>>,
+    policy: header::DuplicatePolicy,
 ) -> impl Fn(&'a [u8]) -> (&'a [u8], Multipart<'a>) {
     let m = m.clone();
 
@@ -111,8 +119,11 @@ pub fn multipart<'a>(
 
             // parse mime headers, otherwise pick default mime
             let (input_body, fields_raw) = header::header_kv(input);
-            let NaiveEntityFields { entries, mime } =
-                fields_raw.into_iter().collect::<NaiveEntityFields>();
+            let NaiveEntityFields {
+                entries,
+                mime,
+                duplicates,
+            } = NaiveEntityFields::new(fields_raw, policy);
 
             // interpret mime according to context
             let mime = match m.ctype.subtype {
@@ -128,9 +139,10 @@ pub fn multipart<'a>(
             // parse mime body
             // XXX this can be an (indirect) recursive call;
             // -> risk of stack overflow
-            let mime_body = part::part_body(mime)(rpart);
+            let mime_body = part::part_body_with_policy(mime, policy)(rpart);
             mparts.push(AnyPart {
                 entries,
+                duplicates,
                 mime_body,
                 raw: input[0..input.len() - input_next.len()].into(),
                 raw_headers: input[0..input.len() - input_body.len()].into(),
@@ -142,8 +154,12 @@ pub fn multipart<'a>(
         // RFC2064 specifies that a multipart must have at least one child part.
         // If there is no child part, insert an empty part as recovery strategy.
         if multipart.children.is_empty() {
-            #[cfg(feature = "tracing-recover")]
-            warn!("multipart containing zero parts");
+            diagnostics::diagnose!(
+                "tracing-recover",
+                Recovered("multipart containing zero parts"),
+                Severity::Warning,
+                None,
+            );
             multipart.children.push(AnyPart::default());
         }
 
@@ -236,13 +252,21 @@ impl<'a> Arbitrary<'a> for Message<'a> {
 /// This function always consumes its entire input.
 pub fn message<'a>(
     m: mime::MIME<'a, mime::r#type::Message<'a>>,
+) -> impl Fn(&'a [u8]) -> Message<'a> {
+    message_with_policy(m, header::DuplicatePolicy::default())
+}
+
+/// Same as `message`, with the given policy for duplicated header fields.
+pub fn message_with_policy<'a>(
+    m: mime::MIME<'a, mime::r#type::Message<'a>>,
+    policy: header::DuplicatePolicy,
 ) -> impl Fn(&'a [u8]) -> Message<'a> {
     move |input: &[u8]| {
         #[cfg(feature = "tracing")]
         let _span = span!(Level::DEBUG, "part::composite::message", ?m).entered();
 
         // parse the input as a toplevel message
-        let msg = message::message(input);
+        let msg = message::message_with_policy(input, policy);
         let mut msg_mime = m.clone();
         // If the headers contain non-ascii UTF8 and if this is a
         // message/RFC822, promote the message outer MIME to message/global
@@ -369,6 +393,7 @@ This is the epilogue. It is also to be ignored.
                          }),
                          raw: RawInput::between(input, b"\nThis is implicitly", b"NOT end with a linebreak."),
                          raw_headers: b"\n".into(),
+                         duplicates: vec![],
                      },
                      AnyPart {
                          entries: vec![EntityEntry::MIME { e: Entry::Type, raw_body: b" text/plain; charset=us-ascii".into() }],
@@ -386,6 +411,7 @@ This is the epilogue. It is also to be ignored.
                          }),
                          raw: RawInput::between(input, b"Content-type", b"DOES end with a linebreak.\n"),
                          raw_headers: b"Content-type: text/plain; charset=us-ascii\n\n".into(),
+                         duplicates: vec![],
                      },
                  ],
                  raw_body: input.into(),
@@ -466,6 +492,7 @@ This is implicitly typed plain US-ASCII text.
                                         b"terminator"
                                     ),
                                     raw_headers: b"\n".into(),
+                                    duplicates: vec![],
                                 },],
                                 raw_body: RawInput::between(
                                     input,
@@ -477,6 +504,7 @@ This is implicitly typed plain US-ASCII text.
                             raw_headers:
                                 b"Content-Type: multipart/mixed; boundary=\"inner boundary\"\n\n"
                                     .into(),
+                            duplicates: vec![],
                         },
                         AnyPart {
                             entries: vec![],
@@ -490,6 +518,7 @@ This is implicitly typed plain US-ASCII text.
                             }),
                             raw: b"\nThis is implicitly typed plain US-ASCII text.".into(),
                             raw_headers: b"\n".into(),
+                            duplicates: vec![],
                         },
                     ],
                     raw_body: input.into(),
@@ -541,6 +570,7 @@ leftovers";
                         }),
                         raw: b"\nPart text".into(),
                         raw_headers: b"\n".into(),
+                        duplicates: vec![],
                     },],
                     raw_body: b"\n--boundary\n\nPart text".into(),
                 },
@@ -585,6 +615,7 @@ leftovers";
                         }),
                         raw: b"\n\r".into(),
                         raw_headers: b"\n".into(),
+                        duplicates: vec![],
                     },],
                     raw_body: input.into(),
                 },
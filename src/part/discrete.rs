@@ -4,8 +4,16 @@ use bounded_static::ToStatic;
 use std::borrow::Cow;
 use std::fmt;
 
-use crate::mime;
+use crate::mime::{
+    self,
+    mechanism::{encode_base64, encode_quoted_printable, Mechanism},
+};
+use crate::print;
 use crate::raw_input::RawInput;
+#[cfg(feature = "charset-detection")]
+use crate::text::charset;
+use crate::text::charset::EmailCharset;
+use crate::text::html;
 #[cfg(feature = "arbitrary")]
 use crate::{arbitrary_utils::arbitrary_part_body, fuzz_eq::FuzzEq};
 
@@ -28,6 +36,118 @@ impl<'a> fmt::Debug for Text<'a> {
     }
 }
 
+impl<'a> Text<'a> {
+    /// Builds a text part of the given subtype, whose body is `text` encoded
+    /// in `charset`.
+    ///
+    /// If `text` contains characters that cannot be represented in `charset`,
+    /// `fallback` is used instead, then UTF-8 if `fallback` cannot represent
+    /// them either. The charset parameter is set to the charset that was used.
+    ///
+    /// Line breaks are normalized to CRLF. The content transfer encoding is
+    /// 7bit if the encoded body is ASCII with lines of at most 998 octets,
+    /// otherwise the shortest of quoted-printable and base64.
+    pub fn encode(
+        text: &str,
+        subtype: mime::r#type::TextSubtype,
+        charset: EmailCharset,
+        fallback: EmailCharset,
+    ) -> Self {
+        let text = text.replace("\r\n", "\n").replace('\n', "\r\n");
+        let (charset, body) = [charset, fallback]
+            .into_iter()
+            .find_map(|c| Some((c.clone(), c.encode(&text)?.into_owned())))
+            .unwrap_or_else(|| (EmailCharset::utf8(), text.into_bytes()));
+
+        let (transfer_encoding, body) = if body.is_ascii() && !print::has_long_line(&body, 0) {
+            (Mechanism::_7Bit, body)
+        } else {
+            let qp = encode_quoted_printable(&body, true);
+            let b64 = encode_base64(&body);
+            if qp.len() <= b64.len() {
+                (Mechanism::QuotedPrintable, qp)
+            } else {
+                (Mechanism::Base64, b64)
+            }
+        };
+
+        Self {
+            mime: mime::MIME {
+                ctype: mime::r#type::Text {
+                    subtype,
+                    charset,
+                    other_params: vec![],
+                },
+                fields: mime::CommonMIME {
+                    transfer_encoding,
+                    ..Default::default()
+                },
+            },
+            body: Cow::Owned(body),
+            raw_body: RawInput::none(),
+        }
+    }
+
+    /// Returns the body of the part, with its content transfer encoding
+    /// decoded.
+    pub fn decoded_body(&self) -> Cow<'_, [u8]> {
+        self.mime.fields.transfer_encoding.decode(&self.body)
+    }
+
+    /// Decides which charset to decode the body of an HTML part with, given
+    /// its Content-Type charset and `<meta>` declaration. See
+    /// [`html::html_charset`]. Returns `None` if the part is not text/html.
+    pub fn html_charset(&self) -> Option<html::HtmlCharset> {
+        if self.mime.ctype.subtype != mime::r#type::TextSubtype::Html {
+            return None;
+        }
+        Some(html::html_charset(
+            &self.mime.ctype.charset,
+            &self.decoded_body(),
+        ))
+    }
+
+    /// Makes the Content-Type charset and the `<meta>` declaration of an HTML
+    /// part agree, by rewriting the one that is not trusted (see
+    /// [`Text::html_charset`]) to match the other.
+    ///
+    /// If the Content-Type charset is trusted, the `<meta>` declaration is
+    /// rewritten and the body is encoded again with the content transfer
+    /// encoding of the part. Returns the charset of the part, or `None` (and
+    /// leaves the part unchanged) if it is not text/html.
+    pub fn reconcile_html_charset(&mut self) -> Option<html::HtmlCharset> {
+        let decision = self.html_charset()?;
+        match decision.source {
+            html::CharsetSource::Bom | html::CharsetSource::Meta => {
+                self.mime.ctype.charset = decision.charset.clone();
+            }
+            html::CharsetSource::ContentType => {
+                if decision
+                    .meta
+                    .as_ref()
+                    .is_some_and(|m| *m != decision.charset)
+                {
+                    let body = html::set_meta_charset(&self.decoded_body(), &decision.charset);
+                    if let Some(body) = body {
+                        let encoding = &self.mime.fields.transfer_encoding;
+                        self.body = Cow::Owned(encoding.encode(&body, true).into_owned());
+                        self.raw_body = RawInput::none();
+                    }
+                }
+            }
+        }
+        Some(decision)
+    }
+
+    /// Guesses the charset of the body of the part, regardless of the charset
+    /// it declares. See [`charset::detect`].
+    #[cfg(feature = "charset-detection")]
+    pub fn detect_charset(&self) -> charset::CharsetGuess {
+        let html = self.mime.ctype.subtype == mime::r#type::TextSubtype::Html;
+        charset::detect(&self.decoded_body(), html)
+    }
+}
+
 #[cfg(feature = "arbitrary")]
 impl<'a> Arbitrary<'a> for Text<'a> {
     fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
@@ -68,3 +188,64 @@ impl<'a> Arbitrary<'a> for Binary<'a> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mime::r#type::TextSubtype;
+
+    #[test]
+    fn test_text_encode() {
+        let t = Text::encode(
+            "Hello\nworld",
+            TextSubtype::Plain,
+            EmailCharset::utf8(),
+            EmailCharset::utf8(),
+        );
+        assert_eq!(t.mime.ctype.charset, EmailCharset::utf8());
+        assert_eq!(t.mime.fields.transfer_encoding, Mechanism::_7Bit);
+        assert_eq!(&t.body[..], b"Hello\r\nworld");
+
+        // ISO-2022-JP is a 7-bit charset
+        let t = Text::encode(
+            "日本語",
+            TextSubtype::Plain,
+            EmailCharset::from("iso-2022-jp"),
+            EmailCharset::utf8(),
+        );
+        assert_eq!(t.mime.ctype.charset, EmailCharset::from("iso-2022-jp"));
+        assert_eq!(t.mime.fields.transfer_encoding, Mechanism::_7Bit);
+        assert_eq!(&t.body[..], b"\x1b$BF|K\\8l\x1b(B");
+
+        let t = Text::encode(
+            "Café",
+            TextSubtype::Html,
+            EmailCharset::from("iso-8859-1"),
+            EmailCharset::utf8(),
+        );
+        assert_eq!(t.mime.ctype.charset, EmailCharset::from("iso-8859-1"));
+        assert_eq!(t.mime.fields.transfer_encoding, Mechanism::QuotedPrintable);
+        assert_eq!(&t.body[..], b"Caf=E9");
+        assert_eq!(&t.decoded_body()[..], b"Caf\xe9");
+
+        // unrepresentable characters: use the fallback, then UTF-8
+        let t = Text::encode(
+            "Привет",
+            TextSubtype::Plain,
+            EmailCharset::from("iso-8859-1"),
+            EmailCharset::from("koi8-r"),
+        );
+        assert_eq!(t.mime.ctype.charset, EmailCharset::from("koi8-r"));
+        assert_eq!(t.mime.fields.transfer_encoding, Mechanism::Base64);
+        assert_eq!(t.decoded_body(), &b"\xf0\xd2\xc9\xd7\xc5\xd4"[..]);
+
+        let t = Text::encode(
+            "Привет 日本語",
+            TextSubtype::Plain,
+            EmailCharset::from("iso-8859-1"),
+            EmailCharset::from("koi8-r"),
+        );
+        assert_eq!(t.mime.ctype.charset, EmailCharset::utf8());
+        assert_eq!(t.decoded_body(), "Привет 日本語".as_bytes());
+    }
+}
@@ -2,6 +2,7 @@ use bounded_static::ToStatic;
 #[cfg(feature = "tracing")]
 use tracing::warn;
 
+use crate::diagnostics::{self, DiagnosticKind, Severity};
 #[cfg(feature = "arbitrary")]
 use crate::fuzz_eq::FuzzEq;
 use crate::header;
@@ -10,7 +11,8 @@ use crate::print::{Formatter, Print};
 use crate::raw_input::RawInput;
 
 /// Header field of a generic MIME entity (a MIME entity that is not a toplevel
-/// message). Is either a MIME-defined field or an unstructured field.
+/// message). Is either a MIME-defined field, an unstructured field, or a field
+/// whose body could not be parsed.
 #[derive(Clone, Debug, PartialEq, ToStatic)]
 #[cfg_attr(feature = "arbitrary", derive(FuzzEq))]
 pub enum EntityField<'a> {
@@ -19,6 +21,7 @@ pub enum EntityField<'a> {
         raw_body: RawInput<'a>,
     },
     Unstructured(header::Unstructured<'a>),
+    Invalid(header::Invalid<'a>),
 }
 
 impl<'a> EntityField<'a> {
@@ -26,6 +29,7 @@ impl<'a> EntityField<'a> {
         match self {
             EntityField::MIME { f, .. } => f.raw_name(),
             EntityField::Unstructured(u) => u.name.clone(),
+            EntityField::Invalid(i) => i.name.clone(),
         }
     }
 
@@ -33,6 +37,7 @@ impl<'a> EntityField<'a> {
         match self {
             EntityField::MIME { raw_body, .. } => raw_body.clone(),
             EntityField::Unstructured(u) => u.raw_body.clone(),
+            EntityField::Invalid(i) => i.raw_body.clone(),
         }
     }
 }
@@ -42,6 +47,7 @@ impl<'a> Print for EntityField<'a> {
         match self {
             EntityField::MIME { f, .. } => f.print(fmt),
             EntityField::Unstructured(u) => u.print(fmt),
+            EntityField::Invalid(i) => i.print(fmt),
         }
     }
 }
@@ -55,6 +61,7 @@ pub enum EntityEntry<'a> {
         raw_body: RawInput<'a>,
     },
     Unstructured(header::Unstructured<'a>),
+    Invalid(header::Invalid<'a>),
 }
 
 /// Collects fields and entries for a generic MIME entity. Only for eml-codec's
@@ -64,34 +71,92 @@ pub enum EntityEntry<'a> {
 pub(crate) struct NaiveEntityFields<'a> {
     pub mime: mime::NaiveMIME<'a>,
     pub entries: Vec<EntityEntry<'a>>,
+    #[cfg_attr(feature = "arbitrary", fuzz_eq(ignore))]
+    pub duplicates: Vec<header::DuplicateField<'a>>,
 }
 
 impl<'a> FromIterator<header::FieldRaw<'a>> for NaiveEntityFields<'a> {
+    fn from_iter<I: IntoIterator<Item = header::FieldRaw<'a>>>(it: I) -> Self {
+        Self::new(it, header::DuplicatePolicy::default())
+    }
+}
+
+impl<'a> NaiveEntityFields<'a> {
     #[cfg_attr(
         feature = "tracing",
-        tracing::instrument(name = "EntityFields::from_iter", skip(it))
+        tracing::instrument(name = "EntityFields::new", skip(it))
     )]
-    fn from_iter<I: IntoIterator<Item = header::FieldRaw<'a>>>(it: I) -> Self {
-        let mut e: NaiveEntityFields<'a> = Default::default();
+    pub fn new<I: IntoIterator<Item = header::FieldRaw<'a>>>(
+        it: I,
+        policy: header::DuplicatePolicy,
+    ) -> Self {
+        let mut mime = mime::NaiveMIME::new(policy);
+        // entries, along with the field they come from
+        let mut entries: Vec<(EntityEntry<'a>, header::DuplicateField<'a>)> = vec![];
+        let mut duplicates = vec![];
         for f in it {
+            let _field = diagnostics::enter_field(&f);
             match mime::field::NaiveField::try_from(&f) {
                 Ok(mimef) => {
-                    if let Some(entry) = e.mime.add_field(mimef) {
-                        e.entries.push(EntityEntry::MIME {
-                            e: entry,
-                            raw_body: f.body.into(),
-                        })
-                    } else {
-                        // otherwise drop the field
-                        #[cfg(feature = "tracing-recover")]
-                        warn!(field = ?f, "dropping conflicting MIME field");
+                    match mime.add_field(mimef) {
+                        Ok(entry) => {
+                            // with `KeepLast`, this field replaces an earlier one
+                            header::discard_entry(
+                                &mut entries,
+                                &mut duplicates,
+                                |e| matches!(e, EntityEntry::MIME { e, .. } if *e == entry),
+                            );
+                            mime.set_source(entry, (&f).into());
+                            entries.push((
+                                EntityEntry::MIME {
+                                    e: entry,
+                                    raw_body: f.body.into(),
+                                },
+                                (&f).into(),
+                            ))
+                        }
+                        Err(mime::AddFieldErr::Duplicate) => {
+                            diagnostics::diagnose!(
+                                "tracing-recover",
+                                (field = ?f, "discarding duplicate MIME field"),
+                                DiagnosticKind::DuplicateField,
+                                Severity::Warning,
+                                None,
+                            );
+                            duplicates.push((&f).into());
+                        }
+                        Err(mime::AddFieldErr::Rejected(entry)) => {
+                            diagnostics::diagnose!(
+                                "tracing-recover",
+                                (field = ?f, "rejecting duplicated MIME field"),
+                                DiagnosticKind::DuplicateField,
+                                Severity::Warning,
+                                None,
+                            );
+                            header::discard_entry(
+                                &mut entries,
+                                &mut duplicates,
+                                |e| matches!(e, EntityEntry::MIME { e, .. } if *e == entry),
+                            );
+                            duplicates.push((&f).into());
+                        }
                     }
                     continue;
                 }
                 Err(mime::field::InvalidField::Body) => {
-                    // this is a MIME field but its body is invalid; drop it.
-                    #[cfg(feature = "tracing-unsupported")]
-                    warn!(field = ?f, "dropping MIME field with an invalid body");
+                    // this is a MIME field but its body is invalid; keep it
+                    // as an invalid field.
+                    diagnostics::diagnose!(
+                        "tracing-unsupported",
+                        (field = ?f, "MIME field with an invalid body"),
+                        DiagnosticKind::InvalidField,
+                        Severity::Error,
+                        None,
+                    );
+                    entries.push((
+                        EntityEntry::Invalid(header::Invalid::from_raw(&f)),
+                        (&f).into(),
+                    ));
                     continue;
                 }
                 Err(mime::field::InvalidField::Name) => {
@@ -100,13 +165,27 @@ impl<'a> FromIterator<header::FieldRaw<'a>> for NaiveEntityFields<'a> {
             };
 
             if let Some(u) = header::Unstructured::from_raw(&f) {
-                e.entries.push(EntityEntry::Unstructured(u));
+                entries.push((EntityEntry::Unstructured(u), (&f).into()));
             } else {
-                // otherwise drop the field
-                #[cfg(feature = "tracing-unsupported")]
-                warn!(field = ?f, "dropping field which cannot be parsed as unstructured");
+                // otherwise keep it as an invalid field
+                diagnostics::diagnose!(
+                    "tracing-unsupported",
+                    (field = ?f, "field which cannot be parsed as unstructured"),
+                    DiagnosticKind::InvalidField,
+                    Severity::Error,
+                    None,
+                );
+                entries.push((
+                    EntityEntry::Invalid(header::Invalid::from_raw(&f)),
+                    (&f).into(),
+                ));
             }
         }
-        e
+
+        NaiveEntityFields {
+            mime,
+            entries: entries.into_iter().map(|(e, _)| e).collect(),
+            duplicates,
+        }
     }
 }
@@ -7,12 +7,15 @@ pub mod discrete;
 /// Representation of all headers in a MIME entity
 pub mod field;
 
-use crate::mime::{AnyMIME, MIME};
+use crate::diagnostics::{self, DiagnosticKind, Severity};
+use crate::header::{self, DuplicateField, DuplicatePolicy};
+use crate::mime::mechanism::{encode_quoted_printable, Mechanism};
+use crate::mime::{self, AnyMIME, MIME};
 use crate::part::{
-    composite::{message, multipart, Message, Multipart},
+    composite::{message_with_policy, multipart_with_policy, Message, Multipart},
     discrete::{Binary, Text},
 };
-use crate::print::{print_seq, Formatter, Print};
+use crate::print::{has_long_line, print_seq, FmtError, Formatter, Print};
 use crate::raw_input::RawInput;
 #[cfg(feature = "tracing-unsupported")]
 use crate::utils::bytes_to_trace_string;
@@ -20,14 +23,11 @@ use crate::utils::bytes_to_trace_string;
 use crate::{
     arbitrary_utils::{arbitrary_shuffle, arbitrary_vec_where},
     fuzz_eq::FuzzEq,
-    header, mime,
 };
 #[cfg(feature = "arbitrary")]
 use arbitrary::Arbitrary;
 use bounded_static::ToStatic;
 use std::borrow::Cow;
-#[cfg(feature = "tracing-unsupported")]
-use tracing::warn;
 
 #[derive(Clone, Debug, PartialEq, ToStatic)]
 #[cfg_attr(feature = "arbitrary", derive(FuzzEq))]
@@ -41,6 +41,10 @@ pub struct AnyPart<'a> {
     //   no optional fields set to `None`.)
     // Invariant: `fields` must contain no duplicates.
     pub entries: Vec<field::EntityEntry<'a>>,
+    // Header fields discarded by the `DuplicatePolicy` used when parsing. They
+    // are not printed.
+    #[cfg_attr(feature = "arbitrary", fuzz_eq(ignore))]
+    pub duplicates: Vec<DuplicateField<'a>>,
     pub mime_body: MimeBody<'a>,
     pub raw: RawInput<'a>,
     pub raw_headers: RawInput<'a>,
@@ -62,6 +66,7 @@ impl<'a> AnyPart<'a> {
                     }
                 }
                 field::EntityEntry::Unstructured(u) => field::EntityField::Unstructured(u.clone()),
+                field::EntityEntry::Invalid(i) => field::EntityField::Invalid(i.clone()),
             };
             v.push(field);
         }
@@ -73,6 +78,7 @@ impl Default for AnyPart<'static> {
     fn default() -> Self {
         Self {
             entries: vec![],
+            duplicates: vec![],
             mime_body: MimeBody::Txt(discrete::Text {
                 mime: MIME {
                     ctype: Default::default(),
@@ -108,6 +114,7 @@ impl<'a> Arbitrary<'a> for AnyPart<'a> {
         arbitrary_shuffle(u, &mut entries)?;
         Ok(AnyPart {
             entries,
+            duplicates: vec![],
             mime_body,
             raw: RawInput::none(),
             raw_headers: RawInput::none(),
@@ -164,6 +171,26 @@ impl<'a> MimeBody<'a> {
             Self::Bin(v) => v.raw_body.clone(),
         }
     }
+    // 7bit and 8bit bodies must not have lines longer than 998 octets (RFC
+    // 5322, section 2.1.1). If this body does, returns it re-encoded as
+    // quoted-printable or, if `fmt` does not re-encode such bodies, reports
+    // it.
+    pub(crate) fn reencode_long_lines(&self, fmt: &mut impl Formatter) -> Option<Vec<u8>> {
+        let (encoding, body, text) = match self {
+            Self::Txt(t) => (&t.mime.fields.transfer_encoding, &t.body, true),
+            Self::Bin(b) => (&b.mime.fields.transfer_encoding, &b.body, false),
+            Self::Mult(_) | Self::Msg(_) => return None,
+        };
+        if !matches!(encoding, Mechanism::_7Bit | Mechanism::_8Bit) || !has_long_line(body, 0) {
+            return None;
+        }
+        if !fmt.reencode_long_lines() {
+            fmt.report(FmtError::BodyLineTooLong);
+            return None;
+        }
+        Some(encode_quoted_printable(body, text))
+    }
+
     pub fn print_body(&self, fmt: &mut impl Formatter) {
         match &self {
             MimeBody::Mult(multipart) => {
@@ -182,8 +209,112 @@ impl<'a> MimeBody<'a> {
                 fmt.pop_boundary();
             }
             MimeBody::Msg(message) => message.child.print(fmt),
-            MimeBody::Txt(text) => fmt.write_bytes(&text.body),
-            MimeBody::Bin(binary) => fmt.write_bytes(&binary.body),
+            MimeBody::Txt(text) => self.write_leaf_body(fmt, &text.body),
+            MimeBody::Bin(binary) => self.write_leaf_body(fmt, &binary.body),
+        }
+    }
+
+    // Bodies using the binary transfer encoding are not made of lines: their
+    // line endings must be kept whatever the output line ending style.
+    fn write_leaf_body(&self, fmt: &mut impl Formatter, body: &[u8]) {
+        if self.is_binary_encoded() {
+            fmt.write_binary(body)
+        } else {
+            fmt.write_bytes(body)
+        }
+    }
+
+    fn is_binary_encoded(&self) -> bool {
+        match self {
+            Self::Txt(t) => t.mime.fields.transfer_encoding == Mechanism::Binary,
+            Self::Bin(b) => b.mime.fields.transfer_encoding == Mechanism::Binary,
+            Self::Mult(_) | Self::Msg(_) => false,
+        }
+    }
+
+    // Whether this body or one of its parts uses the binary transfer encoding,
+    // in which case it cannot be copied from the input as a whole.
+    pub(crate) fn has_binary_part(&self) -> bool {
+        match self {
+            Self::Mult(m) => m.children.iter().any(|c| c.mime_body.has_binary_part()),
+            Self::Msg(m) => m.child.mime_body.has_binary_part(),
+            Self::Txt(_) | Self::Bin(_) => self.is_binary_encoded(),
+        }
+    }
+
+    // Prints the body for minimal-diff printing (see
+    // `message::Message::print_diff`). `type_printed` indicates whether the
+    // `Content-Type` field of the part was re-serialized; for a multipart, this
+    // means that a new boundary was registered on `fmt` and must be used.
+    pub(crate) fn print_body_diff(
+        &self,
+        fmt: &mut impl Formatter,
+        input: &[u8],
+        original: &Self,
+        type_printed: bool,
+    ) {
+        let new_boundary = type_printed && matches!(self, MimeBody::Mult(_));
+        if self == original && !new_boundary {
+            match self.raw_body().span_in(input) {
+                Some(span) if !self.has_binary_part() => {
+                    fmt.write_bytes(&input[span]);
+                    return;
+                }
+                Some(span) if self.is_binary_encoded() => {
+                    fmt.write_binary(&input[span]);
+                    return;
+                }
+                _ => (),
+            }
+        }
+        match self {
+            MimeBody::Mult(multipart) => {
+                let original_children = match original {
+                    MimeBody::Mult(o) => &o.children[..],
+                    _ => &[],
+                };
+                // keep the boundary of the input if the `Content-Type` field
+                // was copied from it
+                let boundary = match &multipart.mime.ctype.boundary {
+                    Some(b) if !new_boundary => Some(b.as_bytes()),
+                    _ => None,
+                };
+                let write_boundary = |fmt: &mut _| match boundary {
+                    Some(b) => Formatter::write_bytes(fmt, b),
+                    None => Formatter::write_current_boundary(fmt),
+                };
+                if !multipart.preamble.is_empty() {
+                    fmt.write_bytes(&multipart.preamble);
+                    fmt.write_crlf();
+                }
+                for child in &multipart.children {
+                    fmt.write_bytes(b"--");
+                    write_boundary(fmt);
+                    fmt.write_crlf();
+                    // parts are identified by their raw input
+                    match original_children
+                        .iter()
+                        .find(|o| child.raw.0.is_some() && o.raw == child.raw)
+                    {
+                        Some(o) => child.print_diff_in(fmt, input, o),
+                        None => child.print(fmt),
+                    }
+                    fmt.write_crlf();
+                }
+                fmt.write_bytes(b"--");
+                write_boundary(fmt);
+                fmt.write_bytes(b"--");
+                fmt.write_crlf();
+                fmt.write_bytes(&multipart.epilogue);
+                if boundary.is_none() {
+                    fmt.pop_boundary();
+                }
+            }
+            MimeBody::Msg(message) => match original {
+                MimeBody::Msg(o) => message.child.print_diff_in(fmt, input, &o.child),
+                _ => message.child.print(fmt),
+            },
+            MimeBody::Txt(_) | MimeBody::Bin(_) => self.print_body(fmt),
         }
     }
 }
@@ -200,14 +331,94 @@ impl<'a> From<Message<'a>> for MimeBody<'a> {
 
 impl<'a> Print for AnyPart<'a> {
     fn print(&self, fmt: &mut impl Formatter) {
+        let reencoded = self.mime_body.reencode_long_lines(fmt);
+        let mut fields = self.field_list();
+        if reencoded.is_some() {
+            fields.retain(|f| {
+                !matches!(
+                    f,
+                    field::EntityField::MIME {
+                        f: mime::field::Field::TransferEncoding(_),
+                        ..
+                    }
+                )
+            });
+            fields.push(field::EntityField::MIME {
+                f: mime::field::Field::TransferEncoding(Mechanism::QuotedPrintable),
+                raw_body: RawInput::none(),
+            });
+        }
         fmt.begin_line_folding();
-        print_seq(fmt, &self.field_list(), |_| ());
+        print_seq(fmt, &fields, |_| ());
         fmt.end_line_folding();
         fmt.write_crlf();
-        self.mime_body.print_body(fmt);
+        match reencoded {
+            Some(body) => fmt.write_bytes(&body),
+            None => self.mime_body.print_body(fmt),
+        }
     }
 }
 
+impl<'a> AnyPart<'a> {
+    // Minimal-diff printing (see `message::Message::print_diff`), where
+    // `input` is the input of the toplevel message.
+    pub(crate) fn print_diff_in(&self, fmt: &mut impl Formatter, input: &[u8], original: &Self) {
+        if self == original && !self.mime_body.has_binary_part() {
+            if let Some(span) = self.raw.span_in(input) {
+                fmt.write_bytes(&input[span]);
+                return;
+            }
+        }
+        let type_printed = print_headers_diff(
+            fmt,
+            input,
+            &self.field_list(),
+            &original.field_list(),
+            (self.duplicates == original.duplicates).then_some(&self.raw_headers),
+            |f| f.raw_body(),
+            |f| {
+                matches!(
+                    f,
+                    field::EntityField::MIME {
+                        f: mime::field::Field::Type(_),
+                        ..
+                    }
+                )
+            },
+        );
+        self.mime_body
+            .print_body_diff(fmt, input, &original.mime_body, type_printed);
+    }
+}
+
+// Prints a header section for minimal-diff printing (see
+// `message::Message::print_diff`). The whole section is copied from `input`
+// if its fields are unchanged and `raw_headers` is provided, otherwise fields
+// are copied or printed one by one. Returns whether a field matching
+// `is_type` (the `Content-Type` field) was printed rather than copied.
+pub(crate) fn print_headers_diff<F: Print + PartialEq>(
+    fmt: &mut impl Formatter,
+    input: &[u8],
+    fields: &[F],
+    original: &[F],
+    raw_headers: Option<&RawInput>,
+    raw_body: impl Fn(&F) -> RawInput,
+    is_type: impl Fn(&F) -> bool,
+) -> bool {
+    if fields == original {
+        if let Some(span) = raw_headers.and_then(|r| r.span_in(input)) {
+            fmt.write_bytes(&input[span]);
+            return false;
+        }
+    }
+    let copied = header::print_fields_diff(fmt, input, fields, original, raw_body);
+    fmt.write_crlf();
+    fields
+        .iter()
+        .zip(copied)
+        .any(|(f, copied)| is_type(f) && !copied)
+}
+
 /// Parse any type of part.
 ///
 /// This function always consumes the whole input.
@@ -217,18 +428,31 @@ impl<'a> Print for AnyPart<'a> {
 /// Multiparts are a bit special as they have a clearly delimited beginning
 /// and end contrary to all the other parts that are going up to the end of the buffer
 pub fn part_body<'a>(m: AnyMIME<'a>) -> impl FnOnce(&'a [u8]) -> MimeBody<'a> {
+    part_body_with_policy(m, DuplicatePolicy::default())
+}
+
+/// Same as `part_body`, with the given policy for duplicated header fields in
+/// nested parts and messages.
+pub fn part_body_with_policy<'a>(
+    m: AnyMIME<'a>,
+    policy: DuplicatePolicy,
+) -> impl FnOnce(&'a [u8]) -> MimeBody<'a> {
     move |input| {
         let part = match m {
             AnyMIME::Mult(a) => {
-                let (_rest, part) = multipart(a)(input);
-                #[cfg(feature = "tracing-unsupported")]
-                if !_rest.is_empty() {
-                    warn!(rest = %bytes_to_trace_string(_rest),
-                          "leftover input after multipart parsing")
+                let (rest, part) = multipart_with_policy(a, policy)(input);
+                if !rest.is_empty() {
+                    diagnostics::diagnose!(
+                        "tracing-unsupported",
+                        (rest = %bytes_to_trace_string(rest), "leftover input after multipart parsing"),
+                        DiagnosticKind::TrailingData,
+                        Severity::Error,
+                        Some(rest),
+                    )
                 }
                 part.into()
             }
-            AnyMIME::Msg(a) => message(a)(input).into(),
+            AnyMIME::Msg(a) => message_with_policy(a, policy)(input).into(),
             AnyMIME::Txt(a) => MimeBody::Txt(Text {
                 mime: a,
                 body: Cow::Borrowed(input),
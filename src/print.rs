@@ -4,6 +4,8 @@ use rand::{Rng, SeedableRng};
 use crate::text::ascii;
 pub use eml_codec_derives::ToStringFromPrint;
 use rand_chacha::ChaCha20Rng as RNG;
+use std::io::{self, Write};
+use std::ops::Range;
 
 // NOTE regarding line-folding and UTF-8 (RFC6532).
 //
@@ -22,8 +24,6 @@ use rand_chacha::ChaCha20Rng as RNG;
 // interpretation of "character", and is easier than e.g. performing unicode
 // segmentation to count text length in number of grapheme clusters.
 
-// TODO: provide streaming printing
-
 pub trait Print {
     fn print(&self, fmt: &mut impl Formatter);
 }
@@ -114,11 +114,9 @@ impl<T: Print> Print for &T {
 ///
 /// Note that the line limit (if any) is determined by each Formatter
 /// implementation.
+///
+/// `CheckedFmt` can be used to check that these requirements are obeyed.
 pub trait Formatter {
-    // XXX could we provide more safety to ensure that callers of a Formatter
-    // obey the requirements above, instead of panicking or being silently
-    // incorrect?
-
     /// Switches the `Formatter` mode to "line folding". The `Formatter`
     /// must be currently in "direct" mode.
     fn begin_line_folding(&mut self);
@@ -150,6 +148,15 @@ pub trait Formatter {
     /// It is fine for `buf` to include whitespace characters.
     fn write_bytes(&mut self, buf: &[u8]);
 
+    /// Write bytes from `buf` that are not line-oriented, i.e. a body using
+    /// the binary transfer encoding. Their line endings are left as is when
+    /// the output uses another line ending style (see `FmtConfig`).
+    ///
+    /// In "line folding" mode, this is the same as `write_bytes`.
+    fn write_binary(&mut self, buf: &[u8]) {
+        self.write_bytes(buf)
+    }
+
     /// Write whitespace bytes from `buf`. In "line folding" mode, they can be
     /// used for line folding.
     ///
@@ -159,12 +166,45 @@ pub trait Formatter {
     /// Terminate the current line, writing CRLF ("\r\n").
     fn write_crlf(&mut self);
 
+    /// Whether comments kept from parsed structured header fields (addresses,
+    /// dates, message identifiers, MIME parameters) should be printed.
+    fn print_comments(&self) -> bool {
+        false
+    }
+
+    /// Whether header fields that could not be parsed (`header::Invalid`)
+    /// should be dropped instead of being printed verbatim.
+    fn sanitize(&self) -> bool {
+        false
+    }
+
+    /// Whether 7bit and 8bit bodies with lines longer than 998 octets (which
+    /// RFC 5322 forbids) are re-encoded as quoted-printable when printed. If
+    /// not, they are printed as is and reported using `report`.
+    fn reencode_long_lines(&self) -> bool {
+        true
+    }
+
+    /// Charset of the encoded words (RFC 2047) used to print the UTF-8 text of
+    /// phrases (e.g. display names) and unstructured header fields, if any.
+    /// If `None`, UTF-8 text is printed as is (RFC 6532).
+    fn encoded_word_charset(&self) -> Option<&'static encoding_rs::Encoding> {
+        None
+    }
+
+    /// Reports output that does not comply with the RFCs, when the
+    /// `Formatter` is not supposed to fix it.
+    fn report(&mut self, _e: FmtError) {}
+
     /// Write a single folding white space character.
     fn write_fws(&mut self) {
         self.write_fws_bytes(b" ")
     }
 
     /// Consumes the `Formatter` and returns the data that was printed to it.
+    ///
+    /// A `Formatter` that writes its output elsewhere may not support it (see
+    /// `WriteFmt`).
     fn flush(self) -> Vec<u8>;
 }
 
@@ -176,9 +216,28 @@ enum FormatterMode {
 /// `Fmt` implements `Formatter`.
 pub struct Fmt {
     line_limit: Option<usize>,
+    comments: bool,
+    sanitize: bool,
+    strict: bool,
+    encoded_word_charset: Option<&'static encoding_rs::Encoding>,
     mode: FormatterMode,
     boundaries: Boundaries,
+    encoder: OutputEncoder,
     buf: Vec<u8>,
+    // ranges of `buf` written using `write_binary`
+    binary: Vec<Range<usize>>,
+    // first error passed to `report`
+    error: Option<FmtError>,
+}
+
+/// Line ending style of the output of a `Fmt`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// CRLF, as required by RFC 5322 and SMTP.
+    #[default]
+    Crlf,
+    /// LF only, as used by local storage formats on Unix (Maildir, mbox).
+    Lf,
 }
 
 /// Configuration passed when initializing a `Fmt`.
@@ -189,14 +248,56 @@ pub struct Fmt {
 /// `seed` is used to seed the internal RNG which generates multipart
 /// boundaries. If set to `None`, the RNG is seeded using randomness from
 /// the operating system.
+///
+/// `comments` defines whether comments of structured header fields are
+/// reproduced in the output. They are dropped by default.
+///
+/// `sanitize` defines whether header fields that could not be parsed are
+/// dropped from the output. By default, they are reproduced verbatim so that
+/// no information is lost.
+///
+/// `line_ending` defines how lines of the output are terminated. Lines are
+/// terminated by CRLF by default. Bodies using the binary transfer encoding
+/// are not made of lines and are printed as is.
+///
+/// `dot_stuffing` defines whether the output is dot-stuffed for the SMTP DATA
+/// command (RFC 5321, section 4.5.2): a period is added at the beginning of
+/// lines starting with a period, and the output is terminated by a line
+/// containing a single period. See `normalize_input` for the reverse
+/// transformation.
+///
+/// `strict` defines whether output that would not comply with RFC 5322 is
+/// reported instead of being fixed. This currently concerns 7bit and 8bit
+/// bodies with lines longer than 998 octets, which are otherwise re-encoded
+/// as quoted-printable. The reported errors are returned by the `finish`
+/// method of `Fmt`, `WriteFmt` (as `InvalidData` I/O errors) and
+/// `CheckedFmt`; `Formatter::flush` ignores them.
+///
+/// `encoded_word_charset` defines whether the UTF-8 text of phrases (e.g.
+/// display names) and unstructured header fields is printed as encoded words,
+/// and in which charset; see `EncodedWord::encode`. Text that the charset
+/// cannot represent is encoded in UTF-8. By default, UTF-8 text is printed as
+/// is.
 pub struct FmtConfig {
     seed: Option<u64>,
     line_limit: Option<usize>,
+    comments: bool,
+    sanitize: bool,
+    line_ending: LineEnding,
+    dot_stuffing: bool,
+    strict: bool,
+    encoded_word_charset: Option<&'static encoding_rs::Encoding>,
 }
 
 pub const FMT_DEFAULT: FmtConfig = FmtConfig {
     seed: None,
     line_limit: Some(78), // RFC recommended line limit for emails
+    comments: false,
+    sanitize: false,
+    line_ending: LineEnding::Crlf,
+    dot_stuffing: false,
+    strict: false,
+    encoded_word_charset: None,
 };
 
 pub const FMT_NOFOLD: FmtConfig = FMT_DEFAULT.with_line_limit(None);
@@ -209,6 +310,42 @@ impl FmtConfig {
     pub const fn with_line_limit(self, line_limit: Option<usize>) -> Self {
         Self { line_limit, ..self }
     }
+
+    pub const fn with_comments(self, comments: bool) -> Self {
+        Self { comments, ..self }
+    }
+
+    pub const fn with_sanitize(self, sanitize: bool) -> Self {
+        Self { sanitize, ..self }
+    }
+
+    pub const fn with_line_ending(self, line_ending: LineEnding) -> Self {
+        Self {
+            line_ending,
+            ..self
+        }
+    }
+
+    pub const fn with_dot_stuffing(self, dot_stuffing: bool) -> Self {
+        Self {
+            dot_stuffing,
+            ..self
+        }
+    }
+
+    pub const fn with_strict(self, strict: bool) -> Self {
+        Self { strict, ..self }
+    }
+
+    pub const fn with_encoded_word_charset(
+        self,
+        encoded_word_charset: Option<&'static encoding_rs::Encoding>,
+    ) -> Self {
+        Self {
+            encoded_word_charset,
+            ..self
+        }
+    }
 }
 
 impl Default for FmtConfig {
@@ -216,6 +353,12 @@ impl Default for FmtConfig {
         Self {
             seed: None,           // defaults to system RNG
             line_limit: Some(78), // RFC recommended line limit for emails
+            comments: false,
+            sanitize: false,
+            line_ending: LineEnding::Crlf,
+            dot_stuffing: false,
+            strict: false,
+            encoded_word_charset: None,
         }
     }
 }
@@ -228,9 +371,25 @@ impl Fmt {
             .unwrap_or_else(RNG::from_os_rng);
         Self {
             line_limit: cfg.line_limit,
+            comments: cfg.comments,
+            sanitize: cfg.sanitize,
+            strict: cfg.strict,
+            encoded_word_charset: cfg.encoded_word_charset,
             mode: FormatterMode::Direct,
             boundaries: Boundaries::new(rand),
+            encoder: OutputEncoder::new(cfg.line_ending, cfg.dot_stuffing),
             buf: Vec::new(),
+            binary: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Consumes the `Fmt` and returns the data that was printed to it, or the
+    /// first error that was reported (see `FmtConfig`).
+    pub fn finish(mut self) -> Result<Vec<u8>, FmtError> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(self.flush()),
         }
     }
 }
@@ -283,6 +442,17 @@ impl Formatter for Fmt {
         }
     }
 
+    fn write_binary(&mut self, buf: &[u8]) {
+        match self.mode {
+            FormatterMode::Direct => {
+                let start = self.buf.len();
+                self.buf.extend_from_slice(buf);
+                self.binary.push(start..self.buf.len())
+            }
+            FormatterMode::Folding(_) => self.write_bytes(buf),
+        }
+    }
+
     fn write_fws_bytes(&mut self, buf: &[u8]) {
         match self.mode {
             FormatterMode::Direct => self.buf.extend_from_slice(buf),
@@ -297,12 +467,530 @@ impl Formatter for Fmt {
         }
     }
 
+    fn print_comments(&self) -> bool {
+        self.comments
+    }
+
+    fn sanitize(&self) -> bool {
+        self.sanitize
+    }
+
+    fn reencode_long_lines(&self) -> bool {
+        !self.strict
+    }
+
+    fn encoded_word_charset(&self) -> Option<&'static encoding_rs::Encoding> {
+        self.encoded_word_charset
+    }
+
+    fn report(&mut self, e: FmtError) {
+        self.error.get_or_insert(e);
+    }
+
     fn flush(mut self) -> Vec<u8> {
         self.boundaries.assert_empty();
         if let FormatterMode::Folding(mut folder) = self.mode {
             folder.flush(&mut self.buf)
         }
-        self.buf
+        if self.encoder.is_identity() {
+            return self.buf;
+        }
+        let mut out = Vec::with_capacity(self.buf.len());
+        let mut pos = 0;
+        for range in &self.binary {
+            self.encoder
+                .encode(&self.buf[pos..range.start], &mut out, false);
+            self.encoder
+                .encode(&self.buf[range.clone()], &mut out, true);
+            pos = range.end;
+        }
+        self.encoder.encode(&self.buf[pos..], &mut out, false);
+        self.encoder.finish(&mut out);
+        out
+    }
+}
+
+/// `WriteFmt` implements `Formatter` over any `std::io::Write`.
+///
+/// Instead of accumulating the whole output in memory, `WriteFmt` writes it
+/// as printing goes: line-folded text (e.g. a header section) is written once
+/// its folding is done, and data written in "direct" mode (e.g. a body) is
+/// passed through to the writer.
+///
+/// The writer is flushed after each header block and body chunk.
+///
+/// The `Formatter` methods cannot fail: the first error returned by the writer
+/// is recorded, nothing more is written after it, and it is returned by
+/// `finish`. Errors passed to `Formatter::report` are handled the same way.
+/// As the output goes to the writer, `Formatter::flush` is not supported and
+/// panics: use `finish` instead.
+pub struct WriteFmt<W: Write> {
+    fmt: Fmt,
+    writer: W,
+    error: Option<io::Error>,
+    // output of `fmt.encoder`
+    encoded: Vec<u8>,
+}
+
+impl<W: Write> WriteFmt<W> {
+    pub fn new(writer: W, cfg: FmtConfig) -> Self {
+        Self {
+            fmt: Fmt::new(cfg),
+            writer,
+            error: None,
+            encoded: Vec::new(),
+        }
+    }
+
+    /// Writes the remaining output and flushes the writer. Returns the writer,
+    /// or the first error that occurred while printing.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.fmt.boundaries.assert_empty();
+        if let FormatterMode::Folding(ref mut folder) = self.fmt.mode {
+            folder.flush(&mut self.fmt.buf)
+        }
+        self.write_buffered();
+        if !self.fmt.encoder.is_identity() {
+            self.encoded.clear();
+            self.fmt.encoder.finish(&mut self.encoded);
+            let encoded = std::mem::take(&mut self.encoded);
+            self.write_raw(&encoded);
+        }
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_all(&mut self, buf: &[u8], binary: bool) {
+        if self.fmt.encoder.is_identity() {
+            return self.write_raw(buf);
+        }
+        let mut encoded = std::mem::take(&mut self.encoded);
+        encoded.clear();
+        self.fmt.encoder.encode(buf, &mut encoded, binary);
+        self.write_raw(&encoded);
+        self.encoded = encoded;
+    }
+
+    fn flush_writer(&mut self) {
+        if self.error.is_none() {
+            if let Err(e) = self.writer.flush() {
+                self.error = Some(e)
+            }
+        }
+    }
+
+    // write to the writer, bypassing the encoder
+    fn write_raw(&mut self, buf: &[u8]) {
+        if self.error.is_none() {
+            if let Err(e) = self.writer.write_all(buf) {
+                self.error = Some(e)
+            }
+        }
+    }
+
+    // write the output buffered by `fmt` to the writer
+    fn write_buffered(&mut self) {
+        let buf = std::mem::take(&mut self.fmt.buf);
+        self.write_all(&buf, false);
+        // reuse the allocation
+        self.fmt.buf = buf;
+        self.fmt.buf.clear();
+    }
+}
+
+impl<W: Write> Formatter for WriteFmt<W> {
+    fn begin_line_folding(&mut self) {
+        self.fmt.begin_line_folding()
+    }
+
+    fn end_line_folding(&mut self) {
+        self.fmt.end_line_folding();
+        self.write_buffered();
+        self.flush_writer()
+    }
+
+    fn push_new_boundary(&mut self) {
+        self.fmt.push_new_boundary()
+    }
+
+    fn write_current_boundary(&mut self) {
+        self.fmt.write_current_boundary()
+    }
+
+    fn pop_boundary(&mut self) {
+        self.fmt.pop_boundary()
+    }
+
+    fn write_bytes(&mut self, buf: &[u8]) {
+        match self.fmt.mode {
+            FormatterMode::Direct => {
+                self.write_buffered();
+                self.write_all(buf, false);
+                self.flush_writer()
+            }
+            FormatterMode::Folding(_) => self.fmt.write_bytes(buf),
+        }
+    }
+
+    fn write_binary(&mut self, buf: &[u8]) {
+        match self.fmt.mode {
+            FormatterMode::Direct => {
+                self.write_buffered();
+                self.write_all(buf, true);
+                self.flush_writer()
+            }
+            FormatterMode::Folding(_) => self.fmt.write_bytes(buf),
+        }
+    }
+
+    fn write_fws_bytes(&mut self, buf: &[u8]) {
+        self.fmt.write_fws_bytes(buf)
+    }
+
+    fn write_crlf(&mut self) {
+        self.fmt.write_crlf()
+    }
+
+    fn print_comments(&self) -> bool {
+        self.fmt.print_comments()
+    }
+
+    fn sanitize(&self) -> bool {
+        self.fmt.sanitize()
+    }
+
+    fn reencode_long_lines(&self) -> bool {
+        self.fmt.reencode_long_lines()
+    }
+
+    fn encoded_word_charset(&self) -> Option<&'static encoding_rs::Encoding> {
+        self.fmt.encoded_word_charset()
+    }
+
+    fn report(&mut self, e: FmtError) {
+        if self.error.is_none() {
+            self.error = Some(io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+    }
+
+    /// Panics: the output is written to the writer, use `finish` instead.
+    fn flush(self) -> Vec<u8> {
+        panic!("WriteFmt::flush is not supported, use WriteFmt::finish")
+    }
+}
+
+/// Misuse of the `Formatter` API detected by a `CheckedFmt`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FmtError {
+    /// `begin_line_folding` was called in "line folding" mode.
+    AlreadyFolding,
+    /// `end_line_folding` was called in "direct" mode.
+    NotFolding,
+    /// CRLF was written using `write_bytes` in "line folding" mode.
+    CrlfInFoldedText,
+    /// A line started with whitespace in "line folding" mode.
+    WhitespaceAtLineStart,
+    /// `write_fws_bytes` was passed bytes that are not whitespace.
+    NotWhitespace,
+    /// A boundary was written or popped while none was registered.
+    NoBoundary,
+    /// Boundaries were still registered when printing ended.
+    UnbalancedBoundary,
+    /// Line folding produced a line longer than 998 octets (the limit of
+    /// RFC 5322), as there was no whitespace to fold on.
+    LineTooLong,
+    /// A 7bit or 8bit body has lines longer than 998 octets, and was not
+    /// re-encoded (see `FmtConfig`).
+    BodyLineTooLong,
+}
+
+impl std::fmt::Display for FmtError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            Self::AlreadyFolding => "begin_line_folding called in line folding mode",
+            Self::NotFolding => "end_line_folding called in direct mode",
+            Self::CrlfInFoldedText => "CRLF written in line folding mode",
+            Self::WhitespaceAtLineStart => "line starting with whitespace",
+            Self::NotWhitespace => "write_fws_bytes called with non-whitespace bytes",
+            Self::NoBoundary => "no multipart boundary registered",
+            Self::UnbalancedBoundary => "multipart boundary still registered",
+            Self::LineTooLong => "header line longer than 998 octets",
+            Self::BodyLineTooLong => "body line longer than 998 octets",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl std::error::Error for FmtError {}
+
+// RFC 5322 section 2.1.1, without the final CRLF
+const MAX_LINE_LEN: usize = 998;
+
+/// `CheckedFmt` implements `Formatter`, checking that it is used as documented
+/// in the `Formatter` trait.
+///
+/// Where `Fmt` panics or silently produces incorrect output, `CheckedFmt`
+/// records an error (ignoring the offending call) and returns it from
+/// `finish`. This is useful to test `Print` implementations.
+pub struct CheckedFmt {
+    fmt: Fmt,
+    error: Option<FmtError>,
+    // position in the output where line folding started
+    folding_start: usize,
+    // whether the last byte written in line folding mode was CR
+    last_cr: bool,
+}
+
+impl CheckedFmt {
+    pub fn new(cfg: FmtConfig) -> Self {
+        Self {
+            fmt: Fmt::new(cfg),
+            error: None,
+            folding_start: 0,
+            last_cr: false,
+        }
+    }
+
+    /// Consumes the `CheckedFmt` and returns the data that was printed to it,
+    /// or the first error that was detected.
+    pub fn finish(mut self) -> Result<Vec<u8>, FmtError> {
+        if !self.fmt.boundaries.active_boundaries.is_empty() {
+            self.fail(FmtError::UnbalancedBoundary);
+            self.fmt.boundaries.active_boundaries.clear();
+        }
+        if let FormatterMode::Folding(_) = self.fmt.mode {
+            self.end_line_folding()
+        }
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.fmt.flush()),
+        }
+    }
+
+    // only record the first error
+    fn fail(&mut self, e: FmtError) {
+        self.error.get_or_insert(e);
+    }
+
+    fn at_line_start(&self) -> bool {
+        match &self.fmt.mode {
+            FormatterMode::Folding(folder) => folder.cur_fold.is_empty(),
+            FormatterMode::Direct => false,
+        }
+    }
+}
+
+// Whether the lines of `out` that include bytes written from `from` onwards
+// exceed the line length limit.
+pub(crate) fn has_long_line(out: &[u8], from: usize) -> bool {
+    let start = out[..from]
+        .iter()
+        .rposition(|c| *c == b'\n')
+        .map_or(0, |i| i + 1);
+    long_lines(&out[start..]).next().is_some()
+}
+
+// Byte ranges of the lines of `input` that exceed the line length limit,
+// without their line ending (LF or CRLF).
+pub(crate) fn long_lines(input: &[u8]) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut start = 0;
+    input.split(|c| *c == b'\n').filter_map(move |line| {
+        let range = start..start + line.strip_suffix(b"\r").unwrap_or(line).len();
+        start += line.len() + 1;
+        (range.len() > MAX_LINE_LEN).then_some(range)
+    })
+}
+
+impl Formatter for CheckedFmt {
+    fn begin_line_folding(&mut self) {
+        match self.fmt.mode {
+            FormatterMode::Direct => {
+                self.folding_start = self.fmt.buf.len();
+                self.last_cr = false;
+                self.fmt.begin_line_folding()
+            }
+            FormatterMode::Folding(_) => self.fail(FmtError::AlreadyFolding),
+        }
+    }
+
+    fn end_line_folding(&mut self) {
+        match self.fmt.mode {
+            FormatterMode::Folding(_) => {
+                self.fmt.end_line_folding();
+                if has_long_line(&self.fmt.buf, self.folding_start) {
+                    self.fail(FmtError::LineTooLong)
+                }
+            }
+            FormatterMode::Direct => self.fail(FmtError::NotFolding),
+        }
+    }
+
+    fn push_new_boundary(&mut self) {
+        self.fmt.push_new_boundary()
+    }
+
+    fn write_current_boundary(&mut self) {
+        if self.fmt.boundaries.active_boundaries.is_empty() {
+            return self.fail(FmtError::NoBoundary);
+        }
+        self.last_cr = false;
+        self.fmt.write_current_boundary()
+    }
+
+    fn pop_boundary(&mut self) {
+        if self.fmt.boundaries.active_boundaries.is_empty() {
+            return self.fail(FmtError::NoBoundary);
+        }
+        self.fmt.pop_boundary()
+    }
+
+    fn write_bytes(&mut self, buf: &[u8]) {
+        if let FormatterMode::Folding(_) = self.fmt.mode {
+            if buf.is_empty() {
+                return;
+            }
+            if self.at_line_start() && ascii::WS.contains(&buf[0]) {
+                return self.fail(FmtError::WhitespaceAtLineStart);
+            }
+            if buf.windows(2).any(|w| w == ascii::CRLF) || (self.last_cr && buf[0] == b'\n') {
+                return self.fail(FmtError::CrlfInFoldedText);
+            }
+            self.last_cr = buf.last() == Some(&b'\r');
+        }
+        self.fmt.write_bytes(buf)
+    }
+
+    fn write_binary(&mut self, buf: &[u8]) {
+        match self.fmt.mode {
+            FormatterMode::Direct => self.fmt.write_binary(buf),
+            FormatterMode::Folding(_) => self.write_bytes(buf),
+        }
+    }
+
+    fn write_fws_bytes(&mut self, buf: &[u8]) {
+        if !buf.iter().all(|c| ascii::WS.contains(c)) {
+            return self.fail(FmtError::NotWhitespace);
+        }
+        if !buf.is_empty() && self.at_line_start() {
+            return self.fail(FmtError::WhitespaceAtLineStart);
+        }
+        self.last_cr = false;
+        self.fmt.write_fws_bytes(buf)
+    }
+
+    fn write_crlf(&mut self) {
+        self.last_cr = false;
+        self.fmt.write_crlf()
+    }
+
+    fn print_comments(&self) -> bool {
+        self.fmt.print_comments()
+    }
+
+    fn sanitize(&self) -> bool {
+        self.fmt.sanitize()
+    }
+
+    fn reencode_long_lines(&self) -> bool {
+        self.fmt.reencode_long_lines()
+    }
+
+    fn encoded_word_charset(&self) -> Option<&'static encoding_rs::Encoding> {
+        self.fmt.encoded_word_charset()
+    }
+
+    fn report(&mut self, e: FmtError) {
+        self.fail(e)
+    }
+
+    /// Returns the data printed so far, ignoring the detected errors (see
+    /// `finish`).
+    fn flush(mut self) -> Vec<u8> {
+        self.fmt.boundaries.active_boundaries.clear();
+        if let FormatterMode::Folding(_) = self.fmt.mode {
+            self.end_line_folding()
+        }
+        self.fmt.flush()
+    }
+}
+
+// Output encoding -------------------------------------------------------------
+
+// Applies the line ending style and dot-stuffing of a `FmtConfig` to the output
+// of a formatter, which is produced with CRLF line endings. The output can be
+// encoded in several chunks; the line endings of binary chunks are kept.
+struct OutputEncoder {
+    line_ending: LineEnding,
+    dot_stuffing: bool,
+    // whether the next byte starts a new line
+    line_start: bool,
+    // whether the last byte encoded is a CR that has not been output yet
+    pending_cr: bool,
+}
+
+impl OutputEncoder {
+    fn new(line_ending: LineEnding, dot_stuffing: bool) -> Self {
+        Self {
+            line_ending,
+            dot_stuffing,
+            line_start: true,
+            pending_cr: false,
+        }
+    }
+
+    fn is_identity(&self) -> bool {
+        self.line_ending == LineEnding::Crlf && !self.dot_stuffing
+    }
+
+    fn line_ending(&self) -> &'static [u8] {
+        match self.line_ending {
+            LineEnding::Crlf => ascii::CRLF,
+            LineEnding::Lf => b"\n",
+        }
+    }
+
+    fn encode(&mut self, input: &[u8], out: &mut Vec<u8>, binary: bool) {
+        for &c in input {
+            if self.pending_cr {
+                self.pending_cr = false;
+                if c == ascii::LF {
+                    // CRLF becomes LF
+                    out.push(ascii::LF);
+                    self.line_start = true;
+                    continue;
+                }
+                out.push(ascii::CR);
+            }
+            if self.line_start && self.dot_stuffing && c == b'.' {
+                out.push(b'.');
+            }
+            self.line_start = false;
+            if c == ascii::CR && self.line_ending == LineEnding::Lf && !binary {
+                self.pending_cr = true;
+                continue;
+            }
+            out.push(c);
+            self.line_start = c == ascii::LF;
+        }
+    }
+
+    // NOTE: must be called once, after the whole output has been encoded
+    fn finish(&mut self, out: &mut Vec<u8>) {
+        if self.pending_cr {
+            out.push(ascii::CR);
+            self.pending_cr = false;
+            self.line_start = false;
+        }
+        if self.dot_stuffing {
+            if !self.line_start {
+                out.extend_from_slice(self.line_ending());
+            }
+            out.push(b'.');
+            out.extend_from_slice(self.line_ending());
+        }
     }
 }
 
@@ -573,6 +1261,21 @@ pub fn print_to_vec<T: Print>(cfg: FmtConfig, x: T) -> Vec<u8> {
     print_to_vec_with(cfg, |fmt| x.print(fmt))
 }
 
+/// Prints a printable value using a `CheckedFmt`, returning the first misuse of
+/// the `Formatter` API that was detected, if any.
+pub fn print_checked<T: Print>(cfg: FmtConfig, x: T) -> Result<Vec<u8>, FmtError> {
+    let mut fmt = CheckedFmt::new(cfg);
+    x.print(&mut fmt);
+    fmt.finish()
+}
+
+/// Prints a printable value to a writer, using a `WriteFmt`.
+pub fn print_to<T: Print>(writer: impl Write, cfg: FmtConfig, x: T) -> io::Result<()> {
+    let mut fmt = WriteFmt::new(writer, cfg);
+    x.print(&mut fmt);
+    fmt.finish().map(|_| ())
+}
+
 // Cow<'a, [u8]> is our base bytes type
 impl<'a> Print for std::borrow::Cow<'a, [u8]> {
     fn print(&self, fmt: &mut impl Formatter) {
@@ -592,12 +1295,14 @@ pub(crate) mod tests {
         };
         super::print_to_vec_with(cfg, f)
     }
+    // values are printed using a `CheckedFmt`, to catch misuses of the
+    // `Formatter` API
     pub fn print_to_vec<T: Print>(x: T) -> Vec<u8> {
         let cfg = FmtConfig {
             seed: Some(0),
             ..FMT_DEFAULT
         };
-        super::print_to_vec(cfg, x)
+        super::print_checked(cfg, x).unwrap()
     }
 
     #[test]
@@ -651,4 +1356,241 @@ pub(crate) mod tests {
         });
         assert_eq!(folded, [&b"X"[..], &[b' '; 82],].concat());
     }
+
+    #[test]
+    #[should_panic(expected = "use WriteFmt::finish")]
+    fn test_write_fmt_flush() {
+        let mut fmt = WriteFmt::new(Vec::new(), FMT_DEFAULT);
+        fmt.write_bytes(b"hello");
+        fmt.flush();
+    }
+
+    #[test]
+    fn test_write_fmt() {
+        let txt = b"From: alice@example.com\r
+Content-Type: multipart/mixed; boundary=\"XYZ\"\r
+\r
+--XYZ\r
+\r
+first part\r
+--XYZ--\r
+";
+        let msg = crate::message::message(txt);
+        let cfg = || FMT_DEFAULT.with_seed(Some(0));
+
+        // records the chunks written to it, and the number of chunks written
+        // when it was flushed
+        struct Chunks(Vec<Vec<u8>>, Vec<usize>);
+        impl Write for Chunks {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.push(buf.to_vec());
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                self.1.push(self.0.len());
+                Ok(())
+            }
+        }
+        let mut chunks = Chunks(vec![], vec![]);
+        print_to(&mut chunks, cfg(), &msg).unwrap();
+        assert_eq!(chunks.0.concat(), super::print_to_vec(cfg(), &msg));
+        let body = chunks.0.iter().position(|c| c == b"first part").unwrap();
+        // flushed after the header block and after the body chunk
+        assert!(chunks.0[0].starts_with(b"From:"));
+        assert!(chunks.1.contains(&1));
+        assert!(chunks.1.contains(&(body + 1)));
+
+        // writer errors are returned
+        struct Failing;
+        impl Write for Failing {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("failing"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let err = print_to(Failing, cfg(), &msg).unwrap_err();
+        assert_eq!(err.to_string(), "failing");
+    }
+
+    #[test]
+    fn test_checked_fmt() {
+        let check = |f: &dyn Fn(&mut CheckedFmt)| {
+            let mut fmt = CheckedFmt::new(FMT_DEFAULT.with_seed(Some(0)));
+            f(&mut fmt);
+            fmt.finish()
+        };
+
+        assert_eq!(
+            check(&|f| {
+                f.begin_line_folding();
+                f.write_bytes(b"Subject:");
+                f.write_fws();
+                f.write_bytes(b"hello");
+                f.write_crlf();
+                f.end_line_folding();
+            }),
+            Ok(b"Subject: hello\r\n".to_vec())
+        );
+        assert_eq!(
+            check(&|f| {
+                f.begin_line_folding();
+                f.begin_line_folding();
+            }),
+            Err(FmtError::AlreadyFolding)
+        );
+        assert_eq!(check(&|f| f.end_line_folding()), Err(FmtError::NotFolding));
+        assert_eq!(
+            check(&|f| {
+                f.begin_line_folding();
+                f.write_bytes(b"a\r");
+                f.write_bytes(b"\nb");
+            }),
+            Err(FmtError::CrlfInFoldedText)
+        );
+        assert_eq!(
+            check(&|f| {
+                f.begin_line_folding();
+                f.write_bytes(b" a");
+            }),
+            Err(FmtError::WhitespaceAtLineStart)
+        );
+        assert_eq!(
+            check(&|f| {
+                f.begin_line_folding();
+                f.write_fws();
+            }),
+            Err(FmtError::WhitespaceAtLineStart)
+        );
+        assert_eq!(
+            check(&|f| {
+                f.begin_line_folding();
+                f.write_bytes(b"a");
+                f.write_fws_bytes(b"b");
+            }),
+            Err(FmtError::NotWhitespace)
+        );
+        assert_eq!(check(&|f| f.pop_boundary()), Err(FmtError::NoBoundary));
+        assert_eq!(
+            check(&|f| f.write_current_boundary()),
+            Err(FmtError::NoBoundary)
+        );
+        assert_eq!(
+            check(&|f| f.push_new_boundary()),
+            Err(FmtError::UnbalancedBoundary)
+        );
+        assert_eq!(
+            check(&|f| {
+                f.begin_line_folding();
+                f.write_bytes(&[b'x'; 999]);
+                f.write_crlf();
+                f.end_line_folding();
+            }),
+            Err(FmtError::LineTooLong)
+        );
+        // lines written in direct mode are not checked
+        assert!(check(&|f| f.write_bytes(&[b'x'; 999])).is_ok());
+    }
+
+    #[test]
+    fn test_checked_message() {
+        let txt = b"From: alice@example.com (Alice)\r
+To: bob@example.com, \"Carol\" <carol@example.com>\r
+Subject: a rather long subject that will need to be folded when it is printed again\r
+Content-Type: multipart/mixed; boundary=\"XYZ\"\r
+\r
+--XYZ\r
+Content-Type: message/rfc822\r
+\r
+From: dave@example.com\r
+\r
+inner\r
+--XYZ--\r
+";
+        let msg = crate::message::message(txt);
+        let cfg = || FMT_DEFAULT.with_seed(Some(0)).with_comments(true);
+        assert_eq!(
+            print_checked(cfg(), &msg),
+            Ok(super::print_to_vec(cfg(), &msg))
+        );
+    }
+
+    #[test]
+    fn test_line_ending_dot_stuffing() {
+        let txt = b"From: alice@example.com\r
+Subject: hello\r
+\r
+first line\r
+.second line\r
+.\r
+";
+        let msg = crate::message::message(txt);
+        let cfg = || FMT_DEFAULT.with_seed(Some(0));
+        let crlf = super::print_to_vec(cfg(), &msg);
+        let lf = String::from_utf8(crlf.clone())
+            .unwrap()
+            .replace("\r\n", "\n");
+
+        let cfg_lf = || cfg().with_line_ending(LineEnding::Lf);
+        assert_eq!(
+            String::from_utf8(super::print_to_vec(cfg_lf(), &msg)).unwrap(),
+            lf
+        );
+        let cfg_smtp = || cfg().with_dot_stuffing(true);
+        let smtp = super::print_to_vec(cfg_smtp(), &msg);
+        assert!(smtp.ends_with(b"\r\nfirst line\r\n..second line\r\n..\r\n.\r\n"));
+
+        // the same output is produced when streaming
+        let mut out = vec![];
+        print_to(&mut out, cfg_lf(), &msg).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), lf);
+        let mut out = vec![];
+        print_to(&mut out, cfg_smtp(), &msg).unwrap();
+        assert_eq!(out, smtp);
+
+        // the normalizer undoes the transformations
+        assert_eq!(
+            crate::text::whitespace::normalize(lf.as_bytes(), false),
+            crlf
+        );
+        assert_eq!(crate::text::whitespace::normalize(&smtp, true), crlf);
+    }
+
+    #[test]
+    fn test_line_ending_binary() {
+        let txt = b"From: alice@example.com\r
+Content-Type: application/octet-stream\r
+Content-Transfer-Encoding: binary\r
+\r
+\x00\r\n\x01\r\n";
+        let msg = crate::message::message(txt);
+        let cfg = || FMT_DEFAULT.with_seed(Some(0));
+        let cfg_lf = || cfg().with_line_ending(LineEnding::Lf);
+
+        // the header is converted, not the body
+        let lf = super::print_to_vec(cfg_lf(), &msg);
+        assert!(lf.starts_with(b"From: alice@example.com\n"));
+        assert!(lf.ends_with(b"1.0\n\n\x00\r\n\x01\r\n"));
+        let mut out = vec![];
+        print_to(&mut out, cfg_lf(), &msg).unwrap();
+        assert_eq!(out, lf);
+        let mut fmt = Fmt::new(cfg_lf());
+        msg.print_diff(&msg, &mut fmt);
+        let diff = fmt.flush();
+        assert!(diff.starts_with(b"From: alice@example.com\n"));
+        assert!(diff.ends_with(b"binary\n\n\x00\r\n\x01\r\n"));
+    }
+
+    #[test]
+    fn test_output_encoder_chunks() {
+        // CRLF split across chunks
+        let mut enc = OutputEncoder::new(LineEnding::Lf, true);
+        let mut out = vec![];
+        enc.encode(b".a\r", &mut out, false);
+        enc.encode(b"\n.b\r", &mut out, false);
+        enc.encode(b"c", &mut out, false);
+        enc.finish(&mut out);
+        assert_eq!(out, b"..a\n..b\rc\n.\n");
+    }
 }
@@ -6,6 +6,7 @@ use arbitrary::Arbitrary;
 use bounded_static::{IntoBoundedStatic, ToBoundedStatic};
 use std::borrow::Cow;
 use std::fmt;
+use std::ops::Range;
 
 #[derive(Clone, PartialEq)]
 pub struct RawInput<'a>(pub Option<&'a [u8]>);
@@ -21,6 +22,33 @@ impl<'a> RawInput<'a> {
             Some(s) => s,
         }
     }
+
+    /// Byte offsets (start and end) of this raw input relative to the start
+    /// of `input`.
+    ///
+    /// Returns `None` if there is no raw input, or if it is not a subslice of
+    /// `input` (e.g. when the AST was built programmatically, or parsed from
+    /// another buffer).
+    pub fn span_in(&self, input: &[u8]) -> Option<Range<usize>> {
+        range_in(addresses(input), self.0?)
+    }
+}
+
+// Range of `s` relative to the start of `input`, if `s` is a subslice of
+// `input` (given as a range of addresses).
+pub(crate) fn range_in(input: Range<usize>, s: &[u8]) -> Option<Range<usize>> {
+    let start = s.as_ptr() as usize;
+    let end = start + s.len();
+    if input.start <= start && end <= input.end {
+        Some(start - input.start..end - input.start)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn addresses(input: &[u8]) -> Range<usize> {
+    let start = input.as_ptr() as usize;
+    start..start + input.len()
 }
 
 impl<'a> From<&'a [u8]> for RawInput<'a> {
@@ -1,9 +1,9 @@
+use crate::diagnostics::{self, Severity};
 use crate::i18n::ContainsUtf8;
 use crate::text::words::is_vchar;
 use bounded_static::{IntoBoundedStatic, ToBoundedStatic};
 use charset::Charset;
-#[cfg(feature = "tracing-recover")]
-use tracing::warn;
+use std::cell::RefCell;
 #[cfg(feature = "arbitrary")]
 use {crate::fuzz_eq::FuzzEq, arbitrary::Arbitrary};
 
@@ -38,8 +38,13 @@ impl<T: AsRef<[u8]>> From<T> for EmailCharset {
                 match Charset::for_label(sanitized.as_bytes()) {
                     Some(c) => Self::Charset(c),
                     None => {
-                        #[cfg(feature = "tracing-recover")]
-                        warn!(value = sanitized, "unknown charset");
+                        diagnostics::diagnose!(
+                            "tracing-recover",
+                            (value = sanitized),
+                            Recovered("unknown charset"),
+                            Severity::Warning,
+                            None,
+                        );
                         Self::Unknown(sanitized)
                     }
                 }
@@ -75,6 +80,30 @@ impl EmailCharset {
         Self::Charset(Charset::for_encoding(encoding_rs::UTF_8))
     }
 
+    /// Returns whether this is a known charset other than UTF-8 and US-ASCII,
+    /// i.e. a charset that raw 8-bit header text may be written in.
+    pub fn is_legacy(&self) -> bool {
+        matches!(self, Self::Charset(c) if *c != Charset::for_encoding(encoding_rs::UTF_8))
+    }
+
+    /// Encodes `text` in this charset. Returns `None` if `text` contains
+    /// characters that cannot be represented in this charset, or if encoding
+    /// into this charset is not supported (e.g. UTF-16 or unknown charsets).
+    pub fn encode<'a>(&self, text: &'a str) -> Option<std::borrow::Cow<'a, [u8]>> {
+        match self {
+            Self::US_ASCII => text.is_ascii().then_some(text.as_bytes().into()),
+            Self::Charset(c) => {
+                let enc = encoding_rs::Encoding::for_label(c.name().as_bytes())?;
+                if enc.output_encoding() != enc {
+                    return None;
+                }
+                let (bytes, _, unmappable) = enc.encode(text);
+                (!unmappable).then_some(bytes)
+            }
+            Self::Unknown(_) => None,
+        }
+    }
+
     pub fn decode<'a>(&self, bytes: &'a [u8]) -> std::borrow::Cow<'a, str> {
         match self {
             Self::US_ASCII | Self::Unknown(_) => charset::decode_ascii(bytes),
@@ -86,6 +115,95 @@ impl EmailCharset {
     }
 }
 
+/// A charset proposed by [`detect`].
+#[cfg(feature = "charset-detection")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CharsetGuess {
+    pub charset: EmailCharset,
+    /// How likely the guess is to be right, between 0 and 1.
+    pub confidence: f32,
+}
+
+/// Guesses the charset of `body`, the content of a text part whose content
+/// transfer encoding is already decoded, for parts that declare no charset
+/// or a wrong one.
+///
+/// Bodies that are valid UTF-8 are assumed to be UTF-8. Otherwise, if `html`
+/// is set, the charset declared by a `<meta>` element is used if the body is
+/// valid in that charset. Otherwise, the charset is guessed from the byte
+/// frequencies of `body`.
+#[cfg(feature = "charset-detection")]
+pub fn detect(body: &[u8], html: bool) -> CharsetGuess {
+    // ASCII text is decoded the same in all ASCII-compatible charsets,
+    // but ESC starts the escape sequences of ISO-2022-JP.
+    if body.is_ascii() && !body.contains(&0x1b) {
+        return CharsetGuess {
+            charset: EmailCharset::US_ASCII,
+            confidence: 1.0,
+        };
+    }
+    // non-ASCII text in other charsets is seldom valid UTF-8
+    if std::str::from_utf8(body).is_ok() {
+        return CharsetGuess {
+            charset: EmailCharset::utf8(),
+            confidence: 0.95,
+        };
+    }
+    if html {
+        if let Some(EmailCharset::Charset(c)) = crate::text::html::meta_charset(body) {
+            if !c.decode_without_bom_handling(body).1 {
+                return CharsetGuess {
+                    charset: EmailCharset::Charset(c),
+                    confidence: 0.9,
+                };
+            }
+        }
+    }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(body, true);
+    let (encoding, plausible) = detector.guess_assess(None, false);
+    let malformed = encoding.decode_without_bom_handling(body).1;
+    CharsetGuess {
+        charset: EmailCharset::Charset(Charset::for_encoding(encoding)),
+        confidence: match (plausible, malformed) {
+            (_, true) => 0.1,
+            (false, false) => 0.4,
+            (true, false) => 0.7,
+        },
+    }
+}
+
+thread_local! {
+    static FALLBACK: RefCell<Option<EmailCharset>> = const { RefCell::new(None) };
+}
+
+// Runs `f`, decoding header text that is not valid UTF-8 with `charset`
+// (see `text::utf8::take_utf8_while1`).
+pub(crate) fn with_fallback<T>(charset: Option<EmailCharset>, f: impl FnOnce() -> T) -> T {
+    // restores the previous fallback charset, even if `f` panics
+    struct Restore(Option<EmailCharset>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            FALLBACK.with(|c| c.replace(self.0.take()));
+        }
+    }
+    let _restore = Restore(FALLBACK.with(|c| c.replace(charset)));
+    f()
+}
+
+// Decodes `bytes` with the fallback charset, if one is set and `bytes` are
+// valid in this charset.
+pub(crate) fn decode_fallback(bytes: &[u8]) -> Option<String> {
+    FALLBACK.with(|c| match c.borrow().as_ref()? {
+        EmailCharset::Charset(cs) => match cs.decode_without_bom_handling(bytes) {
+            (decoded, false) => Some(decoded.into_owned()),
+            (_, true) => None,
+        },
+        EmailCharset::US_ASCII | EmailCharset::Unknown(_) => None,
+    })
+}
+
 impl IntoBoundedStatic for EmailCharset {
     type Static = Self;
     fn into_static(self) -> Self::Static {
@@ -112,7 +230,10 @@ impl<'a> Arbitrary<'a> for EmailCharset {
             4 => Ok(Self::from(b"iso-8859-15")),
             5 => Ok(Self::from(b"GBK")),
             6 => {
-                let label: &[u8] = u.arbitrary()?;
+                // '*' separates the charset from the language in
+                // encoded words (RFC2231, 5)
+                let label: Vec<u8> = u.arbitrary()?;
+                let label: Vec<u8> = label.into_iter().filter(|c| *c != b'*').collect();
                 Ok(Self::from(label))
             }
             _ => unreachable!(),
@@ -149,4 +270,30 @@ mod tests {
             EmailCharset::Unknown("!*abc".to_string()),
         );
     }
+
+    #[cfg(feature = "charset-detection")]
+    #[test]
+    fn test_detect() {
+        let guess = detect(b"Hello", false);
+        assert_eq!(guess.charset, EmailCharset::US_ASCII);
+        assert_eq!(guess.confidence, 1.0);
+
+        let guess = detect("Привет, как дела?".as_bytes(), false);
+        assert_eq!(guess.charset, EmailCharset::utf8());
+
+        // KOI8-R text is detected as KOI8-U, a superset of KOI8-R
+        let text = "Привет, как дела? Всё хорошо, спасибо.";
+        let (koi8, _, _) = encoding_rs::KOI8_R.encode(text);
+        let guess = detect(&koi8, false);
+        assert_eq!(guess.charset, EmailCharset::from("koi8-u"));
+        assert_eq!(guess.charset.decode(&koi8), text);
+        assert!(guess.confidence >= 0.7);
+
+        // the meta declaration is trusted if the body is valid in its charset
+        let (html, _, _) = encoding_rs::SHIFT_JIS
+            .encode("<meta charset=\"shift_jis\"><p>日本語のテキストです。</p>");
+        let guess = detect(&html, true);
+        assert_eq!(guess.charset, EmailCharset::from("shift_jis"));
+        assert_eq!(guess.confidence, 0.9);
+    }
 }
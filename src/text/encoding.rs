@@ -16,18 +16,20 @@ use nom::{
 use std::borrow::Cow;
 use std::fmt;
 
+use crate::diagnostics::discard_on_failure;
 use crate::i18n::ContainsUtf8;
 use crate::print::{print_seq, Formatter, Print, ToStringFromPrint};
 use crate::text::ascii;
 use crate::text::charset::EmailCharset;
 use crate::text::utf8::take_utf8_while1;
 use crate::text::whitespace::{self, cfws, fws};
-use crate::text::words;
+use crate::text::words::{self, MIMEAtom};
 #[cfg(feature = "arbitrary")]
 use crate::{
     arbitrary_utils::{arbitrary_vec_nonempty, arbitrary_vec_where},
     fuzz_eq::FuzzEq,
 };
+use charset::Charset;
 
 // Context in which an encoded word is parsed.
 //
@@ -56,13 +58,15 @@ pub fn encoded_word_token(
     move |input| {
         // An encoded word is always a special case of an atom-like token. Which characters are
         // allowed in this atom token depends on the context, so we first read the atom, then try to
-        // parse it fully as an encoded word.
-        map_parser(
+        // parse it fully as an encoded word. Diagnostics reported while
+        // reading a token that is not an encoded word are dropped, as the
+        // token is read again by the caller.
+        discard_on_failure(map_parser(
             // read an atom-like token
             encoded_word_token_atom(ctx),
             // ...which must fully represent an encoded word
             all_consuming(alt((encoded_word_token_quoted, encoded_word_token_base64))),
-        )(input)
+        ))(input)
     }
 }
 
@@ -88,7 +92,7 @@ fn encoded_word_token_atom(ctx: Context) -> impl FnMut(&[u8]) -> IResult<&[u8],
 pub fn encoded_word_token_quoted(input: &[u8]) -> IResult<&[u8], EncodedWordToken<'_>> {
     let (rest, (_, charset, _, _, _, txt, _)) = tuple((
         tag("=?"),
-        words::mime_atom_plain,
+        take_while1(words::is_mime_atom_text),
         tag("?"),
         one_of("Qq"),
         tag("?"),
@@ -96,8 +100,10 @@ pub fn encoded_word_token_quoted(input: &[u8]) -> IResult<&[u8], EncodedWordToke
         tag("?="),
     ))(input)?;
 
+    let (enc, lang) = charset_and_language(charset);
     let parsed = EncodedWordToken::Quoted(QuotedWord {
-        enc: charset.0.into(),
+        enc,
+        lang,
         chunks: txt,
     });
     Ok((rest, parsed))
@@ -106,7 +112,7 @@ pub fn encoded_word_token_quoted(input: &[u8]) -> IResult<&[u8], EncodedWordToke
 pub fn encoded_word_token_base64(input: &[u8]) -> IResult<&[u8], EncodedWordToken<'_>> {
     let (rest, (_, charset, _, _, _, txt, _)) = tuple((
         tag("=?"),
-        words::mime_atom_plain,
+        take_while1(words::is_mime_atom_text),
         tag("?"),
         one_of("Bb"),
         tag("?"),
@@ -114,13 +120,28 @@ pub fn encoded_word_token_base64(input: &[u8]) -> IResult<&[u8], EncodedWordToke
         tag("?="),
     ))(input)?;
 
+    let (enc, lang) = charset_and_language(charset);
     let parsed = EncodedWordToken::Base64(Base64Word {
-        enc: charset.0.into(),
+        enc,
+        lang,
         content: Cow::Borrowed(txt),
     });
     Ok((rest, parsed))
 }
 
+// Splits the charset of an encoded word from its optional language,
+// as in `=?charset*language?...?=` (RFC2231, 5).
+fn charset_and_language(input: &[u8]) -> (EmailCharset, Option<MIMEAtom<'_>>) {
+    match input.iter().position(|c| *c == ascii::ASTERISK) {
+        Some(i) => {
+            let lang = &input[i + 1..];
+            let lang = (!lang.is_empty()).then_some(MIMEAtom(Cow::Borrowed(lang)));
+            (input[..i].into(), lang)
+        }
+        None => (input.into(), None),
+    }
+}
+
 /// Represents an encoded word.
 #[derive(Clone, ContainsUtf8, Debug, PartialEq, ToStatic, ToStringFromPrint)]
 #[cfg_attr(feature = "arbitrary", derive(FuzzEq))]
@@ -128,77 +149,140 @@ pub fn encoded_word_token_base64(input: &[u8]) -> IResult<&[u8], EncodedWordToke
 pub struct EncodedWord<'a>(pub Vec<EncodedWordToken<'a>>); // must be non-empty
 
 impl<'a> EncodedWord<'a> {
-    /// Returns the data represented by this `EncodedWord`, encoded into UTF8
+    /// Returns the data represented by this `EncodedWord`, encoded into UTF8.
+    ///
+    /// The octets of adjacent tokens that use the same charset are
+    /// concatenated before being decoded, so that a multi-byte character
+    /// split between two tokens is decoded correctly.
     pub fn data(&self) -> String {
-        self.0
-            .iter()
-            .map(|tok| tok.data())
-            .collect::<Vec<_>>()
-            .join("")
+        let mut d = WordDecoder::default();
+        for tok in &self.0 {
+            tok.decode_into(&mut d);
+            d.end_word();
+        }
+        d.finish()
     }
 
-    /// Build an encoded word from UTF-8 chars. Uses the UTF-8 charset and
-    /// quoted encoding.
+    /// Build an encoded word from UTF-8 chars, using the UTF-8 charset.
+    ///
+    /// See [`EncodedWord::encode`] for how the text is split into encoded
+    /// words and how the Q or B encoding is chosen.
     pub fn from_chars<I>(chars: I) -> Self
     where
         I: IntoIterator<Item = char>,
     {
-        const HEADER: &[u8] = b"=?UTF-8?Q?";
-        const FOOTER: &[u8] = b"?=";
+        let text: String = chars.into_iter().collect();
+        // UTF-8 can represent any char
+        Self::encode(&text, encoding_rs::UTF_8).unwrap()
+    }
+
+    /// Build an encoded word representing `text` in the given charset.
+    /// Returns `None` if `text` contains characters that cannot be represented
+    /// in `charset`; callers can then fall back to [`EncodedWord::from_chars`].
+    ///
+    /// The text is split into as many encoded words as needed so that each of
+    /// them is at most 75 characters long (RFC2047, 2). Splits only happen
+    /// between characters and each encoded word is encoded on its own, so that
+    /// a multi-byte character (or the escape sequences of a stateful charset
+    /// such as ISO-2022-JP) never spans two encoded words. Each encoded word
+    /// uses whichever of the Q and B encodings is shorter.
+    pub fn encode(text: &str, charset: &'static encoding_rs::Encoding) -> Option<Self> {
         // specified in RFC2047
         const MAX_LEN: usize = 75;
 
+        let label = EmailCharset::Charset(Charset::for_encoding(charset.output_encoding()));
         let mut tokens: Vec<EncodedWordToken> = vec![];
-        let mut cur_chunks: Vec<QuotedChunk> = vec![];
-        let mut cur_word_len = 0;
-        let mut char_bytes: [u8; 4] = [0; 4];
-
-        for c in chars {
-            if HEADER.len() + cur_word_len + FOOTER.len() > MAX_LEN - 3
-            /* max size minus room for the next encoded byte */
-            {
-                let mut w = QuotedWord {
-                    enc: EmailCharset::utf8(),
-                    chunks: vec![],
-                };
-                std::mem::swap(&mut w.chunks, &mut cur_chunks);
-                tokens.push(EncodedWordToken::Quoted(w));
-                cur_word_len = 0;
-            }
-
-            if c.is_ascii() && is_qchar_safe_strict(c as u8) {
-                if let Some(QuotedChunk::Safe(s)) = cur_chunks.last_mut() {
-                    let s = s.to_mut();
-                    s.push(c as u8)
-                } else {
-                    cur_chunks.push(QuotedChunk::Safe(vec![c as u8].into()));
-                }
-                cur_word_len += 1;
-            } else if c == char::from(ascii::SP) {
-                // space has a special treatment (RFC2047, 4.2, (2))
-                cur_chunks.push(QuotedChunk::Space);
-                cur_word_len += 1;
+        let mut word_start = 0;
+        let mut cur = encode_token(&label, charset, "")?;
+
+        for (i, c) in text.char_indices() {
+            let end = i + c.len_utf8();
+            let next = encode_token(&label, charset, &text[word_start..end])?;
+            if next.1 > MAX_LEN && word_start < i {
+                // `c` does not fit: close the current word and start a new one
+                tokens.push(cur.0);
+                word_start = i;
+                cur = encode_token(&label, charset, &text[i..end])?;
             } else {
-                c.encode_utf8(&mut char_bytes);
-                let c_bytes = &char_bytes[0..c.len_utf8()];
-                if let Some(QuotedChunk::Encoded(e)) = cur_chunks.last_mut() {
-                    e.extend_from_slice(c_bytes)
-                } else {
-                    cur_chunks.push(QuotedChunk::Encoded(c_bytes.to_vec()))
-                }
-                // each encoded byte uses three characters (=XX)
-                cur_word_len += 3 * c.len_utf8();
+                cur = next;
             }
         }
+        tokens.push(cur.0);
+
+        Some(EncodedWord(tokens))
+    }
 
-        tokens.push(EncodedWordToken::Quoted(QuotedWord {
-            enc: EmailCharset::utf8(),
-            chunks: cur_chunks,
-        }));
+    /// Same as `encode`, falling back to UTF-8 if some characters of `text`
+    /// cannot be represented in `charset`.
+    pub fn encode_or_utf8(text: &str, charset: &'static encoding_rs::Encoding) -> Self {
+        Self::encode(text, charset).unwrap_or_else(|| Self::from_chars(text.chars()))
+    }
+}
 
-        EncodedWord(tokens)
+// Encodes `text` as a single encoded word in `charset`, using the shortest
+// of the Q and B encodings. Returns the word and its printed length, or
+// `None` if `text` is not representable in `charset`.
+fn encode_token(
+    label: &EmailCharset,
+    charset: &'static encoding_rs::Encoding,
+    text: &str,
+) -> Option<(EncodedWordToken<'static>, usize)> {
+    let (bytes, _, unmappable) = charset.encode(text);
+    if unmappable {
+        return None;
+    }
+    // "=?" charset "?X?" encoded-text "?="
+    let overhead = 2 + label.as_bytes().len() + 3 + 2;
+    let b_len = overhead + bytes.len().div_ceil(3) * 4;
+    // Safe bytes of a Q-encoded word are decoded as ASCII characters, which
+    // is only correct if the charset never uses ASCII bytes as part of a
+    // multi-byte character (unlike e.g. Shift_JIS or ISO-2022-JP).
+    let q_len = (charset == encoding_rs::UTF_8 || charset.is_single_byte()).then(|| {
+        overhead
+            + bytes
+                .iter()
+                .map(|&b| {
+                    if b == ascii::SP || is_qchar_safe_strict(b) {
+                        1
+                    } else {
+                        3
+                    }
+                })
+                .sum::<usize>()
+    });
+
+    match q_len {
+        Some(q_len) if q_len <= b_len => {
+            let mut chunks: Vec<QuotedChunk> = vec![];
+            for &b in bytes.iter() {
+                match chunks.last_mut() {
+                    // space has a special treatment (RFC2047, 4.2, (2))
+                    _ if b == ascii::SP => chunks.push(QuotedChunk::Space),
+                    Some(QuotedChunk::Safe(s)) if is_qchar_safe_strict(b) => s.to_mut().push(b),
+                    _ if is_qchar_safe_strict(b) => chunks.push(QuotedChunk::Safe(vec![b].into())),
+                    Some(QuotedChunk::Encoded(e)) => e.push(b),
+                    _ => chunks.push(QuotedChunk::Encoded(vec![b])),
+                }
+            }
+            let word = QuotedWord {
+                enc: label.clone(),
+                lang: None,
+                chunks,
+            };
+            Some((EncodedWordToken::Quoted(word), q_len))
+        }
+        _ => {
+            let content = general_purpose::STANDARD_NO_PAD.encode(&bytes);
+            let word = Base64Word {
+                enc: label.clone(),
+                lang: None,
+                content: Cow::Owned(content.into_bytes()),
+            };
+            Some((EncodedWordToken::Base64(word), b_len))
+        }
     }
 }
+
 impl<'a> Print for EncodedWord<'a> {
     fn print(&self, fmt: &mut impl Formatter) {
         print_seq(fmt, &self.0, Formatter::write_fws)
@@ -220,12 +304,70 @@ pub enum EncodedWordToken<'a> {
 }
 impl<'a> EncodedWordToken<'a> {
     pub fn data(&self) -> String {
+        let mut d = WordDecoder::default();
+        self.decode_into(&mut d);
+        d.finish()
+    }
+
+    fn decode_into<'b>(&'b self, d: &mut WordDecoder<'b>) {
         match self {
-            EncodedWordToken::Quoted(v) => v.data(),
-            EncodedWordToken::Base64(v) => v.data(),
+            EncodedWordToken::Quoted(v) => v.decode_into(d),
+            EncodedWordToken::Base64(v) => v.decode_into(d),
         }
     }
 }
+
+// Decodes the octets of a sequence of encoded words into UTF-8. Octets of
+// adjacent words in the same charset are decoded together when a word ends
+// with an incomplete character.
+#[derive(Default)]
+struct WordDecoder<'a> {
+    out: String,
+    enc: Option<&'a EmailCharset>,
+    octets: Vec<u8>,
+}
+impl<'a> WordDecoder<'a> {
+    fn push_octets(&mut self, enc: &'a EmailCharset, octets: &[u8]) {
+        if self.enc != Some(enc) {
+            self.flush();
+            self.enc = Some(enc);
+        }
+        self.octets.extend_from_slice(octets);
+    }
+
+    // Raw UTF-8 text, which may appear in Q-encoded words (RFC6532, 3.2)
+    fn push_utf8(&mut self, bytes: &[u8]) {
+        self.flush();
+        let (content, _) = encoding_rs::UTF_8.decode_without_bom_handling(bytes);
+        self.out.push_str(content.as_ref());
+    }
+
+    // Called between encoded words: octets that can be decoded on their own
+    // are decoded now, as concatenating them with the next word may be
+    // invalid for stateful charsets (e.g. ISO-2022-JP words each end by
+    // switching back to ASCII).
+    fn end_word(&mut self) {
+        let complete = match self.enc {
+            Some(EmailCharset::Charset(c)) => !c.decode_without_bom_handling(&self.octets).1,
+            _ => true,
+        };
+        if complete {
+            self.flush()
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(enc) = self.enc {
+            self.out.push_str(enc.decode(&self.octets).as_ref());
+        }
+        self.octets.clear();
+    }
+
+    fn finish(mut self) -> String {
+        self.flush();
+        self.out
+    }
+}
 impl<'a> Print for EncodedWordToken<'a> {
     fn print(&self, fmt: &mut impl Formatter) {
         match self {
@@ -239,6 +381,8 @@ impl<'a> Print for EncodedWordToken<'a> {
 #[cfg_attr(feature = "arbitrary", derive(FuzzEq))]
 pub struct Base64Word<'a> {
     pub enc: EmailCharset,
+    // language of the text (RFC2231, 5)
+    pub lang: Option<MIMEAtom<'a>>,
     // `content` must represent base64-encoded data. In particular,
     // all bytes in `content` must satisfy `is_bchar`.
     #[cfg_attr(feature = "arbitrary", fuzz_eq(use_eq))]
@@ -248,6 +392,7 @@ impl<'a> fmt::Debug for Base64Word<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt.debug_struct("Base64Word")
             .field("enc", &self.enc)
+            .field("lang", &self.lang)
             .field("content", &String::from_utf8_lossy(&self.content))
             .finish()
     }
@@ -255,10 +400,15 @@ impl<'a> fmt::Debug for Base64Word<'a> {
 
 impl<'a> Base64Word<'a> {
     pub fn data(&self) -> String {
-        general_purpose::STANDARD_NO_PAD
-            .decode(&self.content)
-            .map(|d| self.enc.decode(d.as_slice()).to_string())
-            .unwrap_or("".into())
+        let mut d = WordDecoder::default();
+        self.decode_into(&mut d);
+        d.finish()
+    }
+
+    fn decode_into<'b>(&'b self, d: &mut WordDecoder<'b>) {
+        if let Ok(octets) = general_purpose::STANDARD_NO_PAD.decode(&self.content) {
+            d.push_octets(&self.enc, &octets)
+        }
     }
 }
 
@@ -266,8 +416,16 @@ impl<'a> Print for Base64Word<'a> {
     fn print(&self, fmt: &mut impl Formatter) {
         fmt.write_bytes(b"=?");
         fmt.write_bytes(self.enc.as_bytes());
+        print_language(fmt, &self.lang);
         fmt.write_bytes(b"?B?");
         fmt.write_bytes(&self.content);
+        // `content` does not include the padding, which is required by
+        // the base64 encoding (RFC2045, 6.8)
+        match self.content.len() % 4 {
+            2 => fmt.write_bytes(b"=="),
+            3 => fmt.write_bytes(b"="),
+            _ => (),
+        }
         fmt.write_bytes(b"?=");
     }
 }
@@ -276,9 +434,11 @@ impl<'a> Print for Base64Word<'a> {
 impl<'a> Arbitrary<'a> for Base64Word<'a> {
     fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
         let enc: EmailCharset = u.arbitrary()?;
+        let lang = u.arbitrary()?;
         let content = arbitrary_vec_where(u, |c| is_bchar(*c))?;
         Ok(Base64Word {
             enc,
+            lang,
             content: Cow::Owned(content),
         })
     }
@@ -288,25 +448,34 @@ impl<'a> Arbitrary<'a> for Base64Word<'a> {
 #[cfg_attr(feature = "arbitrary", derive(Arbitrary))]
 pub struct QuotedWord<'a> {
     pub enc: EmailCharset,
+    // language of the text (RFC2231, 5)
+    pub lang: Option<MIMEAtom<'a>>,
     pub chunks: Vec<QuotedChunk<'a>>,
 }
 
 impl<'a> QuotedWord<'a> {
     pub fn data(&self) -> String {
-        self.chunks.iter().fold(String::new(), |mut acc, c| {
+        let mut d = WordDecoder::default();
+        self.decode_into(&mut d);
+        d.finish()
+    }
+
+    fn decode_into<'b>(&'b self, d: &mut WordDecoder<'b>) {
+        for c in &self.chunks {
             match c {
-                QuotedChunk::Safe(v) => {
-                    let (content, _) = encoding_rs::UTF_8.decode_without_bom_handling(v);
-                    acc.push_str(content.as_ref());
-                }
-                QuotedChunk::Space => acc.push(' '),
-                QuotedChunk::Encoded(v) => {
-                    let d = self.enc.decode(v.as_slice());
-                    acc.push_str(d.as_ref());
-                }
-            };
-            acc
-        })
+                QuotedChunk::Safe(v) if v.is_ascii() => d.push_octets(&self.enc, v),
+                QuotedChunk::Safe(v) => d.push_utf8(v),
+                QuotedChunk::Space => d.push_octets(&self.enc, b" "),
+                QuotedChunk::Encoded(v) => d.push_octets(&self.enc, v),
+            }
+        }
+    }
+}
+
+fn print_language(fmt: &mut impl Formatter, lang: &Option<MIMEAtom>) {
+    if let Some(lang) = lang {
+        fmt.write_bytes(b"*");
+        lang.print(fmt);
     }
 }
 
@@ -314,6 +483,7 @@ impl<'a> Print for QuotedWord<'a> {
     fn print(&self, fmt: &mut impl Formatter) {
         fmt.write_bytes(b"=?");
         fmt.write_bytes(self.enc.as_bytes());
+        print_language(fmt, &self.lang);
         fmt.write_bytes(b"?Q?");
         print_seq(fmt, &self.chunks, |_| ());
         fmt.write_bytes(b"?=");
@@ -324,6 +494,7 @@ impl<'a> Print for QuotedWord<'a> {
 impl<'a> FuzzEq for QuotedWord<'a> {
     fn fuzz_eq(&self, other: &Self) -> bool {
         self.enc.fuzz_eq(&other.enc)
+            && self.lang.fuzz_eq(&other.lang)
             && normalize_quoted_chunks(&self.chunks) == normalize_quoted_chunks(&other.chunks)
     }
 }
@@ -533,6 +704,7 @@ mod tests {
                 .1,
             EncodedWord(vec![EncodedWordToken::Base64(Base64Word {
                 enc: EmailCharset::from(b"iso-8859-1"),
+                lang: None,
                 content: b"SWYgeW91IGNhbiByZWFkIHRoaXMgeW8"[..].into(),
             })])
         );
@@ -581,6 +753,63 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_split_multibyte() {
+        // "é" is split between the two encoded words
+        assert_eq!(
+            encoded_word(Context::Phrase)(b"=?UTF-8?Q?Accus=C3?= =?utf-8?Q?=A9_de?=")
+                .unwrap()
+                .1
+                .data(),
+            "Accusé de".to_string(),
+        );
+
+        // "日本" in Shift_JIS, with "本" split between B and Q words
+        assert_eq!(
+            encoded_word(Context::Phrase)(b"=?Shift_JIS?B?k/qW?= =?Shift_JIS?Q?=7B?=")
+                .unwrap()
+                .1
+                .data(),
+            "日本".to_string(),
+        );
+
+        // octets in different charsets are not concatenated
+        assert_eq!(
+            encoded_word(Context::Phrase)(b"=?UTF-8?Q?=C3?= =?ISO-8859-1?Q?=A9?=")
+                .unwrap()
+                .1
+                .data(),
+            "\u{FFFD}©".to_string(),
+        );
+    }
+
+    #[test]
+    fn test_language() {
+        let (rest, w) = encoded_word(Context::Phrase)(b"=?US-ASCII*EN?Q?Keith_Moore?=").unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            w,
+            EncodedWord(vec![EncodedWordToken::Quoted(QuotedWord {
+                enc: EmailCharset::US_ASCII,
+                lang: Some(MIMEAtom(b"EN"[..].into())),
+                chunks: vec![
+                    QuotedChunk::Safe(b"Keith"[..].into()),
+                    QuotedChunk::Space,
+                    QuotedChunk::Safe(b"Moore"[..].into()),
+                ],
+            })])
+        );
+        assert_eq!(w.data(), "Keith Moore".to_string());
+        assert_eq!(
+            print_to_vec_with(|f| w.print(f)),
+            b"=?us-ascii*EN?Q?Keith_Moore?="
+        );
+
+        let (_, w) = encoded_word(Context::Phrase)(b"=?utf-8*fr-FR?B?w6k=?=").unwrap();
+        assert_eq!(w.data(), "é".to_string());
+        assert_eq!(print_to_vec_with(|f| w.print(f)), b"=?UTF-8*fr-FR?B?w6k=?=");
+    }
+
     #[test]
     fn test_encode() {
         let out = print_to_vec_with(|f| {
@@ -588,7 +817,7 @@ mod tests {
         });
         assert_eq!(
             String::from_utf8_lossy(&out),
-            "=?UTF-8?Q?Accus=C3=A9_de_r=C3=A9ception_=28affich=C3=A9=29?="
+            "=?UTF-8?B?QWNjdXPDqSBkZSByw6ljZXB0aW9uIChhZmZpY2jDqSk=?="
         );
 
         let out = print_to_vec_with(|f| {
@@ -608,7 +837,55 @@ mod tests {
         });
         assert_eq!(
             String::from_utf8_lossy(&out),
-            "=?UTF-8?Q?Accus=C3=A9_de_r=C3=A9ception_=28affich=C3=A9=29_Accus=C3=A9_?=\r\n =?UTF-8?Q?de_r=C3=A9ception_=28affich=C3=A9=29?="
+            "=?UTF-8?B?QWNjdXPDqSBkZSByw6ljZXB0aW9uIChhZmZpY2jDqSkgQWNjdXPDqSBkZSBy?=\r\n =?UTF-8?B?w6ljZXB0aW9uIChhZmZpY2jDqSk=?="
+        );
+    }
+
+    #[test]
+    fn test_encode_cjk() {
+        let text = "日本語の件名です。長い件名は複数のエンコードされた単語に分割されます";
+        let w = EncodedWord::from_chars(text.chars());
+        assert_eq!(w.data(), text);
+        assert!(w.0.len() > 1);
+        for tok in &w.0 {
+            // B is shorter than Q for CJK text
+            assert!(matches!(tok, EncodedWordToken::Base64(_)));
+            // each word is self-contained and within the length limit
+            let out = print_to_vec_with(|f| tok.print(f));
+            assert!(out.len() <= 75);
+            let (_, parsed) = encoded_word_token_base64(&out).unwrap();
+            assert!(!parsed.data().contains('\u{FFFD}'));
+        }
+    }
+
+    #[test]
+    fn test_encode_charset() {
+        let text = "日本語の件名です。長い件名は複数のエンコードされた単語に分割されます";
+        let w = EncodedWord::encode(text, encoding_rs::ISO_2022_JP).unwrap();
+        assert_eq!(w.data(), text);
+        let out = print_to_vec_with(|f| w.0[0].print(f));
+        assert!(out.starts_with(b"=?ISO-2022-JP?B?GyRC"));
+        for tok in &w.0 {
+            let out = print_to_vec_with(|f| tok.print(f));
+            assert!(out.len() <= 75);
+            // each word switches back to ASCII at its end
+            let EncodedWordToken::Base64(b) = tok else {
+                panic!()
+            };
+            let bytes = general_purpose::STANDARD_NO_PAD.decode(&b.content).unwrap();
+            assert!(bytes.ends_with(b"\x1b(B"));
+        }
+
+        let out = print_to_vec_with(|f| {
+            EncodedWord::encode("Accusé", encoding_rs::WINDOWS_1252)
+                .unwrap()
+                .print(f)
+        });
+        assert_eq!(out, b"=?windows-1252?Q?Accus=E9?=");
+
+        assert_eq!(
+            EncodedWord::encode("Smîth 日本", encoding_rs::ISO_2022_JP),
+            None
         );
     }
 
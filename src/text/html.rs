@@ -0,0 +1,379 @@
+use charset::Charset;
+use std::ops::Range;
+
+use crate::text::ascii;
+use crate::text::charset::EmailCharset;
+
+// Browsers only look for a `<meta>` charset declaration in the first 1024
+// bytes of a document (WHATWG HTML, 13.2.3.2).
+const PRESCAN_LEN: usize = 1024;
+
+/// Returns the charset declared by a `<meta charset>` or `<meta
+/// http-equiv="Content-Type">` element of the HTML document `body`, whose
+/// content transfer encoding must already be decoded.
+///
+/// This follows the algorithm browsers use to prescan documents (WHATWG
+/// HTML, "prescan a byte stream to determine its encoding"): only the first
+/// 1024 bytes are examined, comments are skipped, labels are resolved as
+/// browsers do, and UTF-16 declarations are read as UTF-8. Declarations of
+/// unknown charsets are ignored.
+pub fn meta_charset(body: &[u8]) -> Option<EmailCharset> {
+    meta_declaration(body).map(|(charset, _)| charset)
+}
+
+// Returns the charset declared by a `<meta>` element of `body` (see
+// `meta_charset`), along with the position of its label in `body`.
+fn meta_declaration(body: &[u8]) -> Option<(EmailCharset, Range<usize>)> {
+    let input = &body[..body.len().min(PRESCAN_LEN)];
+    let mut i = 0;
+    while i < input.len() {
+        let rest = &input[i..];
+        if rest.starts_with(b"<!--") {
+            // the "--" of "<!--" may also be part of the closing "-->"
+            i += find(&rest[2..], b"-->").map_or(rest.len(), |p| 2 + p + 3);
+        } else if starts_with_ignore_case(rest, b"<meta")
+            && rest
+                .get(5)
+                .is_some_and(|c| is_space(*c) || *c == ascii::SLASH)
+        {
+            i += 5;
+            if let Some(decl) = meta_element(input, &mut i) {
+                return Some(decl);
+            }
+        } else if rest.len() > 1
+            && rest[0] == b'<'
+            && (rest[1].is_ascii_alphabetic()
+                || (rest[1] == ascii::SLASH && rest.get(2).is_some_and(u8::is_ascii_alphabetic)))
+        {
+            // skip the tag name, then its attributes
+            i += rest
+                .iter()
+                .position(|c| is_space(*c) || *c == b'>')
+                .unwrap_or(rest.len());
+            while attribute(input, &mut i).is_some() {}
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            i += find(rest, b">").map_or(rest.len(), |p| p + 1);
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Where the charset of an HTML document was found, see [`html_charset`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CharsetSource {
+    /// A byte order mark at the start of the document.
+    Bom,
+    /// The `charset` parameter of the Content-Type field.
+    ContentType,
+    /// A `<meta>` element of the document.
+    Meta,
+}
+
+/// The charset of an HTML document, see [`html_charset`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct HtmlCharset {
+    /// The charset to decode the document with.
+    pub charset: EmailCharset,
+    pub source: CharsetSource,
+    /// The charset declared by a `<meta>` element of the document, if any.
+    pub meta: Option<EmailCharset>,
+}
+
+/// Decides which charset to decode the HTML document `body` with, given the
+/// charset `declared` by its Content-Type field. `body` must already have its
+/// content transfer encoding decoded.
+///
+/// This follows the precedence browsers use (WHATWG HTML, "encoding sniffing
+/// algorithm"): a byte order mark comes first, then the charset of the
+/// Content-Type field, then the one declared by a `<meta>` element (see
+/// [`meta_charset`]). An unknown Content-Type charset is ignored, and so is
+/// US-ASCII: it is also the charset of text parts that declare none
+/// (RFC 2045, 5.2), so it does not take precedence over a `<meta>` element.
+pub fn html_charset(declared: &EmailCharset, body: &[u8]) -> HtmlCharset {
+    let meta = meta_charset(body);
+    let (charset, source) = if let Some(bom) = bom_charset(body) {
+        (bom, CharsetSource::Bom)
+    } else if let (EmailCharset::US_ASCII | EmailCharset::Unknown(_), Some(meta)) =
+        (declared, &meta)
+    {
+        (meta.clone(), CharsetSource::Meta)
+    } else {
+        (declared.clone(), CharsetSource::ContentType)
+    };
+    HtmlCharset {
+        charset,
+        source,
+        meta,
+    }
+}
+
+/// Rewrites the charset declared by the `<meta>` element of the HTML document
+/// `body` (see [`meta_charset`]) to `charset`. Returns `None` if `body` has
+/// no such declaration.
+pub fn set_meta_charset(body: &[u8], charset: &EmailCharset) -> Option<Vec<u8>> {
+    let (_, range) = meta_declaration(body)?;
+    let mut out = Vec::with_capacity(body.len());
+    out.extend_from_slice(&body[..range.start]);
+    out.extend_from_slice(charset.as_bytes());
+    out.extend_from_slice(&body[range.end..]);
+    Some(out)
+}
+
+fn bom_charset(body: &[u8]) -> Option<EmailCharset> {
+    let (enc, _) = encoding_rs::Encoding::for_bom(body)?;
+    Some(EmailCharset::Charset(Charset::for_encoding(enc)))
+}
+
+// Reads the attributes of a `<meta>` element starting at `input[*i]`, and
+// returns the charset it declares and the position of its label, if any.
+fn meta_element(input: &[u8], i: &mut usize) -> Option<(EmailCharset, Range<usize>)> {
+    let mut names: Vec<Vec<u8>> = vec![];
+    let mut got_pragma = false;
+    let mut need_pragma = None;
+    let mut charset = None;
+
+    while let Some((name, value, range)) = attribute(input, i) {
+        if names.contains(&name) {
+            continue;
+        }
+        match name.as_slice() {
+            b"http-equiv" => got_pragma |= value.eq_ignore_ascii_case(b"content-type"),
+            b"content" if charset.is_none() => {
+                if let Some(label) = content_charset(&value) {
+                    let label_range = range.start + label.start..range.start + label.end;
+                    charset = Some(resolve(&value[label]).map(|c| (c, label_range)));
+                    need_pragma = Some(true);
+                }
+            }
+            b"charset" => {
+                charset = Some(resolve(&value).map(|c| (c, range)));
+                need_pragma = Some(false);
+            }
+            _ => (),
+        }
+        names.push(name);
+    }
+
+    match need_pragma {
+        Some(true) if !got_pragma => None,
+        Some(_) => charset.flatten(),
+        None => None,
+    }
+}
+
+// Resolves a charset label as browsers do.
+fn resolve(label: &[u8]) -> Option<EmailCharset> {
+    let enc = encoding_rs::Encoding::for_label(label)?;
+    let enc = if enc == encoding_rs::UTF_16BE || enc == encoding_rs::UTF_16LE {
+        encoding_rs::UTF_8
+    } else if enc == encoding_rs::X_USER_DEFINED {
+        encoding_rs::WINDOWS_1252
+    } else {
+        enc
+    };
+    Some(EmailCharset::Charset(Charset::for_encoding(enc)))
+}
+
+// Extracts the position of the charset label in the value of the `content`
+// attribute of a `<meta http-equiv="Content-Type">` element, e.g.
+// `text/html; charset=x`.
+fn content_charset(content: &[u8]) -> Option<Range<usize>> {
+    let mut i = 0;
+    loop {
+        i += find_ignore_case(&content[i..], b"charset")? + b"charset".len();
+        let rest = skip_spaces(&content[i..]);
+        let Some(rest) = rest.strip_prefix(b"=") else {
+            continue;
+        };
+        let rest = skip_spaces(rest);
+        let start = content.len() - rest.len();
+        return match rest.first() {
+            Some(q @ (b'"' | b'\'')) => {
+                let end = rest[1..].iter().position(|c| c == q)?;
+                Some(start + 1..start + 1 + end)
+            }
+            Some(_) => {
+                let end = rest
+                    .iter()
+                    .position(|c| is_space(*c) || *c == b';')
+                    .unwrap_or(rest.len());
+                Some(start..start + end)
+            }
+            None => None,
+        };
+    }
+}
+
+// Reads an attribute of an element starting at `input[*i]`, returning its
+// lowercased name, its value and the position of the value in `input`.
+// Returns `None` at the end of the element.
+fn attribute(input: &[u8], i: &mut usize) -> Option<(Vec<u8>, Vec<u8>, Range<usize>)> {
+    while *i < input.len() && (is_space(input[*i]) || input[*i] == ascii::SLASH) {
+        *i += 1;
+    }
+    match input.get(*i) {
+        None => return None,
+        Some(b'>') => {
+            *i += 1;
+            return None;
+        }
+        _ => (),
+    }
+
+    let mut name = vec![];
+    while let Some(&c) = input.get(*i) {
+        if (c == b'=' && !name.is_empty()) || is_space(c) || c == ascii::SLASH || c == b'>' {
+            break;
+        }
+        name.push(c.to_ascii_lowercase());
+        *i += 1;
+    }
+    while input.get(*i).is_some_and(|c| is_space(*c)) {
+        *i += 1;
+    }
+    if input.get(*i) != Some(&b'=') {
+        return Some((name, vec![], *i..*i));
+    }
+    *i += 1;
+    while input.get(*i).is_some_and(|c| is_space(*c)) {
+        *i += 1;
+    }
+
+    let mut value = vec![];
+    let start;
+    match input.get(*i) {
+        Some(q @ (b'"' | b'\'')) => {
+            *i += 1;
+            start = *i;
+            while let Some(c) = input.get(*i) {
+                if c == q {
+                    let range = start..*i;
+                    *i += 1;
+                    return Some((name, value, range));
+                }
+                value.push(c.to_ascii_lowercase());
+                *i += 1;
+            }
+        }
+        _ => {
+            start = *i;
+            while let Some(&c) = input.get(*i) {
+                if is_space(c) || c == b'>' {
+                    break;
+                }
+                value.push(c.to_ascii_lowercase());
+                *i += 1;
+            }
+        }
+    }
+    Some((name, value, start..*i))
+}
+
+fn is_space(c: u8) -> bool {
+    matches!(c, b'\t' | b'\n' | b'\x0c' | b'\r' | b' ')
+}
+
+fn skip_spaces(input: &[u8]) -> &[u8] {
+    let start = input
+        .iter()
+        .position(|c| !is_space(*c))
+        .unwrap_or(input.len());
+    &input[start..]
+}
+
+fn starts_with_ignore_case(input: &[u8], prefix: &[u8]) -> bool {
+    input.len() >= prefix.len() && input[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+fn find(input: &[u8], needle: &[u8]) -> Option<usize> {
+    input.windows(needle.len()).position(|w| w == needle)
+}
+
+fn find_ignore_case(input: &[u8], needle: &[u8]) -> Option<usize> {
+    input
+        .windows(needle.len())
+        .position(|w| w.eq_ignore_ascii_case(needle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_meta_charset() {
+        assert_eq!(
+            meta_charset(b"<html><head><meta charset=\"iso-8859-1\"></head>"),
+            Some(EmailCharset::from("windows-1252")),
+        );
+        assert_eq!(
+            meta_charset(
+                b"<META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=KOI8-R\">"
+            ),
+            Some(EmailCharset::from("koi8-r")),
+        );
+        assert_eq!(
+            meta_charset(b"<meta content='text/html; charset=utf-16'http-equiv=content-type />"),
+            Some(EmailCharset::utf8()),
+        );
+
+        // http-equiv is required with content
+        assert_eq!(
+            meta_charset(b"<meta content=\"text/html; charset=koi8-r\">"),
+            None
+        );
+        // declarations in comments, attribute values or unknown charsets
+        // are ignored
+        assert_eq!(
+            meta_charset(
+                b"<!-- <meta charset=koi8-r> --><p title=\"<meta charset=gbk>\">\
+                <meta charset=foo><meta charset=shift_jis>"
+            ),
+            Some(EmailCharset::from("shift_jis")),
+        );
+        // only the beginning of the document is examined
+        let mut body = vec![b' '; PRESCAN_LEN];
+        body.extend_from_slice(b"<meta charset=koi8-r>");
+        assert_eq!(meta_charset(&body), None);
+    }
+
+    #[test]
+    fn test_html_charset() {
+        let koi8 = EmailCharset::from("koi8-r");
+        let body = b"<meta http-equiv=Content-Type content='text/html; charset=koi8-r'>";
+
+        let c = html_charset(&EmailCharset::utf8(), body);
+        assert_eq!(c.charset, EmailCharset::utf8());
+        assert_eq!(c.source, CharsetSource::ContentType);
+        assert_eq!(c.meta, Some(koi8.clone()));
+
+        for declared in [EmailCharset::US_ASCII, EmailCharset::from("foo")] {
+            let c = html_charset(&declared, body);
+            assert_eq!(c.charset, koi8);
+            assert_eq!(c.source, CharsetSource::Meta);
+        }
+
+        let c = html_charset(&koi8, b"\xef\xbb\xbf<p>BOM</p>");
+        assert_eq!(c.charset, EmailCharset::utf8());
+        assert_eq!(c.source, CharsetSource::Bom);
+        assert_eq!(c.meta, None);
+    }
+
+    #[test]
+    fn test_set_meta_charset() {
+        assert_eq!(
+            set_meta_charset(
+                b"<meta http-equiv=Content-Type content='text/html; charset=koi8-r'>",
+                &EmailCharset::utf8()
+            )
+            .unwrap(),
+            b"<meta http-equiv=Content-Type content='text/html; charset=UTF-8'>"
+        );
+        assert_eq!(
+            set_meta_charset(b"<META CHARSET=\"Koi8-R\"><p>", &EmailCharset::utf8()).unwrap(),
+            b"<META CHARSET=\"UTF-8\"><p>"
+        );
+        assert_eq!(set_meta_charset(b"<p>", &EmailCharset::utf8()), None);
+    }
+}
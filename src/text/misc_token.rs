@@ -13,6 +13,7 @@ use std::borrow::Cow;
 #[cfg(feature = "tracing")]
 use tracing::warn;
 
+use crate::diagnostics::obsolete;
 use crate::i18n::ContainsUtf8;
 use crate::print::{print_seq, Formatter, Print, ToStringFromPrint};
 use crate::text::{
@@ -230,7 +231,10 @@ pub fn phrase_token(input: &[u8]) -> IResult<&[u8], PhraseToken<'_>> {
         // non-obs- syntax, thus ensuring that this AST can be safely
         // printed as-is.
         map(
-            delimited(opt(cfws), tag(&[ascii::PERIOD][..]), opt(cfws)),
+            obsolete(
+                "obs-phrase",
+                delimited(opt(cfws), tag(&[ascii::PERIOD][..]), opt(cfws)),
+            ),
             |_| {
                 PhraseToken::Word(Word::Quoted(QuotedString(vec![Cow::Owned(
                     ".".to_string(),
@@ -246,11 +250,56 @@ pub struct Phrase<'a>(pub Vec<PhraseToken<'a>>);
 
 impl<'a> Print for Phrase<'a> {
     fn print(&self, fmt: &mut impl Formatter) {
+        if let Some(cs) = fmt.encoded_word_charset().filter(|_| self.contains_utf8()) {
+            let mut encoded = self.clone();
+            encoded.encode_utf8(cs);
+            return print_seq(fmt, &encoded.0, Formatter::write_fws);
+        }
         print_seq(fmt, &self.0, Formatter::write_fws)
     }
 }
 
 impl<'a> Phrase<'a> {
+    // Rewrites the words of the phrase containing UTF-8 as encoded words in
+    // `cs` (see `EncodedWord::encode_or_utf8`). Consecutive words are merged
+    // into a single encoded word, as whitespace in between encoded words is
+    // ignored when decoding.
+    pub(crate) fn encode_utf8(&mut self, cs: &'static encoding_rs::Encoding) {
+        if !self.contains_utf8() {
+            return;
+        }
+        let mut out = vec![];
+        let mut run: Option<String> = None;
+        for tok in std::mem::take(&mut self.0) {
+            match tok {
+                PhraseToken::Word(w) if w.contains_utf8() => {
+                    let run = run.get_or_insert_with(String::new);
+                    if !run.is_empty() || matches!(out.last(), Some(PhraseToken::Encoded(_))) {
+                        run.push(' ')
+                    }
+                    run.extend(w.chars());
+                }
+                tok => {
+                    if let Some(mut run) = run.take() {
+                        if matches!(tok, PhraseToken::Encoded(_)) {
+                            run.push(' ')
+                        }
+                        out.push(PhraseToken::Encoded(encoding::EncodedWord::encode_or_utf8(
+                            &run, cs,
+                        )))
+                    }
+                    out.push(tok)
+                }
+            }
+        }
+        if let Some(run) = run {
+            out.push(PhraseToken::Encoded(encoding::EncodedWord::encode_or_utf8(
+                &run, cs,
+            )))
+        }
+        self.0 = out;
+    }
+
     // Merges consecutive Encoded tokens
     #[cfg(feature = "arbitrary")]
     fn normalize(&self) -> Self {
@@ -322,14 +371,17 @@ pub struct UtextToken<'a> {
 fn obs_utext_token<'a>(input: &'a [u8]) -> IResult<&'a [u8], UtextToken<'a>> {
     alt((
         take_utf8_while1(is_vchar).map(|s| UtextToken { txt: s, obs: false }),
-        take_while1(|c| is_obs_no_ws_ctl(c) || c == ascii::NULL)
-            // SAFETY: from the line above we know that `s` contains ASCII bytes
-            // (they satisfy either is_obs_no_ws_ctl or are NULL).
-            .map(|s| unsafe { str::from_utf8_unchecked(s) })
-            .map(|s| UtextToken {
-                txt: Cow::Borrowed(s),
-                obs: true,
-            }),
+        obsolete(
+            "obs-utext",
+            take_while1(|c| is_obs_no_ws_ctl(c) || c == ascii::NULL),
+        )
+        // SAFETY: from the line above we know that `s` contains ASCII bytes
+        // (they satisfy either is_obs_no_ws_ctl or are NULL).
+        .map(|s| unsafe { str::from_utf8_unchecked(s) })
+        .map(|s| UtextToken {
+            txt: Cow::Borrowed(s),
+            obs: true,
+        }),
     ))(input)
 }
 
@@ -418,6 +470,93 @@ pub struct Unstructured<'a>(pub Vec<UnstrToken<'a>>);
 
 impl<'a> Print for Unstructured<'a> {
     fn print(&self, fmt: &mut impl Formatter) {
+        if let Some(cs) = fmt.encoded_word_charset().filter(|_| self.contains_utf8()) {
+            let mut encoded = self.clone();
+            encoded.encode_utf8(cs);
+            return encoded.print_tokens(fmt);
+        }
+        self.print_tokens(fmt)
+    }
+}
+
+impl<'a> Unstructured<'a> {
+    // Rewrites the text of the field body containing UTF-8 as encoded words
+    // in `cs` (see `EncodedWord::encode_or_utf8`). Whitespace in between UTF-8
+    // words, and in between them and an existing encoded word, is made part of
+    // the new encoded word so that it is not lost when decoding.
+    pub(crate) fn encode_utf8(&mut self, cs: &'static encoding_rs::Encoding) {
+        if !self.contains_utf8() {
+            return;
+        }
+        let flush = |out: &mut Vec<UnstrToken>, run: String| {
+            out.push(UnstrToken::Encoded(encoding::EncodedWord::encode_or_utf8(
+                &run, cs,
+            )))
+        };
+        let mut out = vec![];
+        let mut run: Option<String> = None;
+        // whitespace following the current run
+        let mut ws: Vec<UnstrToken> = vec![];
+        for tok in std::mem::take(&mut self.0) {
+            match tok {
+                UnstrToken::Plain(txt, UnstrTxtKind::Txt)
+                    if txt.contains_utf8() || (run.is_some() && ws.is_empty()) =>
+                {
+                    let run = run.get_or_insert_with(|| {
+                        let n = out
+                            .iter()
+                            .rev()
+                            .take_while(|t| matches!(t, UnstrToken::Plain(_, UnstrTxtKind::Fws)))
+                            .count();
+                        let mut s = String::new();
+                        if n > 0 && matches!(out.iter().rev().nth(n), Some(UnstrToken::Encoded(_)))
+                        {
+                            for t in out.drain(out.len() - n..) {
+                                if let UnstrToken::Plain(w, _) = t {
+                                    s.push_str(&w)
+                                }
+                            }
+                        }
+                        s
+                    });
+                    for t in ws.drain(..) {
+                        if let UnstrToken::Plain(w, _) = t {
+                            run.push_str(&w)
+                        }
+                    }
+                    run.push_str(&txt);
+                }
+                UnstrToken::Plain(_, UnstrTxtKind::Fws) if run.is_some() => ws.push(tok),
+                // obsolete text is not printed
+                UnstrToken::Plain(_, UnstrTxtKind::Obs) if run.is_some() => (),
+                UnstrToken::Encoded(_) => {
+                    if let Some(mut run) = run.take() {
+                        for t in ws.drain(..) {
+                            if let UnstrToken::Plain(w, _) = t {
+                                run.push_str(&w)
+                            }
+                        }
+                        flush(&mut out, run);
+                    }
+                    out.push(tok)
+                }
+                tok => {
+                    if let Some(run) = run.take() {
+                        flush(&mut out, run);
+                        out.append(&mut ws);
+                    }
+                    out.push(tok)
+                }
+            }
+        }
+        if let Some(run) = run {
+            flush(&mut out, run);
+            out.append(&mut ws);
+        }
+        self.0 = out;
+    }
+
+    fn print_tokens(&self, fmt: &mut impl Formatter) {
         for i in 0..self.0.len() {
             let tok = &self.0[i];
 
@@ -431,9 +570,7 @@ impl<'a> Print for Unstructured<'a> {
             tok.print(fmt)
         }
     }
-}
 
-impl<'a> Unstructured<'a> {
     pub fn to_string_keep_obs(&self) -> String {
         let mut s = String::new();
         for tok in &self.0 {
@@ -618,6 +755,7 @@ mod tests {
                 UnstrToken::Plain(" "[..].into(), UnstrTxtKind::Fws),
                 UnstrToken::Encoded(EncodedWord(vec![EncodedWordToken::Quoted(QuotedWord {
                     enc: EmailCharset::utf8(),
+                    lang: None,
                     chunks: vec![QuotedChunk::Safe(b"foo"[..].into())],
                 })]))
             ])
@@ -2,6 +2,7 @@ pub mod ascii;
 pub mod boundary;
 pub mod charset;
 pub mod encoding;
+pub mod html;
 pub mod misc_token;
 pub mod quoted;
 pub mod recovery;
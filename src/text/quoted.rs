@@ -16,6 +16,7 @@ use std::ops::ControlFlow;
 #[cfg(feature = "tracing")]
 use tracing::warn;
 
+use crate::diagnostics::{self, obsolete, DiagnosticKind, Severity};
 use crate::i18n::ContainsUtf8;
 use crate::print::{Formatter, Print, ToStringFromPrint};
 use crate::text::ascii;
@@ -139,14 +140,20 @@ pub fn quoted_pair(input: &[u8]) -> IResult<&[u8], Option<&str>> {
                     // know that `b` contains a single ASCII character.
                     Some(unsafe { str::from_utf8_unchecked(s) })
                 } else {
-                    if !(b == ascii::NULL
-                        || is_obs_no_ws_ctl(b)
-                        || b == ascii::LF
-                        || b == ascii::CR)
-                    {
-                        #[cfg(feature = "tracing-recover")]
-                        warn!(byte = %bytes_to_trace_string(&[b]),
-                                  "invalid quoted pair")
+                    if b == ascii::NULL || is_obs_no_ws_ctl(b) || b == ascii::LF || b == ascii::CR {
+                        diagnostics::report(
+                            DiagnosticKind::Obsolete("obs-qp"),
+                            Severity::Warning,
+                            Some(s),
+                        )
+                    } else {
+                        diagnostics::diagnose!(
+                            "tracing-recover",
+                            (byte = %bytes_to_trace_string(&[b])),
+                            Recovered("invalid quoted pair"),
+                            Severity::Warning,
+                            None,
+                        )
                     }
                     None
                 }
@@ -192,7 +199,7 @@ fn is_obs_qtext(c: u8) -> bool {
 fn qcontent(input: &[u8]) -> IResult<&[u8], Option<Cow<'_, str>>> {
     alt((
         map(take_utf8_while1(is_strict_qtext), Some),
-        map(take_while1(is_obs_qtext), |_| None),
+        map(obsolete("obs-qtext", take_while1(is_obs_qtext)), |_| None),
         map(quoted_pair, |qp| qp.map(Cow::Borrowed)),
     ))(input)
 }
@@ -1,3 +1,5 @@
+use crate::diagnostics::{self, Severity};
+use crate::text::charset;
 #[cfg(feature = "tracing-recover")]
 use crate::utils::bytes_to_trace_string;
 use nom::{
@@ -6,12 +8,15 @@ use nom::{
     Err, IResult,
 };
 use std::borrow::Cow;
-#[cfg(feature = "tracing-recover")]
-use tracing::warn;
 
 /// Parses the input as a sequence of UTF-8 characters that satisfy the
-/// predicate `cond`. If invalid UTF-8 is encountered, it is replaced by
-/// [`char::REPLACEMENT_CHARACTER`] and parsing continues.
+/// predicate `cond`. If invalid UTF-8 is encountered, the invalid bytes are
+/// decoded with the fallback charset if one is set (typically raw 8-bit text
+/// in a legacy charset, see [`crate::message::ParseConfig`]), and the
+/// sequence stops before them if the decoded characters do not satisfy
+/// `cond`. Without a fallback charset, or if the bytes are not valid in it,
+/// invalid UTF-8 is replaced by [`char::REPLACEMENT_CHARACTER`] and parsing
+/// continues.
 ///
 /// This function is zero-copy if the parsed input is valid UTF-8, otherwise a
 /// string gets allocated because of the need to insert replacement characters.
@@ -63,47 +68,64 @@ where
             }
         }
 
-        // we have encountered some invalid UTF-8.
-        #[cfg(feature = "tracing-recover")]
-        warn!(input = %bytes_to_trace_string(i), "input contains invalid UTF-8");
-
-        let mut s = String::new();
+        // we have encountered some invalid UTF-8, at the start of `rest`.
         // SAFETY: `0..end` only contains bytes on which the iterator
         // returned Ok (same as above).
-        s.push_str(unsafe { str::from_utf8_unchecked(&i[0..i_len - rest.len()]) });
-        // push a replacement for the invalid UTF-8
-        s.push(char::REPLACEMENT_CHARACTER);
-
-        // read remaining valid and invalid text, pushing it to `s`.
-        let mut start = i_len - it.as_slice().len();
-        let mut rest = it.as_slice();
+        let mut s = String::from(unsafe { str::from_utf8_unchecked(&i[0..i_len - rest.len()]) });
+        let mut replaced = false;
         loop {
-            match it.next() {
-                Some(Ok(c)) if cond(c) => {
-                    rest = it.as_slice();
-                }
-                res => {
-                    // invalid utf8, end of input, or cond() returned false
-
-                    // start by pushing the valid chunk read so far
-                    let end = i_len - rest.len();
-                    // SAFETY: `start..end` only contains bytes on which the iterator
-                    // return Ok()
-                    s.push_str(unsafe { str::from_utf8_unchecked(&i[start..end]) });
-
-                    if let Some(Err(_)) = res {
-                        // if we read invalid utf8, push a replacement and continue
+            // find the end of the run of invalid UTF-8 sequences
+            let mut it = utf8_iter::ErrorReportingUtf8Chars::new(rest);
+            let mut invalid = 0;
+            let mut run_end = rest;
+            while let Some(Err(_)) = it.next() {
+                invalid += 1;
+                run_end = it.as_slice();
+            }
+            // only the invalid bytes are decoded with the fallback charset:
+            // valid UTF-8 around them is kept as is
+            match charset::decode_fallback(&rest[..rest.len() - run_end.len()]) {
+                Some(decoded) if decoded.chars().all(&cond) => s.push_str(&decoded),
+                // the decoded text is not part of the sequence: stop reading
+                Some(_) => break,
+                None => {
+                    for _ in 0..invalid {
                         s.push(char::REPLACEMENT_CHARACTER);
-                        start = i_len - it.as_slice().len();
-                        rest = it.as_slice();
-                    } else {
-                        // otherwise, stop reading
-                        break;
                     }
+                    replaced = true;
+                }
+            }
+            rest = run_end;
+
+            // read the valid text that follows
+            let mut it = utf8_iter::ErrorReportingUtf8Chars::new(rest);
+            let mut valid_end = rest;
+            let res = loop {
+                match it.next() {
+                    Some(Ok(c)) if cond(c) => valid_end = it.as_slice(),
+                    res => break res,
                 }
+            };
+            // SAFETY: this only contains bytes on which the iterator returned
+            // Ok
+            s.push_str(unsafe { str::from_utf8_unchecked(&rest[..rest.len() - valid_end.len()]) });
+            rest = valid_end;
+            if !matches!(res, Some(Err(_))) {
+                // end of input or cond() returned false; stop reading
+                break;
             }
         }
 
+        if replaced {
+            diagnostics::diagnose!(
+                "tracing-recover",
+                (input = %bytes_to_trace_string(i)),
+                Recovered("input contains invalid UTF-8"),
+                Severity::Warning,
+                None,
+            );
+        }
+
         if !s.is_empty() {
             Ok((rest, Cow::Owned(s)))
         } else {
@@ -1,7 +1,12 @@
+use crate::diagnostics::{self, Severity};
+use crate::i18n::ContainsUtf8;
+use crate::print::{Formatter, Print};
 use crate::text::ascii;
 use crate::text::encoding::{encoded_word_plain, Context};
 use crate::text::quoted::quoted_pair;
 use crate::text::utf8::{is_nonascii_or, space0_str, space1_str, take_utf8_while1};
+#[cfg(feature = "arbitrary")]
+use crate::{arbitrary_utils::arbitrary_string_where, fuzz_eq::FuzzEq};
 // NOTE: the spans emitted from this file are only enabled with
 // "tracing-recover" instead of the more general "tracing" feature like other
 // spans. This is a performance optimization: the combinators in this file are
@@ -10,19 +15,68 @@ use crate::text::utf8::{is_nonascii_or, space0_str, space1_str, take_utf8_while1
 // these spans when they are used, i.e. with "tracing-recover".
 #[cfg(feature = "tracing-recover")]
 use crate::utils::bytes_to_trace_string;
+#[cfg(feature = "arbitrary")]
+use arbitrary::Arbitrary;
+use bounded_static::ToStatic;
 use eml_codec_derives::instrument_input;
 use nom::{
     branch::alt,
     bytes::complete::tag,
     combinator::{map, opt, recognize},
     multi::many1,
-    sequence::{pair, tuple},
+    sequence::{pair, preceded, terminated, tuple},
     IResult, Parser,
 };
 use std::borrow::Cow;
+use std::fmt;
 #[cfg(feature = "tracing-recover")]
 use tracing::warn;
 
+/// Normalizes a message before parsing: bare LF line endings (as used by local
+/// storage formats on Unix) are turned into CRLF and, if `dot_stuffed` is
+/// set, the dot-stuffing of the SMTP DATA command is undone (RFC 5321,
+/// section 4.5.2).
+///
+/// Undoing dot-stuffing removes the leading period of lines starting with a
+/// period, and stops at the terminating line containing a single period
+/// (which is dropped along with anything after it).
+///
+/// The input is returned unchanged (and not copied) if there is nothing to
+/// normalize.
+pub fn normalize(input: &[u8], dot_stuffed: bool) -> Cow<'_, [u8]> {
+    let unchanged = input.split_inclusive(|c| *c == ascii::LF).all(|line| {
+        let bare_lf = line.ends_with(&[ascii::LF]) && !line.ends_with(ascii::CRLF);
+        let dot = dot_stuffed && line.starts_with(b".");
+        !(bare_lf || dot)
+    });
+    if unchanged {
+        return Cow::Borrowed(input);
+    }
+
+    let mut out = Vec::with_capacity(input.len());
+    for line in input.split_inclusive(|c| *c == ascii::LF) {
+        let content = match line.strip_suffix(&[ascii::LF]) {
+            Some(l) => l.strip_suffix(&[ascii::CR]).unwrap_or(l),
+            None => line,
+        };
+        let terminated = content.len() < line.len();
+        let content = if dot_stuffed {
+            match content {
+                b"." => break,
+                [b'.', rest @ ..] => rest,
+                _ => content,
+            }
+        } else {
+            content
+        };
+        out.extend_from_slice(content);
+        if terminated {
+            out.extend_from_slice(ascii::CRLF);
+        }
+    }
+    Cow::Owned(out)
+}
+
 /// Obsolete/Compatible CRLF
 ///
 /// Theoretically, all lines must end with \r\n
@@ -40,9 +94,13 @@ pub fn obs_crlf(input: &[u8]) -> IResult<&[u8], &str> {
             map(
                 alt((tag(&[ascii::LF]), tag(ascii::CRCRLF), tag(&[ascii::CR]))),
                 |input: &[u8]| {
-                    #[cfg(feature = "tracing-recover")]
-                    warn!(input = %unsafe { str::from_utf8_unchecked(input) },
-                          "best-effort line ending");
+                    diagnostics::diagnose!(
+                        "tracing-recover",
+                        (input = %unsafe { str::from_utf8_unchecked(input) }),
+                        Recovered("best-effort line ending"),
+                        Severity::Warning,
+                        Some(input),
+                    );
                     input
                 },
             ),
@@ -77,8 +135,13 @@ pub fn foldable_line(full_line: bool) -> impl Fn(&[u8]) -> IResult<&[u8], &[u8]>
                 }
                 (b'\r', Some(b'\n'), _) => return Ok((&input[i + 2..], &input[0..i])),
                 (_b /* \r | \n */, Some(b' ' | b'\t'), _) => {
-                    #[cfg(feature = "tracing-recover")]
-                    warn!(input = %bytes_to_trace_string(&[_b]), "foldable: best-effort line ending");
+                    diagnostics::diagnose!(
+                        "tracing-recover",
+                        (input = %bytes_to_trace_string(&[_b])),
+                        Recovered("foldable: best-effort line ending"),
+                        Severity::Warning,
+                        Some(&input[i..i + 1]),
+                    );
                     continue;
                 }
                 _ => return Ok((&input[i + 1..], &input[0..i])),
@@ -162,7 +225,7 @@ fn fold_marker(input: &[u8]) -> IResult<&[u8], Vec<&str>> {
 
 /// Folding White Space with Comment
 ///
-/// Note: we drop the comments for now...
+/// Note: comments are dropped; see `cfws_keep` and `comments_in` to keep them.
 ///
 /// ```abnf
 ///   ctext           =   %d33-39 /          ; Printable US-ASCII
@@ -237,6 +300,191 @@ pub fn comment_body(input: &[u8]) -> IResult<&[u8], ()> {
     }
 }
 
+/// The contents of a comment, without the enclosing parentheses and with
+/// line folding removed.
+///
+/// Nested comments and quoted pairs are kept verbatim.
+#[derive(Clone, PartialEq, ToStatic)]
+pub struct Comment<'a>(pub Cow<'a, [u8]>);
+
+impl<'a> Comment<'a> {
+    // `c` is the input recognized by `comment`
+    fn from_raw(c: &'a [u8]) -> Self {
+        let inner = &c[1..c.len() - 1];
+        if inner.iter().any(|b| *b == ascii::CR || *b == ascii::LF) {
+            Comment(Cow::Owned(
+                inner
+                    .iter()
+                    .copied()
+                    .filter(|b| *b != ascii::CR && *b != ascii::LF)
+                    .collect(),
+            ))
+        } else {
+            Comment(Cow::Borrowed(inner))
+        }
+    }
+}
+impl<'a> fmt::Debug for Comment<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_tuple("Comment")
+            .field(&String::from_utf8_lossy(&self.0))
+            .finish()
+    }
+}
+impl<'a> ContainsUtf8 for Comment<'a> {
+    fn contains_utf8(&self) -> bool {
+        !self.0.is_ascii()
+    }
+}
+impl<'a> Print for Comment<'a> {
+    fn print(&self, fmt: &mut impl Formatter) {
+        fmt.write_bytes(b"(");
+        // whitespace is written as folding whitespace, so that long comments
+        // can be folded
+        for chunk in self.0.chunk_by(|a, b| is_wsp(*a) == is_wsp(*b)) {
+            if is_wsp(chunk[0]) {
+                fmt.write_fws_bytes(chunk)
+            } else {
+                fmt.write_bytes(chunk)
+            }
+        }
+        fmt.write_bytes(b")");
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for Comment<'a> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let s =
+            arbitrary_string_where(u, |c| c == ' ' || (c.is_ascii() && is_restr_ctext(c as u8)))?;
+        Ok(Comment(Cow::Owned(s.into_bytes())))
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> FuzzEq for Comment<'a> {
+    fn fuzz_eq(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+/// The comments kept from a structured value (an address, a date...), split
+/// by their position relative to the value. Comments found inside the value
+/// cannot be printed back at their place and are kept in `after`.
+#[derive(Clone, ContainsUtf8, Debug, Default, PartialEq, ToStatic)]
+#[cfg_attr(feature = "arbitrary", derive(Arbitrary, FuzzEq))]
+pub struct Comments<'a> {
+    pub before: Vec<Comment<'a>>,
+    pub after: Vec<Comment<'a>>,
+}
+
+impl<'a> Comments<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.before.is_empty() && self.after.is_empty()
+    }
+
+    /// Keeps only the comments for which `f` returns true.
+    pub fn retain(&mut self, mut f: impl FnMut(&Comment<'a>) -> bool) {
+        self.before.retain(&mut f);
+        self.after.retain(f);
+    }
+}
+
+fn is_wsp(c: u8) -> bool {
+    c == ascii::SP || c == ascii::HT
+}
+
+/// A comment, keeping its contents.
+#[instrument_input("tracing-recover")]
+pub fn comment_keep(input: &[u8]) -> IResult<&[u8], Comment<'_>> {
+    map(recognize(comment), Comment::from_raw)(input)
+}
+
+/// Folding white space with comments, returning the comments.
+///
+/// ```abnf
+///   CFWS            =   (1*([FWS] comment) [FWS]) / FWS
+/// ```
+#[instrument_input("tracing-recover")]
+pub fn cfws_keep(input: &[u8]) -> IResult<&[u8], Vec<Comment<'_>>> {
+    alt((
+        terminated(many1(preceded(opt(fws), comment_keep)), opt(fws)),
+        map(fws, |_| vec![]),
+    ))(input)
+}
+
+/// Collects the comments appearing in `input`, which must be the text
+/// recognized by a structured field parser (e.g. an address or a date).
+/// Comments that come before any other token are put in `before`.
+///
+/// Parentheses inside quoted strings and domain literals are not comments
+/// and are skipped over. Scanning stops at the first unbalanced comment.
+pub fn comments_in(input: &[u8]) -> Comments<'_> {
+    let mut comments = Comments::default();
+    let mut leading = true;
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            ascii::LEFT_PAR => match comment_keep(&input[i..]) {
+                Ok((rest, c)) => {
+                    if leading {
+                        comments.before.push(c)
+                    } else {
+                        comments.after.push(c)
+                    }
+                    i = input.len() - rest.len();
+                    continue;
+                }
+                Err(_) => break,
+            },
+            ascii::SP | ascii::HT | ascii::CR | ascii::LF => {
+                i += 1;
+                continue;
+            }
+            ascii::BACKSLASH => i += 2,
+            ascii::DQUOTE => i += 1 + skip_delimited(&input[i + 1..], ascii::DQUOTE),
+            ascii::LEFT_BRACKET => i += 1 + skip_delimited(&input[i + 1..], ascii::RIGHT_BRACKET),
+            _ => i += 1,
+        }
+        leading = false;
+    }
+    comments
+}
+
+// Returns the length of `input` up to and including `end`, skipping over
+// quoted pairs.
+fn skip_delimited(input: &[u8], end: u8) -> usize {
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            ascii::BACKSLASH => i += 2,
+            c if c == end => return i + 1,
+            _ => i += 1,
+        }
+    }
+    input.len()
+}
+
+/// Prints the comments that come before a structured value, if the formatter
+/// is configured to reproduce comments.
+pub fn print_leading_comments(fmt: &mut impl Formatter, comments: &Comments) {
+    if fmt.print_comments() {
+        for c in &comments.before {
+            c.print(fmt);
+            fmt.write_fws();
+        }
+    }
+}
+
+/// Prints the comments that come after a structured value, if the formatter
+/// is configured to reproduce comments.
+pub fn print_comments(fmt: &mut impl Formatter, comments: &Comments) {
+    if fmt.print_comments() {
+        for c in &comments.after {
+            fmt.write_fws();
+            c.print(fmt);
+        }
+    }
+}
+
 #[instrument_input("tracing-recover")]
 pub fn ctext(input: &[u8]) -> IResult<&[u8], Cow<'_, str>> {
     take_utf8_while1(is_ctext)(input)
@@ -328,6 +576,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_cfws_keep() {
+        assert_eq!(
+            cfws_keep(b" (double (comment)\r\n is fun) (two) wouch"),
+            Ok((
+                &b"wouch"[..],
+                vec![
+                    Comment(b"double (comment) is fun"[..].into()),
+                    Comment(b"two"[..].into()),
+                ]
+            ))
+        );
+        assert_eq!(cfws_keep(b" \r\n wouch"), Ok((&b"wouch"[..], vec![])));
+        assert_eq!(
+            crate::print::tests::print_to_vec(Comment(b"a \\) b"[..].into())),
+            b"(a \\) b)"
+        );
+    }
+
     #[test]
     fn test_cfws_encoded_word() {
         assert_eq!(cfws(b"(=?US-ASCII?Q?Keith_Moore?=)"), Ok((&b""[..], ())),);
@@ -360,4 +627,21 @@ mod tests {
         assert!(foldable_line(true)(b"\r\n").is_err());
         assert!(foldable_line(true)(b"\n").is_err());
     }
+
+    #[test]
+    fn test_normalize() {
+        assert!(matches!(
+            normalize(b"a\r\nb\r\n", false),
+            Cow::Borrowed(b"a\r\nb\r\n")
+        ));
+        assert!(matches!(normalize(b"a\r\n.b\r\n", false), Cow::Borrowed(_)));
+        assert_eq!(
+            normalize(b"a\nb\r\n.c\nd", false).as_ref(),
+            b"a\r\nb\r\n.c\r\nd"
+        );
+        assert_eq!(
+            normalize(b"a\r\n..b\r\n.c\n.\r\nignored\r\n", true).as_ref(),
+            b"a\r\n.b\r\nc\r\n"
+        );
+    }
 }
@@ -1,14 +1,63 @@
-pub(crate) fn set_opt<T>(o: &mut Option<T>, x: T) -> bool {
-    match *o {
-        None => {
-            *o = Some(x);
-            true
+use bounded_static::ToStatic;
+
+use crate::header::DuplicatePolicy;
+
+// Applies a `DuplicatePolicy` to the fields of a header section that may only
+// appear once. `E` identifies such a field.
+#[derive(Clone, Debug, PartialEq, ToStatic)]
+pub(crate) struct SingleFields<E> {
+    policy: DuplicatePolicy,
+    // fields for which all occurrences are discarded (`DuplicatePolicy::Reject`)
+    rejected: Vec<E>,
+}
+
+impl<E> Default for SingleFields<E> {
+    fn default() -> Self {
+        Self {
+            policy: DuplicatePolicy::default(),
+            rejected: vec![],
+        }
+    }
+}
+
+// Why a single-valued field is discarded.
+pub(crate) enum Discard {
+    // only this occurrence of the field is discarded
+    Field,
+    // this occurrence and the one that was previously set are discarded
+    All,
+}
+
+impl<E: PartialEq> SingleFields<E> {
+    pub(crate) fn new(policy: DuplicatePolicy) -> Self {
+        Self {
+            policy,
+            rejected: vec![],
+        }
+    }
+
+    // Set `o` to `x`, the value of the field identified by `e`, according to
+    // the duplicate policy.
+    pub(crate) fn set<T>(&mut self, o: &mut Option<T>, x: T, e: E) -> Result<(), Discard> {
+        if self.rejected.contains(&e) {
+            return Err(Discard::Field);
+        }
+        match (o.is_some(), self.policy) {
+            (false, _) | (true, DuplicatePolicy::KeepLast) => {
+                *o = Some(x);
+                Ok(())
+            }
+            (true, DuplicatePolicy::KeepFirst) => Err(Discard::Field),
+            (true, DuplicatePolicy::Reject) => {
+                *o = None;
+                self.rejected.push(e);
+                Err(Discard::All)
+            }
         }
-        Some(_) => false,
     }
 }
 
-pub(crate) fn vec_filter_none_nonempty<T>(v: Vec<Option<T>>) -> Option<Vec<T>> {
+pub(crate) fn vec_flatten_nonempty<T, I: IntoIterator<Item = T>>(v: Vec<I>) -> Option<Vec<T>> {
     let v: Vec<T> = v.into_iter().flatten().collect();
     if v.is_empty() {
         None